@@ -1,78 +1,293 @@
 extern crate proc_macro;
 
-use syn::{parse_macro_input, Data, DeriveInput, Fields};
+use syn::spanned::Spanned;
+use syn::{
+    parse_macro_input, Attribute, Data, DeriveInput, Error, Fields, Ident, Index, Lit, Meta,
+    NestedMeta, Result,
+};
 
 #[macro_use]
 extern crate quote;
 
 use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
 
-#[proc_macro_derive(BitSerialize)]
-pub fn bit_serialize(tokens: TokenStream) -> TokenStream {
-    let input = parse_macro_input!(tokens as DeriveInput);
-    let name = input.ident;
-    let type_params = input.generics.type_params();
-    let type_params2 = input.generics.type_params();
+/// Options of a field given with `#[bit_serialize(...)]`
+#[derive(Default)]
+struct FieldOptions {
+    /// `skip`: the field isn't written
+    skip: bool,
+    /// `len = "field"`: the field must have as many elements as the named field says
+    len: Option<Ident>,
+}
 
-    let expanded = match input.data {
-        Data::Struct(data_struct) => {
-            if let Fields::Named(fields_named) = data_struct.fields {
-                let field_names = fields_named.named.into_iter().map(|el| el.ident.unwrap());
-                quote! {
-                    impl<
-                        W_derive_bit_serialize: std::io::Write,
-                        E_derive_bit_serialize: bitstream_io::Endianness,
-                        #(#type_params: BitSerialize<W_derive_bit_serialize, E_derive_bit_serialize>),*
-                    > BitSerialize<W_derive_bit_serialize, E_derive_bit_serialize> for #name<#(#type_params2),*>
-                    {
-                    fn bit_serialize(self, bw: &mut bitstream_io::BitWriter<W_derive_bit_serialize, E_derive_bit_serialize>) -> Result<(), std::io::Error> {
-                            #(
-                                self.#field_names.bit_serialize(bw)?;
-                            )*
-                            Ok(())
+fn field_options(attrs: &[Attribute]) -> Result<FieldOptions> {
+    let mut options = FieldOptions::default();
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident("bit_serialize")) {
+        let list = match attr.parse_meta()? {
+            Meta::List(list) => list,
+            meta => return Err(Error::new(meta.span(), "expected #[bit_serialize(...)]")),
+        };
+        for nested in list.nested {
+            match nested {
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("skip") => options.skip = true,
+                NestedMeta::Meta(Meta::NameValue(name_value)) if name_value.path.is_ident("len") => {
+                    match name_value.lit {
+                        Lit::Str(field) => options.len = Some(field.parse()?),
+                        lit => {
+                            return Err(Error::new(
+                                lit.span(),
+                                "expected #[bit_serialize(len = \"field\")]",
+                            ))
                         }
                     }
                 }
-            } else {
-                unimplemented!()
+                nested => {
+                    return Err(Error::new(
+                        nested.span(),
+                        "unknown bit_serialize option, expected skip or len",
+                    ))
+                }
             }
         }
-        Data::Enum(data_enum) => {
-            let mut variant_names_unnamed = Vec::new();
-            let mut variant_names_unit_discr = Vec::new();
-            let mut variant_values_unit_discr = Vec::new();
-            for variant in data_enum.variants {
-                if let Fields::Unnamed(_) = variant.fields {
-                    variant_names_unnamed.push(variant.ident);
-                } else if let Fields::Unit = variant.fields {
-                    if let Some((_, expr)) = variant.discriminant {
-                        variant_names_unit_discr.push(variant.ident);
-                        variant_values_unit_discr.push(expr);
-                    }
+    }
+    Ok(options)
+}
+
+/// Writes the fields of a struct in declaration order, checking `len` constraints first
+fn serialize_struct(fields: Fields) -> Result<TokenStream2> {
+    let mut checks = Vec::new();
+    let mut writes = Vec::new();
+    for (idx, field) in fields.into_iter().enumerate() {
+        let options = field_options(&field.attrs)?;
+        let member = match field.ident {
+            Some(ident) => quote!(#ident),
+            None => {
+                let idx = Index::from(idx);
+                quote!(#idx)
+            }
+        };
+        if options.skip {
+            continue;
+        }
+        if let Some(len) = options.len {
+            let name = member.to_string();
+            let len_name = len.to_string();
+            checks.push(quote! {
+                if self.#member.len() != self.#len.into_inner() as usize {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        format!(
+                            "{} has {} elements but {} is {}",
+                            #name,
+                            self.#member.len(),
+                            #len_name,
+                            self.#len.into_inner()
+                        ),
+                    ));
                 }
+            });
+        }
+        writes.push(quote!(self.#member.bit_serialize(bw)?;));
+    }
+    Ok(quote! {
+        #(#checks)*
+        #(#writes)*
+        Ok(())
+    })
+}
+
+/// Writes the fields of each variant, the discriminant of unit variants that have
+/// one and nothing for other unit variants, e.g. `Body::Empty`
+fn serialize_enum(data_enum: syn::DataEnum) -> TokenStream2 {
+    let mut arms = Vec::new();
+    for variant in data_enum.variants {
+        let ident = variant.ident;
+        match variant.fields {
+            Fields::Unnamed(fields) => {
+                let bindings: Vec<_> = (0..fields.unnamed.len())
+                    .map(|idx| format_ident!("field_{}", idx))
+                    .collect();
+                arms.push(quote! {
+                    Self::#ident(#(#bindings),*) => {
+                        #(#bindings.bit_serialize(bw)?;)*
+                        Ok(())
+                    }
+                });
             }
-            quote! {
-                    impl<
-                        W_derive_bit_serialize: std::io::Write,
-                        E_derive_bit_serialize: bitstream_io::Endianness,
-                        #(#type_params: BitSerialize<W_derive_bit_serialize, E_derive_bit_serialize>),*
-                    > BitSerialize<W_derive_bit_serialize, E_derive_bit_serialize> for #name<#(#type_params2),*>
-                    {
-                    fn bit_serialize(self, bw: &mut bitstream_io::BitWriter<W_derive_bit_serialize, E_derive_bit_serialize>) -> Result<(), std::io::Error> {
-                        match self {
-                            #(
-                                Self::#variant_names_unnamed(val) => val.bit_serialize(bw),
-                            )*
-                            #(
-                                Self::#variant_names_unit_discr => (#variant_values_unit_discr).bit_serialize(bw),
-                            )*
-                            _ => unimplemented!()
-                        }
+            Fields::Named(fields) => {
+                let bindings: Vec<_> = fields
+                    .named
+                    .into_iter()
+                    .map(|field| field.ident.unwrap())
+                    .collect();
+                arms.push(quote! {
+                    Self::#ident { #(#bindings),* } => {
+                        #(#bindings.bit_serialize(bw)?;)*
+                        Ok(())
                     }
-                }
+                });
             }
+            Fields::Unit => match variant.discriminant {
+                Some((_, expr)) => arms.push(quote!(Self::#ident => (#expr).bit_serialize(bw),)),
+                None => arms.push(quote!(Self::#ident => Ok(()),)),
+            },
+        }
+    }
+    quote! {
+        match self {
+            #(#arms)*
+        }
+    }
+}
+
+#[proc_macro_derive(BitSerialize, attributes(bit_serialize))]
+pub fn bit_serialize(tokens: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(tokens as DeriveInput);
+    let name = input.ident;
+    let type_params = input.generics.type_params();
+    let type_params2 = input.generics.type_params();
+
+    let body = match input.data {
+        Data::Struct(data_struct) => serialize_struct(data_struct.fields),
+        Data::Enum(data_enum) => Ok(serialize_enum(data_enum)),
+        Data::Union(data_union) => Err(Error::new(
+            data_union.union_token.span,
+            "BitSerialize can't be derived for unions",
+        )),
+    };
+    let body = match body {
+        Ok(body) => body,
+        Err(err) => return err.to_compile_error().into(),
+    };
+    let expanded = quote! {
+        impl<
+            W_derive_bit_serialize: std::io::Write,
+            E_derive_bit_serialize: bitstream_io::Endianness,
+            #(#type_params: BitSerialize<W_derive_bit_serialize, E_derive_bit_serialize>),*
+        > BitSerialize<W_derive_bit_serialize, E_derive_bit_serialize> for #name<#(#type_params2),*>
+        {
+            #[allow(unused_variables)]
+            fn bit_serialize(self, bw: &mut bitstream_io::BitWriter<W_derive_bit_serialize, E_derive_bit_serialize>) -> Result<(), std::io::Error> {
+                #body
+            }
+        }
+    };
+    TokenStream::from(expanded)
+}
+
+/// Sums the sizes of the fields that are written
+fn size_struct(fields: Fields) -> Result<TokenStream2> {
+    let mut members = Vec::new();
+    for (idx, field) in fields.into_iter().enumerate() {
+        if field_options(&field.attrs)?.skip {
+            continue;
+        }
+        members.push(match field.ident {
+            Some(ident) => quote!(#ident),
+            None => {
+                let idx = Index::from(idx);
+                quote!(#idx)
+            }
+        });
+    }
+    Ok(quote!(0 #(+ self.#members.bit_size())*))
+}
+
+/// Sizes of the fields of the variant at hand, or of the discriminant of unit variants
+fn size_enum(data_enum: syn::DataEnum) -> TokenStream2 {
+    let mut arms = Vec::new();
+    for variant in data_enum.variants {
+        let ident = variant.ident;
+        match variant.fields {
+            Fields::Unnamed(fields) => {
+                let bindings: Vec<_> = (0..fields.unnamed.len())
+                    .map(|idx| format_ident!("field_{}", idx))
+                    .collect();
+                arms.push(quote! {
+                    Self::#ident(#(#bindings),*) => 0 #(+ #bindings.bit_size())*,
+                });
+            }
+            Fields::Named(fields) => {
+                let bindings: Vec<_> = fields
+                    .named
+                    .into_iter()
+                    .map(|field| field.ident.unwrap())
+                    .collect();
+                arms.push(quote! {
+                    Self::#ident { #(#bindings),* } => 0 #(+ #bindings.bit_size())*,
+                });
+            }
+            Fields::Unit => match variant.discriminant {
+                Some((_, expr)) => arms.push(quote!(Self::#ident => (#expr).bit_size(),)),
+                None => arms.push(quote!(Self::#ident => 0,)),
+            },
+        }
+    }
+    quote! {
+        match self {
+            #(#arms)*
+        }
+    }
+}
+
+#[proc_macro_derive(BitSize, attributes(bit_serialize))]
+pub fn bit_size(tokens: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(tokens as DeriveInput);
+    let name = input.ident;
+    let type_params = input.generics.type_params();
+    let type_params2 = input.generics.type_params();
+
+    let body = match input.data {
+        Data::Struct(data_struct) => size_struct(data_struct.fields),
+        Data::Enum(data_enum) => Ok(size_enum(data_enum)),
+        Data::Union(data_union) => Err(Error::new(
+            data_union.union_token.span,
+            "BitSize can't be derived for unions",
+        )),
+    };
+    let body = match body {
+        Ok(body) => body,
+        Err(err) => return err.to_compile_error().into(),
+    };
+    let expanded = quote! {
+        impl<#(#type_params: BitSize),*> BitSize for #name<#(#type_params2),*> {
+            #[allow(unused_variables)]
+            fn bit_size(&self) -> usize {
+                #body
+            }
+        }
+    };
+    TokenStream::from(expanded)
+}
+
+#[proc_macro_derive(FixedBitSize, attributes(bit_serialize))]
+pub fn fixed_bit_size(tokens: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(tokens as DeriveInput);
+    let name = input.ident;
+    let type_params = input.generics.type_params();
+    let type_params2 = input.generics.type_params();
+
+    let fields = match input.data {
+        Data::Struct(data_struct) => data_struct.fields,
+        _ => {
+            return Error::new(name.span(), "FixedBitSize can only be derived for structs")
+                .to_compile_error()
+                .into()
+        }
+    };
+    let mut field_types = Vec::new();
+    for field in fields {
+        match field_options(&field.attrs) {
+            Ok(options) if options.skip => (),
+            Ok(_) => field_types.push(field.ty),
+            Err(err) => return err.to_compile_error().into(),
+        }
+    }
+    let expanded = quote! {
+        impl<#(#type_params: FixedBitSize),*> FixedBitSize for #name<#(#type_params2),*> {
+            const BIT_SIZE: usize = 0 #(+ <#field_types as FixedBitSize>::BIT_SIZE)*;
         }
-        Data::Union(_) => unimplemented!(),
     };
     TokenStream::from(expanded)
 }
@@ -0,0 +1,111 @@
+#[macro_use]
+extern crate serde_derive;
+
+use ptp::capture::{self, Filter};
+use ptp::protocol::parser::parse_ptp_message;
+use ptp::protocol::types::message::Received;
+
+use docopt::Docopt;
+
+const USAGE: &str = "
+Rust PTP capture decoder
+
+Prints the PTP messages over UDPv4, UDPv6 or IEEE 802.3 in a pcap or pcapng file.
+
+Usage:
+  ptp-dump [options] <file>
+  ptp-dump (-h | --help)
+
+Options:
+  -h --help                       Show this screen.
+  -d <n> --domain=<n>             Only show messages of domain
+  -t <type> --type=<type>         Only show messages of type, e.g. Sync or Delay_Req
+  -s <id> --source=<id>           Only show messages of sourcePortIdentity,
+                                  as clockIdentity-portNumber or clockIdentity
+  -q --quiet                      Print one line per message
+  --json                          Print messages as JSON, one per line
+";
+
+#[derive(Debug, Deserialize)]
+struct Args {
+    arg_file: String,
+    flag_domain: Option<u8>,
+    flag_type: Option<String>,
+    flag_source: Option<String>,
+    flag_quiet: bool,
+    flag_json: bool,
+}
+
+fn filter(args: &Args) -> Result<Filter, String> {
+    let mut filter = Filter {
+        domain: args.flag_domain,
+        ..Filter::default()
+    };
+    if let Some(message_type) = &args.flag_type {
+        filter.set_message_type(message_type)?;
+    }
+    if let Some(source) = &args.flag_source {
+        filter.set_source(source)?;
+    }
+    Ok(filter)
+}
+
+fn main() {
+    let args: Args = Docopt::new(USAGE)
+        .and_then(|d| d.deserialize())
+        .unwrap_or_else(|e| e.exit());
+
+    let filter = filter(&args).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        std::process::exit(1)
+    });
+    let packets = capture::read_file(&args.arg_file).unwrap_or_else(|err| {
+        eprintln!("{}: {}", args.arg_file, err);
+        std::process::exit(1)
+    });
+
+    for (number, packet) in packets.iter().enumerate() {
+        let datagram = match capture::extract_ptp(packet.link_type, &packet.data) {
+            Some(datagram) => datagram,
+            None => continue,
+        };
+        let timestamp = format!(
+            "{}.{:09}",
+            packet.timestamp.as_secs(),
+            packet.timestamp.subsec_nanos()
+        );
+        let message = match parse_ptp_message(datagram.payload) {
+            Ok((_, message)) => message,
+            Err(err) => {
+                eprintln!(
+                    "{} #{}: couldn't parse message: {:?}",
+                    timestamp,
+                    number + 1,
+                    err
+                );
+                continue;
+            }
+        };
+        if !filter.matches(&message) {
+            continue;
+        }
+
+        if args.flag_json {
+            let received = Received {
+                source: datagram.source.to_string(),
+                timestamp: packet.timestamp,
+                message: &message,
+            };
+            println!("{}", received.to_json());
+            continue;
+        }
+
+        println!(
+            "{} {} > {} {}",
+            timestamp, datagram.source, datagram.destination, message
+        );
+        if !args.flag_quiet {
+            println!("{:#?}", message);
+        }
+    }
+}
@@ -0,0 +1,272 @@
+#[macro_use]
+extern crate serde_derive;
+
+use std::io::{self, BufRead};
+use std::net::{SocketAddr, UdpSocket};
+use std::os::unix::net::UnixDatagram;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use ptp::management::{self, Request};
+use ptp::protocol::parser::parse_ptp_message;
+use ptp::protocol::types::derived::{clock_identity_from_mac, ClockIdentity, PortIdentity};
+use ptp::protocol::types::enums::{values, Enumeration};
+use ptp::protocol::types::message::{body, Body, Message, Tlv};
+use ptp::protocol::types::primitive::int::Int;
+use ptp::transport::{self, GENERAL_PORT, PTP_PRIMARY_MULTICAST};
+
+use docopt::Docopt;
+
+const USAGE: &str = "
+Rust PTP management client
+
+Sends each command, e.g. 'GET CURRENT_DATA_SET' or 'SET PRIORITY1 100', and
+prints the responses. Commands are read from standard input when none are given.
+
+Usage:
+  ptp-pmc [options] [<command>...]
+  ptp-pmc (-h | --help)
+
+Options:
+  -h --help                       Show this screen.
+  -i <iface> --interface=<iface>  Use UDPv4 multicast on network interface
+                                  instead of the daemon's Unix socket
+  -s <path> --uds=<path>          Unix socket of the daemon [default: /var/run/ptp]
+  -d <n> --domain=<n>             Domain number [default: 0]
+  -b <n> --boundary-hops=<n>      Boundary hops [default: 1]
+  -t <ms> --timeout=<ms>          Time to wait for responses [default: 1000]
+";
+
+#[derive(Debug, Deserialize)]
+struct Args {
+    arg_command: Vec<String>,
+    flag_interface: Option<String>,
+    flag_uds: String,
+    flag_domain: u8,
+    flag_boundary_hops: u8,
+    flag_timeout: u64,
+}
+
+enum Channel {
+    Udp(UdpSocket),
+    Uds { socket: UnixDatagram, path: PathBuf },
+}
+
+impl Channel {
+    fn send(&self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Channel::Udp(socket) => socket.send_to(
+                buf,
+                SocketAddr::new(PTP_PRIMARY_MULTICAST.into(), GENERAL_PORT),
+            ),
+            Channel::Uds { socket, .. } => socket.send(buf),
+        }
+    }
+
+    fn recv(&self, buf: &mut [u8], timeout: Duration) -> io::Result<usize> {
+        match self {
+            Channel::Udp(socket) => {
+                socket.set_read_timeout(Some(timeout))?;
+                socket.recv(buf)
+            }
+            Channel::Uds { socket, .. } => {
+                socket.set_read_timeout(Some(timeout))?;
+                socket.recv(buf)
+            }
+        }
+    }
+}
+
+impl Drop for Channel {
+    fn drop(&mut self) {
+        if let Channel::Uds { path, .. } = self {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+/// Identity telling this client's responses apart from other clients' on the
+/// host: the PID's low 16 bits are the portNumber and its high bits replace
+/// octets 3 and 4 of `clock_identity`
+fn process_identity(mut clock_identity: ClockIdentity) -> PortIdentity {
+    let pid = std::process::id();
+    clock_identity[3] = Int::new((pid >> 24) as u8);
+    clock_identity[4] = Int::new((pid >> 16) as u8);
+    PortIdentity {
+        clockIdentity: clock_identity,
+        portNumber: Int::new(pid as u16),
+    }
+}
+
+fn open(args: &Args) -> io::Result<(Channel, PortIdentity)> {
+    match &args.flag_interface {
+        Some(iface) => {
+            let socket = transport::management_socket(iface, transport::iface_addr(iface)?)?;
+            let identity =
+                process_identity(clock_identity_from_mac(transport::mac_address(iface)?));
+            Ok((Channel::Udp(socket), identity))
+        }
+        None => {
+            // A directory the user can write to, unlike the daemon's /var/run
+            let path = std::env::var_os("XDG_RUNTIME_DIR")
+                .filter(|dir| !dir.is_empty())
+                .map(PathBuf::from)
+                .unwrap_or_else(std::env::temp_dir)
+                .join(format!("ptp-pmc.{}", std::process::id()));
+            let _ = std::fs::remove_file(&path);
+            let socket = UnixDatagram::bind(&path)?;
+            let channel = Channel::Uds { socket, path };
+            if let Channel::Uds { socket, .. } = &channel {
+                socket.connect(&args.flag_uds).map_err(|err| {
+                    io::Error::new(err.kind(), format!("{}: {}", args.flag_uds, err))
+                })?;
+            }
+            Ok((channel, process_identity([Int::new(0); 8])))
+        }
+    }
+}
+
+fn action_name(action: values::ActionField) -> &'static str {
+    match action {
+        values::ActionField::COMMAND => "COMMAND",
+        action => action.name(),
+    }
+}
+
+fn print_response(message: &Message, management: &body::Management) {
+    let action = match management.actionField {
+        Enumeration::Enum(action) => action_name(action).to_string(),
+        Enumeration::Unknown(action) => format!("{:#x}", action.into_inner()),
+    };
+    let source = message.header.sourcePortIdentity;
+    let id_name = |id| match id {
+        Enumeration::Enum(id) => values::ManagementId::name(id).to_string(),
+        Enumeration::Unknown(id) => format!("{:#06x}", Int::into_inner(id)),
+    };
+    for tlv in &management.tlvs {
+        match tlv {
+            Tlv::Management(tlv) => {
+                println!(
+                    "\t{} seq {} {} MANAGEMENT {}",
+                    source,
+                    message.header.sequenceId.into_inner(),
+                    action,
+                    id_name(tlv.managementId)
+                );
+                for (name, value) in management::describe(&tlv.dataField) {
+                    println!("\t\t{:<40}{}", name, value);
+                }
+            }
+            Tlv::Management_Error_Status(tlv) => {
+                println!(
+                    "\t{} seq {} {} MANAGEMENT_ERROR_STATUS {}",
+                    source,
+                    message.header.sequenceId.into_inner(),
+                    action,
+                    id_name(tlv.managementId)
+                );
+                let error = match tlv.managementErrorId {
+                    Enumeration::Enum(error) => error.name().to_string(),
+                    Enumeration::Unknown(error) => format!("{:#06x}", error.into_inner()),
+                };
+                println!("\t\t{:<40}{}", "error", error);
+                if let Some(text) = &tlv.displayData {
+                    println!("\t\t{:<40}{}", "displayData", text);
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Sends a request and prints responses until the timeout expires
+fn run(channel: &Channel, args: &Args, source: PortIdentity, sequence_id: u16, request: Request) {
+    println!(
+        "sending: {} {}",
+        action_name(request.action),
+        request.id.name()
+    );
+    let message = management::management(
+        source,
+        management::all_ports(),
+        args.flag_domain,
+        sequence_id,
+        args.flag_boundary_hops,
+        request.action,
+        vec![request.tlv()],
+    );
+    if let Err(err) = channel.send(&message.to_bytes()) {
+        eprintln!("Couldn't send request: {}", err);
+        return;
+    }
+
+    let deadline = Instant::now() + Duration::from_millis(args.flag_timeout);
+    let mut buf = [0; 1500];
+    while let Some(timeout) = deadline.checked_duration_since(Instant::now()) {
+        if timeout == Duration::from_secs(0) {
+            break;
+        }
+        let len = match channel.recv(&mut buf, timeout) {
+            Ok(len) => len,
+            Err(err)
+                if err.kind() == io::ErrorKind::WouldBlock
+                    || err.kind() == io::ErrorKind::TimedOut =>
+            {
+                break
+            }
+            Err(err) => {
+                eprintln!("Couldn't receive response: {}", err);
+                break;
+            }
+        };
+        let response = match parse_ptp_message(&buf[..len]) {
+            Ok((_, response)) => response,
+            Err(_) => continue,
+        };
+        if response.header.sequenceId.into_inner() != sequence_id
+            || response.header.sourcePortIdentity == source
+        {
+            continue;
+        }
+        if let Body::Management(management) = &response.body {
+            // Responses to other clients also reach a multicast socket
+            if management.targetPortIdentity != source {
+                continue;
+            }
+            if matches!(
+                management.actionField,
+                Enumeration::Enum(values::ActionField::RESPONSE)
+                    | Enumeration::Enum(values::ActionField::ACKNOWLEDGE)
+            ) {
+                print_response(&response, management);
+            }
+        }
+    }
+}
+
+fn main() {
+    let args: Args = Docopt::new(USAGE)
+        .and_then(|d| d.deserialize())
+        .unwrap_or_else(|e| e.exit());
+
+    let (channel, source) = open(&args).unwrap_or_else(|err| {
+        eprintln!("Couldn't open management channel: {}", err);
+        std::process::exit(1)
+    });
+
+    let commands: Vec<String> = if args.arg_command.is_empty() {
+        io::stdin().lock().lines().map_while(Result::ok).collect()
+    } else {
+        args.arg_command.clone()
+    };
+    let mut sequence_id = 0u16;
+    for command in commands.iter().filter(|command| !command.trim().is_empty()) {
+        match command.parse::<Request>() {
+            Ok(request) => {
+                run(&channel, &args, source, sequence_id, request);
+                sequence_id = sequence_id.wrapping_add(1);
+            }
+            Err(err) => eprintln!("{}: {}", command.trim(), err),
+        }
+    }
+}
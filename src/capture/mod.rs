@@ -0,0 +1,487 @@
+use crate::protocol::types::derived::{parse_clock_identity, ClockIdentity};
+use crate::protocol::types::enums::{values, Enumeration};
+use crate::protocol::types::message::Message;
+use crate::transport::{EVENT_PORT, GENERAL_PORT};
+
+use std::convert::TryInto;
+use std::fmt;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::str::FromStr;
+use std::time::Duration;
+
+pub mod writer;
+
+#[cfg(test)]
+mod tests;
+
+pub const LINKTYPE_ETHERNET: u16 = 1;
+pub const LINKTYPE_RAW: u16 = 101;
+pub const LINKTYPE_LINUX_SLL: u16 = 113;
+pub const LINKTYPE_IPV4: u16 = 228;
+pub const LINKTYPE_IPV6: u16 = 229;
+
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_IPV6: u16 = 0x86DD;
+const ETHERTYPE_VLAN: u16 = 0x8100;
+const ETHERTYPE_PTP: u16 = 0x88F7;
+const IPPROTO_UDP: u8 = 17;
+
+const PCAP_MAGIC_USEC: u32 = 0xA1B2_C3D4;
+const PCAP_MAGIC_NSEC: u32 = 0xA1B2_3C4D;
+const PCAPNG_SECTION_HEADER: u32 = 0x0A0D_0D0A;
+const PCAPNG_BYTE_ORDER_MAGIC: u32 = 0x1A2B_3C4D;
+const PCAPNG_INTERFACE_DESCRIPTION: u32 = 0x0000_0001;
+const PCAPNG_ENHANCED_PACKET: u32 = 0x0000_0006;
+const PCAPNG_OPTION_END: u16 = 0;
+const PCAPNG_OPT_COMMENT: u16 = 1;
+const PCAPNG_IF_NAME: u16 = 2;
+const PCAPNG_IF_TSRESOL: u16 = 9;
+const PCAPNG_EPB_FLAGS: u16 = 2;
+/// Comments of packets whose timestamp isn't a kernel timestamp
+const ESTIMATED_TIMESTAMP: &str = "system time after sending";
+const ESTIMATED_RECEIVE_TIMESTAMP: &str = "system time after receiving";
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Format(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "{}", err),
+            Error::Format(err) => write!(f, "invalid capture: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+fn truncated(what: &str) -> Error {
+    Error::Format(format!("truncated {}", what))
+}
+
+/// Whether a packet was received or transmitted by the capturing host
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    Unknown,
+    Inbound,
+    Outbound,
+}
+
+/// Captured frame with its capture timestamp since the Unix epoch
+#[derive(Debug, Clone, PartialEq)]
+pub struct Packet {
+    pub timestamp: Duration,
+    /// Index of the capturing interface, always 0 in pcap files
+    pub interface: u32,
+    pub direction: Direction,
+    pub link_type: u16,
+    pub data: Vec<u8>,
+    /// Timestamp taken after sending or receiving instead of by the kernel,
+    /// written as a comment
+    pub estimated: bool,
+}
+
+/// Reads integers of a capture file in the byte order given by its magic number
+#[derive(Debug, Clone, Copy)]
+struct Endian {
+    big: bool,
+}
+
+const BIG: Endian = Endian { big: true };
+const LITTLE: Endian = Endian { big: false };
+
+impl Endian {
+    fn u16(self, data: &[u8], offset: usize) -> Option<u16> {
+        let bytes = data.get(offset..offset + 2)?.try_into().ok()?;
+        Some(if self.big {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        })
+    }
+
+    fn u32(self, data: &[u8], offset: usize) -> Option<u32> {
+        let bytes = data.get(offset..offset + 4)?.try_into().ok()?;
+        Some(if self.big {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        })
+    }
+}
+
+/// Converts a timestamp in units of 10^-`exponent` or 2^-`exponent` seconds
+fn timestamp(units: u64, exponent: u8, binary: bool) -> Duration {
+    let per_second: u128 = if binary {
+        1 << exponent.min(64)
+    } else {
+        10u128.pow(exponent.min(19).into())
+    };
+    let units = u128::from(units);
+    Duration::new(
+        (units / per_second) as u64,
+        (units % per_second * 1_000_000_000 / per_second) as u32,
+    )
+}
+
+/// Reads all packets of a pcap or pcapng file
+pub fn read_file<P: AsRef<std::path::Path>>(path: P) -> Result<Vec<Packet>, Error> {
+    parse(&std::fs::read(path)?)
+}
+
+/// Parses a pcap or pcapng capture, detected by its magic number
+pub fn parse(data: &[u8]) -> Result<Vec<Packet>, Error> {
+    let magic = BIG.u32(data, 0).ok_or_else(|| truncated("file header"))?;
+    match magic {
+        PCAPNG_SECTION_HEADER => parse_pcapng(data),
+        _ => parse_pcap(data),
+    }
+}
+
+fn parse_pcap(data: &[u8]) -> Result<Vec<Packet>, Error> {
+    let (endian, nanoseconds) = match BIG.u32(data, 0) {
+        Some(PCAP_MAGIC_USEC) => (BIG, false),
+        Some(PCAP_MAGIC_NSEC) => (BIG, true),
+        Some(magic) if magic.swap_bytes() == PCAP_MAGIC_USEC => (LITTLE, false),
+        Some(magic) if magic.swap_bytes() == PCAP_MAGIC_NSEC => (LITTLE, true),
+        _ => return Err(Error::Format("unknown file format".to_string())),
+    };
+    let link_type = endian
+        .u32(data, 20)
+        .ok_or_else(|| truncated("pcap header"))? as u16;
+
+    let mut packets = Vec::new();
+    let mut offset = 24;
+    while offset < data.len() {
+        let header = (
+            endian.u32(data, offset),
+            endian.u32(data, offset + 4),
+            endian.u32(data, offset + 8),
+        );
+        let (seconds, fraction, length) = match header {
+            (Some(seconds), Some(fraction), Some(length)) => (seconds, fraction, length as usize),
+            _ => return Err(truncated("packet header")),
+        };
+        let start = offset + 16;
+        let frame = data
+            .get(start..start + length)
+            .ok_or_else(|| truncated("packet"))?;
+        let fraction = if nanoseconds {
+            fraction
+        } else {
+            fraction.saturating_mul(1000)
+        };
+        packets.push(Packet {
+            timestamp: Duration::new(seconds.into(), 0) + Duration::from_nanos(fraction.into()),
+            interface: 0,
+            direction: Direction::Unknown,
+            link_type,
+            data: frame.to_vec(),
+            estimated: false,
+        });
+        offset = start + length;
+    }
+    Ok(packets)
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Interface {
+    link_type: u16,
+    /// if_tsresol option, units of 10^-exponent or 2^-exponent seconds
+    exponent: u8,
+    binary: bool,
+}
+
+/// Value of the first option with `code` in a pcapng option list
+fn find_option(endian: Endian, options: &[u8], code: u16) -> Option<&[u8]> {
+    let mut offset = 0;
+    while let (Some(option), Some(length)) =
+        (endian.u16(options, offset), endian.u16(options, offset + 2))
+    {
+        if option == PCAPNG_OPTION_END {
+            break;
+        }
+        let value = options.get(offset + 4..offset + 4 + usize::from(length))?;
+        if option == code {
+            return Some(value);
+        }
+        offset += 4 + value.len().div_ceil(4) * 4;
+    }
+    None
+}
+
+fn parse_pcapng(data: &[u8]) -> Result<Vec<Packet>, Error> {
+    let mut endian = BIG;
+    let mut interfaces: Vec<Interface> = Vec::new();
+    let mut packets = Vec::new();
+    let mut offset = 0;
+    while offset < data.len() {
+        if BIG.u32(data, offset) == Some(PCAPNG_SECTION_HEADER) {
+            endian = match BIG.u32(data, offset + 8) {
+                Some(PCAPNG_BYTE_ORDER_MAGIC) => BIG,
+                Some(magic) if magic.swap_bytes() == PCAPNG_BYTE_ORDER_MAGIC => LITTLE,
+                _ => return Err(Error::Format("invalid section header".to_string())),
+            };
+            // Interface IDs are local to a section
+            interfaces.clear();
+        }
+        let (block_type, length) = match (endian.u32(data, offset), endian.u32(data, offset + 4)) {
+            (Some(block_type), Some(length)) if length >= 12 && length % 4 == 0 => {
+                (block_type, length as usize)
+            }
+            _ => return Err(truncated("block header")),
+        };
+        let body = data
+            .get(offset + 8..offset + length - 4)
+            .ok_or_else(|| truncated("block"))?;
+        match block_type {
+            PCAPNG_INTERFACE_DESCRIPTION => {
+                let link_type = endian
+                    .u16(body, 0)
+                    .ok_or_else(|| truncated("interface description"))?;
+                let mut interface = Interface {
+                    link_type,
+                    exponent: 6,
+                    binary: false,
+                };
+                if let Some(resolution) = find_option(endian, &body[8..], PCAPNG_IF_TSRESOL) {
+                    let resolution = *resolution.first().ok_or_else(|| truncated("if_tsresol"))?;
+                    interface.exponent = resolution & 0x7F;
+                    interface.binary = resolution & 0x80 != 0;
+                }
+                interfaces.push(interface);
+            }
+            PCAPNG_ENHANCED_PACKET => {
+                let header = (
+                    endian.u32(body, 0),
+                    endian.u32(body, 4),
+                    endian.u32(body, 8),
+                    endian.u32(body, 12),
+                );
+                let (id, high, low, captured) = match header {
+                    (Some(id), Some(high), Some(low), Some(captured)) => {
+                        (id, high, low, captured as usize)
+                    }
+                    _ => return Err(truncated("enhanced packet")),
+                };
+                let interface = interfaces.get(id as usize).ok_or_else(|| {
+                    Error::Format(format!("packet on undescribed interface {}", id))
+                })?;
+                let frame = body
+                    .get(20..20 + captured)
+                    .ok_or_else(|| truncated("enhanced packet"))?;
+                let options = body.get(20 + captured.div_ceil(4) * 4..).unwrap_or(&[]);
+                let direction = match find_option(endian, options, PCAPNG_EPB_FLAGS)
+                    .and_then(|flags| endian.u32(flags, 0))
+                    .map(|flags| flags & 0x3)
+                {
+                    Some(1) => Direction::Inbound,
+                    Some(2) => Direction::Outbound,
+                    _ => Direction::Unknown,
+                };
+                packets.push(Packet {
+                    timestamp: timestamp(
+                        u64::from(high) << 32 | u64::from(low),
+                        interface.exponent,
+                        interface.binary,
+                    ),
+                    interface: id,
+                    direction,
+                    link_type: interface.link_type,
+                    data: frame.to_vec(),
+                    estimated: match find_option(endian, options, PCAPNG_OPT_COMMENT) {
+                        Some(comment) => {
+                            comment == ESTIMATED_TIMESTAMP.as_bytes()
+                                || comment == ESTIMATED_RECEIVE_TIMESTAMP.as_bytes()
+                        }
+                        None => false,
+                    },
+                });
+            }
+            _ => (),
+        }
+        offset += length;
+    }
+    Ok(packets)
+}
+
+/// Source or destination of a PTP message
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Address {
+    Udp(SocketAddr),
+    Ethernet([u8; 6]),
+    Unknown,
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Address::Udp(addr) => write!(f, "{}", addr),
+            Address::Ethernet(mac) => {
+                let octets: Vec<_> = mac.iter().map(|octet| format!("{:02x}", octet)).collect();
+                write!(f, "{}", octets.join(":"))
+            }
+            Address::Unknown => write!(f, "?"),
+        }
+    }
+}
+
+/// PTP message found in a captured frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Datagram<'a> {
+    pub source: Address,
+    pub destination: Address,
+    pub payload: &'a [u8],
+}
+
+fn mac(data: &[u8], offset: usize) -> Option<[u8; 6]> {
+    data.get(offset..offset + 6)?.try_into().ok()
+}
+
+fn udp(source: IpAddr, destination: IpAddr, data: &[u8]) -> Option<Datagram<'_>> {
+    let endian = BIG;
+    let (source_port, destination_port, length) = (
+        endian.u16(data, 0)?,
+        endian.u16(data, 2)?,
+        endian.u16(data, 4)?,
+    );
+    if ![EVENT_PORT, GENERAL_PORT].contains(&destination_port)
+        && ![EVENT_PORT, GENERAL_PORT].contains(&source_port)
+    {
+        return None;
+    }
+    Some(Datagram {
+        source: Address::Udp(SocketAddr::new(source, source_port)),
+        destination: Address::Udp(SocketAddr::new(destination, destination_port)),
+        payload: data.get(8..usize::from(length).max(8))?,
+    })
+}
+
+fn ip(data: &[u8]) -> Option<Datagram<'_>> {
+    match data.first()? >> 4 {
+        4 => {
+            let header_length = usize::from(data[0] & 0x0F) * 4;
+            let fragment_offset = BIG.u16(data, 6)? & 0x1FFF;
+            if *data.get(9)? != IPPROTO_UDP || fragment_offset != 0 {
+                return None;
+            }
+            let source: [u8; 4] = data.get(12..16)?.try_into().ok()?;
+            let destination: [u8; 4] = data.get(16..20)?.try_into().ok()?;
+            udp(
+                Ipv4Addr::from(source).into(),
+                Ipv4Addr::from(destination).into(),
+                data.get(header_length..)?,
+            )
+        }
+        6 => {
+            if *data.get(6)? != IPPROTO_UDP {
+                return None;
+            }
+            let source: [u8; 16] = data.get(8..24)?.try_into().ok()?;
+            let destination: [u8; 16] = data.get(24..40)?.try_into().ok()?;
+            udp(
+                Ipv6Addr::from(source).into(),
+                Ipv6Addr::from(destination).into(),
+                data.get(40..)?,
+            )
+        }
+        _ => None,
+    }
+}
+
+/// Finds the PTP message in a frame carrying PTP over UDPv4, UDPv6 or IEEE 802.3
+pub fn extract_ptp(link_type: u16, frame: &[u8]) -> Option<Datagram<'_>> {
+    let endian = BIG;
+    let (source, destination, mut ethertype, mut offset) = match link_type {
+        LINKTYPE_ETHERNET => (
+            Address::Ethernet(mac(frame, 6)?),
+            Address::Ethernet(mac(frame, 0)?),
+            endian.u16(frame, 12)?,
+            14,
+        ),
+        LINKTYPE_LINUX_SLL => (
+            match endian.u16(frame, 4)? {
+                6 => Address::Ethernet(mac(frame, 6)?),
+                _ => Address::Unknown,
+            },
+            Address::Unknown,
+            endian.u16(frame, 14)?,
+            16,
+        ),
+        LINKTYPE_RAW | LINKTYPE_IPV4 | LINKTYPE_IPV6 => return ip(frame),
+        _ => return None,
+    };
+    while ethertype == ETHERTYPE_VLAN {
+        ethertype = endian.u16(frame, offset + 2)?;
+        offset += 4;
+    }
+    match ethertype {
+        ETHERTYPE_IPV4 | ETHERTYPE_IPV6 => ip(frame.get(offset..)?),
+        ETHERTYPE_PTP => Some(Datagram {
+            source,
+            destination,
+            payload: frame.get(offset..)?,
+        }),
+        _ => None,
+    }
+}
+
+/// Selects messages by domain, message type and sourcePortIdentity
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Filter {
+    pub domain: Option<u8>,
+    pub message_type: Option<values::MessageType>,
+    pub clock_identity: Option<ClockIdentity>,
+    pub port_number: Option<u16>,
+}
+
+impl Filter {
+    /// Sets the sourcePortIdentity filter from `clockid-port` or just `clockid`
+    pub fn set_source(&mut self, source: &str) -> Result<(), String> {
+        let invalid = || format!("invalid port identity '{}'", source);
+        let (clock, port) = match source.rfind('-') {
+            Some(pos) => (
+                &source[..pos],
+                Some(source[pos + 1..].parse().map_err(|_| invalid())?),
+            ),
+            None => (source, None),
+        };
+        self.clock_identity = Some(parse_clock_identity(clock).ok_or_else(invalid)?);
+        self.port_number = port;
+        Ok(())
+    }
+
+    /// Sets the message type filter from a name such as `Sync` or `Delay_Req`
+    pub fn set_message_type(&mut self, name: &str) -> Result<(), String> {
+        self.message_type = Some(
+            values::MessageType::from_str(name)
+                .map_err(|_| format!("unknown message type '{}'", name))?,
+        );
+        Ok(())
+    }
+
+    pub fn matches(&self, message: &Message) -> bool {
+        let header = &message.header;
+        self.domain
+            .is_none_or(|domain| header.domainNumber.into_inner() == domain)
+            && self
+                .message_type
+                .is_none_or(|message_type| header.messageType == Enumeration::Enum(message_type))
+            && self
+                .clock_identity
+                .is_none_or(|identity| header.sourcePortIdentity.clockIdentity == identity)
+            && self
+                .port_number
+                .is_none_or(|port| header.sourcePortIdentity.portNumber.into_inner() == port)
+    }
+}
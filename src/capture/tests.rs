@@ -0,0 +1,158 @@
+use super::*;
+use crate::protocol::parser::parse_ptp_message;
+
+fn messages(packets: &[Packet]) -> Vec<(Address, Message)> {
+    packets
+        .iter()
+        .filter_map(|packet| extract_ptp(packet.link_type, &packet.data))
+        .map(|datagram| {
+            (
+                datagram.source,
+                parse_ptp_message(datagram.payload).unwrap().1,
+            )
+        })
+        .collect()
+}
+
+#[test]
+pub fn test_pcap() {
+    let packets = parse(include_bytes!("../../tests/fixtures/ptp.pcap")).unwrap();
+    assert_eq!(packets.len(), 6);
+    assert_eq!(packets[1].link_type, LINKTYPE_ETHERNET);
+    assert_eq!(
+        packets[1].timestamp,
+        Duration::new(1_700_000_000, 125_000_000)
+    );
+
+    let messages = messages(&packets);
+    // The DNS query is not PTP
+    assert_eq!(messages.len(), 5);
+    assert_eq!(
+        messages[0].0,
+        Address::Udp("192.168.1.10:320".parse().unwrap())
+    );
+    let types: Vec<_> = messages
+        .iter()
+        .map(|(_, message)| message.header.messageType)
+        .collect();
+    assert_eq!(
+        types,
+        [
+            values::MessageType::Announce,
+            values::MessageType::Sync,
+            values::MessageType::Follow_Up,
+            values::MessageType::Delay_Req,
+            values::MessageType::Sync,
+        ]
+        .iter()
+        .map(|&message_type| Enumeration::Enum(message_type))
+        .collect::<Vec<_>>()
+    );
+
+    let mut filter = Filter::default();
+    filter.set_message_type("Sync").unwrap();
+    assert_eq!(
+        messages.iter().filter(|(_, m)| filter.matches(m)).count(),
+        2
+    );
+    filter.domain = Some(0);
+    assert_eq!(
+        messages.iter().filter(|(_, m)| filter.matches(m)).count(),
+        1
+    );
+
+    let mut filter = Filter::default();
+    filter.set_source("001122.fffe.334455-1").unwrap();
+    assert_eq!(
+        messages.iter().filter(|(_, m)| filter.matches(m)).count(),
+        2
+    );
+    filter.set_source("001122.fffe.334455-2").unwrap();
+    assert_eq!(
+        messages.iter().filter(|(_, m)| filter.matches(m)).count(),
+        0
+    );
+    assert!(filter.set_source("001122.fffe.334455-x").is_err());
+    assert!(filter.set_message_type("Foo").is_err());
+}
+
+#[test]
+pub fn test_pcapng() {
+    let packets = parse(include_bytes!("../../tests/fixtures/ptp.pcapng")).unwrap();
+    assert_eq!(packets.len(), 2);
+    assert_eq!(packets[0].timestamp, Duration::new(1_700_000_002, 123));
+
+    let messages = messages(&packets);
+    assert_eq!(messages.len(), 2);
+    assert_eq!(
+        messages[0].0,
+        Address::Udp("[fe80::ba27:ebff:fe92:b1a6]:319".parse().unwrap())
+    );
+    // IEEE 802.3 behind a VLAN tag
+    assert_eq!(
+        messages[1].0,
+        Address::Ethernet([0xb8, 0x27, 0xeb, 0x92, 0xb1, 0xa6])
+    );
+    assert_eq!(
+        messages[1].1.header.messageType,
+        Enumeration::Enum(values::MessageType::Announce)
+    );
+
+    assert!(parse(b"\x0a\x0d\x0d\x0a\x1c\x00\x00\x00").is_err());
+    assert!(parse(b"not a capture").is_err());
+}
+
+#[test]
+pub fn test_writer() {
+    let dir = std::env::temp_dir().join(format!("ptp-capture-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("ptp.pcapng");
+    let source = "192.168.1.10:319".parse().unwrap();
+    let destination = "224.0.1.129:319".parse().unwrap();
+    let packet = |n: u64, direction| Packet {
+        timestamp: Duration::new(1_700_000_000, 0) + Duration::from_nanos(n),
+        interface: (n % 2) as u32,
+        direction,
+        link_type: LINKTYPE_RAW,
+        data: writer::ipv4_udp(source, destination, &[n as u8; 44]),
+        // The last two are flagged with a comment
+        estimated: n >= 8,
+    };
+
+    let interfaces = ["eth0".to_string(), "eth1".to_string()];
+    let mut writer = writer::Writer::create(&path, &interfaces, 512, 2).unwrap();
+    for n in 0..10 {
+        let direction = if n % 2 == 0 {
+            Direction::Inbound
+        } else {
+            Direction::Outbound
+        };
+        writer.write(&packet(n, direction)).unwrap();
+    }
+    drop(writer);
+
+    // Each file holds the section and interface headers and at most 3 packets
+    let mut packets = read_file(path.with_extension("pcapng.2")).unwrap();
+    packets.extend(read_file(path.with_extension("pcapng.1")).unwrap());
+    packets.extend(read_file(&path).unwrap());
+    assert!(!path.with_extension("pcapng.3").exists());
+    assert_eq!(packets.len(), 7);
+    assert_eq!(packets[0], packet(3, Direction::Outbound));
+    assert_eq!(packets[6], packet(9, Direction::Outbound));
+    assert!(packets[6].estimated && packets[5].estimated && !packets[4].estimated);
+    let datagram = extract_ptp(LINKTYPE_RAW, &packets[4].data).unwrap();
+    assert_eq!(datagram.source, Address::Udp(source.into()));
+    assert_eq!(datagram.payload, &[7; 44][..]);
+
+    // A restart keeps the previous capture
+    let writer = writer::Writer::create(&path, &interfaces, 512, 2).unwrap();
+    drop(writer);
+    assert!(read_file(&path).unwrap().is_empty());
+    assert_eq!(
+        read_file(path.with_extension("pcapng.1")).unwrap(),
+        vec![packet(9, Direction::Outbound)]
+    );
+    assert!(!path.with_extension("pcapng.3").exists());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,195 @@
+use super::{
+    Direction, Packet, ESTIMATED_RECEIVE_TIMESTAMP, ESTIMATED_TIMESTAMP, LINKTYPE_RAW,
+    PCAPNG_BYTE_ORDER_MAGIC, PCAPNG_ENHANCED_PACKET, PCAPNG_EPB_FLAGS, PCAPNG_IF_NAME,
+    PCAPNG_IF_TSRESOL, PCAPNG_INTERFACE_DESCRIPTION, PCAPNG_OPTION_END, PCAPNG_OPT_COMMENT,
+    PCAPNG_SECTION_HEADER,
+};
+
+use std::fs::File;
+use std::io::{self, Write};
+use std::net::SocketAddrV4;
+use std::path::{Path, PathBuf};
+
+const IPPROTO_UDP: u8 = 17;
+
+/// Raw IPv4 packet carrying `payload` in a UDP datagram, for LINKTYPE_RAW captures
+pub fn ipv4_udp(source: SocketAddrV4, destination: SocketAddrV4, payload: &[u8]) -> Vec<u8> {
+    let udp_length = 8 + payload.len() as u16;
+    let total_length = 20 + udp_length;
+    let mut packet = vec![0x45, 0, 0, 0, 0, 0, 0x40, 0, 1, IPPROTO_UDP, 0, 0];
+    packet[2..4].copy_from_slice(&total_length.to_be_bytes());
+    packet.extend_from_slice(&source.ip().octets());
+    packet.extend_from_slice(&destination.ip().octets());
+    let sum = packet
+        .chunks(2)
+        .map(|word| u32::from(u16::from_be_bytes([word[0], word[1]])))
+        .sum::<u32>();
+    let sum = (sum & 0xFFFF) + (sum >> 16);
+    let checksum = !((sum & 0xFFFF) + (sum >> 16)) as u16;
+    packet[10..12].copy_from_slice(&checksum.to_be_bytes());
+    // The UDP checksum is optional over IPv4 and left out
+    packet.extend_from_slice(&source.port().to_be_bytes());
+    packet.extend_from_slice(&destination.port().to_be_bytes());
+    packet.extend_from_slice(&udp_length.to_be_bytes());
+    packet.extend_from_slice(&[0, 0]);
+    packet.extend_from_slice(payload);
+    packet
+}
+
+fn option(code: u16, value: &[u8]) -> Vec<u8> {
+    let mut option = Vec::with_capacity(4 + value.len() + 3);
+    option.extend_from_slice(&code.to_le_bytes());
+    option.extend_from_slice(&(value.len() as u16).to_le_bytes());
+    option.extend_from_slice(value);
+    option.resize(option.len().div_ceil(4) * 4, 0);
+    option
+}
+
+/// Little endian pcapng block with its length fields around `body`
+fn block(block_type: u32, mut body: Vec<u8>) -> Vec<u8> {
+    body.resize(body.len().div_ceil(4) * 4, 0);
+    let length = (body.len() as u32 + 12).to_le_bytes();
+    let mut block = Vec::with_capacity(body.len() + 12);
+    block.extend_from_slice(&block_type.to_le_bytes());
+    block.extend_from_slice(&length);
+    block.extend_from_slice(&body);
+    block.extend_from_slice(&length);
+    block
+}
+
+fn rotated_path(path: &Path, n: usize) -> PathBuf {
+    match n {
+        0 => path.to_path_buf(),
+        n => {
+            let mut path = path.to_path_buf().into_os_string();
+            path.push(format!(".{}", n));
+            path.into()
+        }
+    }
+}
+
+/// Renames the current and rotated files to the next `rotations` rotated
+/// paths, overwriting the last one
+fn shift(path: &Path, rotations: usize) -> io::Result<()> {
+    for n in (0..rotations).rev() {
+        match std::fs::rename(rotated_path(path, n), rotated_path(path, n + 1)) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+/// Writes packets to a pcapng file with one interface per name, each file
+/// starting a new section so rotated files can be read on their own
+#[derive(Debug)]
+pub struct Writer {
+    path: PathBuf,
+    file: File,
+    interfaces: Vec<String>,
+    size: u64,
+    /// Size after which the file is rotated, 0 disables rotation
+    max_size: u64,
+    /// Number of rotated files kept besides the current one
+    rotations: usize,
+}
+
+impl Writer {
+    /// Starts a capture at `path`, a capture left by an earlier run being
+    /// rotated to `path.1` even if rotation is disabled
+    pub fn create<P: AsRef<Path>>(
+        path: P,
+        interfaces: &[String],
+        max_size: u64,
+        rotations: usize,
+    ) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        if path.exists() {
+            shift(&path, rotations.max(1))?;
+        }
+        let mut writer = Writer {
+            file: File::create(&path)?,
+            path,
+            interfaces: interfaces.to_vec(),
+            size: 0,
+            max_size,
+            rotations,
+        };
+        writer.write_header()?;
+        Ok(writer)
+    }
+
+    fn write_header(&mut self) -> io::Result<()> {
+        let mut section = Vec::new();
+        section.extend_from_slice(&PCAPNG_BYTE_ORDER_MAGIC.to_le_bytes());
+        section.extend_from_slice(&1u16.to_le_bytes());
+        section.extend_from_slice(&0u16.to_le_bytes());
+        // Unspecified section length
+        section.extend_from_slice(&(-1i64).to_le_bytes());
+        let mut header = block(PCAPNG_SECTION_HEADER, section);
+        for name in &self.interfaces {
+            let mut description = Vec::new();
+            description.extend_from_slice(&LINKTYPE_RAW.to_le_bytes());
+            description.extend_from_slice(&[0, 0]);
+            description.extend_from_slice(&0u32.to_le_bytes());
+            description.extend(option(PCAPNG_IF_NAME, name.as_bytes()));
+            // Nanosecond timestamps
+            description.extend(option(PCAPNG_IF_TSRESOL, &[9]));
+            description.extend(option(PCAPNG_OPTION_END, &[]));
+            header.extend(block(PCAPNG_INTERFACE_DESCRIPTION, description));
+        }
+        self.file.write_all(&header)?;
+        self.size = header.len() as u64;
+        Ok(())
+    }
+
+    /// Path of the `n`th rotated file, the current file for 0
+    pub fn rotated_path(&self, n: usize) -> PathBuf {
+        rotated_path(&self.path, n)
+    }
+
+    /// Moves `path` to `path.1`, `path.1` to `path.2` and so on, dropping the oldest
+    fn rotate(&mut self) -> io::Result<()> {
+        if self.rotations == 0 {
+            std::fs::remove_file(&self.path)?;
+        }
+        shift(&self.path, self.rotations)?;
+        self.file = File::create(&self.path)?;
+        self.write_header()
+    }
+
+    /// Appends `packet`, whose link type is expected to be LINKTYPE_RAW
+    pub fn write(&mut self, packet: &Packet) -> io::Result<()> {
+        let nanos = packet.timestamp.as_nanos() as u64;
+        let mut body = Vec::with_capacity(packet.data.len() + 32);
+        body.extend_from_slice(&packet.interface.to_le_bytes());
+        body.extend_from_slice(&((nanos >> 32) as u32).to_le_bytes());
+        body.extend_from_slice(&(nanos as u32).to_le_bytes());
+        body.extend_from_slice(&(packet.data.len() as u32).to_le_bytes());
+        body.extend_from_slice(&(packet.data.len() as u32).to_le_bytes());
+        body.extend_from_slice(&packet.data);
+        body.resize(body.len().div_ceil(4) * 4, 0);
+        let flags: u32 = match packet.direction {
+            Direction::Unknown => 0,
+            Direction::Inbound => 1,
+            Direction::Outbound => 2,
+        };
+        body.extend(option(PCAPNG_EPB_FLAGS, &flags.to_le_bytes()));
+        if packet.estimated {
+            let comment = match packet.direction {
+                Direction::Inbound => ESTIMATED_RECEIVE_TIMESTAMP,
+                _ => ESTIMATED_TIMESTAMP,
+            };
+            body.extend(option(PCAPNG_OPT_COMMENT, comment.as_bytes()));
+        }
+        body.extend(option(PCAPNG_OPTION_END, &[]));
+        let block = block(PCAPNG_ENHANCED_PACKET, body);
+
+        if self.max_size > 0 && self.size + block.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+        self.file.write_all(&block)?;
+        self.size += block.len() as u64;
+        Ok(())
+    }
+}
@@ -0,0 +1,144 @@
+use crate::protocol::types::derived::{ClockIdentity, ClockQuality, PortIdentity};
+use crate::protocol::types::message::body;
+use crate::protocol::types::primitive::Enumeration8;
+
+/// Result of comparing data set A to data set B (IEEE 1588-2008, 9.3.4)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Better,
+    BetterByTopology,
+    /// Both data sets describe the same path, only possible for duplicated messages
+    Equal,
+    WorseByTopology,
+    Worse,
+}
+
+impl Comparison {
+    pub fn is_better(self) -> bool {
+        self == Comparison::Better || self == Comparison::BetterByTopology
+    }
+}
+
+type Identity = ([u8; 8], u16);
+
+fn clock_identity(identity: &ClockIdentity) -> [u8; 8] {
+    let mut out = [0; 8];
+    for (out, octet) in out.iter_mut().zip(identity.iter()) {
+        *out = octet.into_inner();
+    }
+    out
+}
+
+fn port_identity(identity: &PortIdentity) -> Identity {
+    (
+        clock_identity(&identity.clockIdentity),
+        identity.portNumber.into_inner(),
+    )
+}
+
+/// Fields of an Announce message relevant to the best master clock algorithm
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Dataset {
+    pub grandmaster_priority1: u8,
+    pub grandmaster_identity: [u8; 8],
+    pub grandmaster_class: u8,
+    pub grandmaster_accuracy: u8,
+    pub grandmaster_variance: u16,
+    pub grandmaster_priority2: u8,
+    pub steps_removed: u16,
+    pub sender: Identity,
+    pub receiver: Identity,
+}
+
+impl Dataset {
+    pub fn new(
+        priority1: u8,
+        identity: &ClockIdentity,
+        quality: &ClockQuality,
+        priority2: u8,
+        steps_removed: u16,
+        sender: &PortIdentity,
+        receiver: &PortIdentity,
+    ) -> Self {
+        Dataset {
+            grandmaster_priority1: priority1,
+            grandmaster_identity: clock_identity(identity),
+            grandmaster_class: quality.clockClass.into_inner(),
+            grandmaster_accuracy: Enumeration8::from(quality.clockAccuracy).into_inner(),
+            grandmaster_variance: quality.offsetScaledLogVariance.into_inner(),
+            grandmaster_priority2: priority2,
+            steps_removed,
+            sender: port_identity(sender),
+            receiver: port_identity(receiver),
+        }
+    }
+
+    pub fn from_announce(
+        announce: &body::Announce,
+        sender: &PortIdentity,
+        receiver: &PortIdentity,
+    ) -> Self {
+        Dataset::new(
+            announce.grandmasterPriority1.into_inner(),
+            &announce.grandmasterIdentity,
+            &announce.grandmasterClockQuality,
+            announce.grandmasterPriority2.into_inner(),
+            announce.stepsRemoved.into_inner(),
+            sender,
+            receiver,
+        )
+    }
+
+    /// Data set comparison algorithm, figures 27 and 28 of IEEE 1588-2008
+    pub fn compare(&self, other: &Dataset) -> Comparison {
+        if self.grandmaster_identity != other.grandmaster_identity {
+            let key = |ds: &Dataset| {
+                (
+                    ds.grandmaster_priority1,
+                    ds.grandmaster_class,
+                    ds.grandmaster_accuracy,
+                    ds.grandmaster_variance,
+                    ds.grandmaster_priority2,
+                    ds.grandmaster_identity,
+                )
+            };
+            return if key(self) < key(other) {
+                Comparison::Better
+            } else {
+                Comparison::Worse
+            };
+        }
+
+        let (a, b) = (
+            u32::from(self.steps_removed),
+            u32::from(other.steps_removed),
+        );
+        if a > b + 1 {
+            Comparison::Worse
+        } else if b > a + 1 {
+            Comparison::Better
+        } else if a > b {
+            match self.receiver.cmp(&self.sender) {
+                std::cmp::Ordering::Less => Comparison::Worse,
+                std::cmp::Ordering::Greater => Comparison::WorseByTopology,
+                std::cmp::Ordering::Equal => Comparison::Equal,
+            }
+        } else if b > a {
+            match other.receiver.cmp(&other.sender) {
+                std::cmp::Ordering::Less => Comparison::Better,
+                std::cmp::Ordering::Greater => Comparison::BetterByTopology,
+                std::cmp::Ordering::Equal => Comparison::Equal,
+            }
+        } else {
+            match self
+                .sender
+                .cmp(&other.sender)
+                .then(self.receiver.1.cmp(&other.receiver.1))
+            {
+                std::cmp::Ordering::Less => Comparison::BetterByTopology,
+                std::cmp::Ordering::Greater => Comparison::WorseByTopology,
+                std::cmp::Ordering::Equal => Comparison::Equal,
+            }
+        }
+    }
+}
@@ -0,0 +1,439 @@
+use crate::config::{ClockConfig, PortConfig};
+use crate::protocol::types::datasets::{
+    CurrentDS, DefaultDS, ParentDS, TimePropertiesDS, DEFAULT_SO, DEFAULT_TSC, TIME_PTP,
+};
+use crate::protocol::types::derived::{
+    ClockIdentity, ClockQuality, PortIdentity, TimeInterval, Timestamp,
+};
+use crate::protocol::types::enums::values::{self, PortState};
+use crate::protocol::types::enums::Enumeration;
+use crate::protocol::types::message::{body, Body, Header, Message};
+use crate::protocol::types::primitive::int::Int;
+use crate::protocol::types::primitive::Octet;
+use crate::stats::{ServoStats, ServoSummary};
+
+use std::time::{Duration, Instant};
+
+pub mod bmc;
+pub mod port;
+pub mod replay;
+pub mod transparent;
+
+#[cfg(test)]
+mod tests;
+
+use bmc::{Comparison, Dataset};
+use port::{Measurement, Port};
+
+const CURRENT_UTC_OFFSET: i16 = 37;
+
+/// Message to be transmitted on one of the clock's ports
+#[derive(Debug, Clone, PartialEq)]
+pub struct Output {
+    pub port: usize,
+    pub message: Message,
+}
+
+/// Time properties announced while the clock is its own grandmaster
+fn local_time_properties() -> TimePropertiesDS {
+    TimePropertiesDS {
+        currentUtcOffset: Int::new(CURRENT_UTC_OFFSET),
+        flags: Int::new(TIME_PTP),
+        timeSource: Enumeration::Enum(values::TimeSource::INTERNAL_OSCILLATOR),
+    }
+}
+
+/// Ordinary or boundary clock, depending on the number of ports
+#[derive(Debug)]
+pub struct Clock {
+    pub config: ClockConfig,
+    pub ports: Vec<Port>,
+    pub default_ds: DefaultDS,
+    pub current_ds: CurrentDS,
+    /// Describes the clock itself while it is its own grandmaster
+    pub parent_ds: ParentDS,
+    pub time_properties_ds: TimePropertiesDS,
+    pub stats: ServoStats,
+    /// Summary completed by the last measurements and not taken yet
+    summary: Option<ServoSummary>,
+}
+
+impl Clock {
+    /// Creates a clock with a port for every configuration, numbered from 1
+    pub fn new(
+        identity: ClockIdentity,
+        config: ClockConfig,
+        ports: &[PortConfig],
+        now: Instant,
+    ) -> Self {
+        let ports: Vec<_> = ports
+            .iter()
+            .enumerate()
+            .map(|(idx, port)| {
+                Port::new(
+                    PortIdentity {
+                        clockIdentity: identity,
+                        portNumber: Int::new(idx as u16 + 1),
+                    },
+                    *port,
+                    now,
+                )
+            })
+            .collect();
+        let mut flags = 0;
+        if config.two_step_flag {
+            flags |= DEFAULT_TSC;
+        }
+        if config.clock_class == 255 {
+            flags |= DEFAULT_SO;
+        }
+        let default_ds = DefaultDS {
+            flags: Int::new(flags),
+            _reserved1: Int::new(0),
+            numberPorts: Int::new(ports.len() as u16),
+            priority1: Int::new(config.priority1),
+            clockQuality: ClockQuality {
+                clockClass: Int::new(config.clock_class),
+                clockAccuracy: config.clock_accuracy.into(),
+                offsetScaledLogVariance: Int::new(config.offset_scaled_log_variance),
+            },
+            priority2: Int::new(config.priority2),
+            clockIdentity: identity,
+            domainNumber: Int::new(config.domain_number),
+            _reserved2: Int::new(0),
+        };
+        let stats = ServoStats::new(config.summary_interval);
+        let mut clock = Clock {
+            config,
+            ports,
+            default_ds,
+            current_ds: CurrentDS {
+                stepsRemoved: Int::new(0),
+                offsetFromMaster: TimeInterval::new(0),
+                meanPathDelay: TimeInterval::new(0),
+            },
+            parent_ds: ParentDS {
+                parentPortIdentity: PortIdentity {
+                    clockIdentity: identity,
+                    portNumber: Int::new(0),
+                },
+                flags: Int::new(0),
+                _reserved: Int::new(0),
+                observedParentOffsetScaledLogVariance: Int::new(0xFFFF),
+                observedParentClockPhaseChangeRate: Int::new(0x7FFF_FFFF),
+                grandmasterPriority1: default_ds.priority1,
+                grandmasterClockQuality: default_ds.clockQuality,
+                grandmasterPriority2: default_ds.priority2,
+                grandmasterIdentity: identity,
+            },
+            time_properties_ds: local_time_properties(),
+            stats,
+            summary: None,
+        };
+        clock.become_grandmaster();
+        clock
+    }
+
+    pub fn identity(&self) -> ClockIdentity {
+        self.default_ds.clockIdentity
+    }
+
+    pub fn domain_number(&self) -> u8 {
+        self.default_ds.domainNumber.into_inner()
+    }
+
+    /// Whether the clock is synchronized to another clock
+    pub fn has_parent(&self) -> bool {
+        self.parent_ds.parentPortIdentity.clockIdentity != self.identity()
+    }
+
+    /// Data set describing this clock as a grandmaster, D0 in IEEE 1588
+    pub fn default_dataset(&self) -> Dataset {
+        let identity = PortIdentity {
+            clockIdentity: self.identity(),
+            portNumber: Int::new(0),
+        };
+        Dataset::new(
+            self.default_ds.priority1.into_inner(),
+            &self.default_ds.clockIdentity,
+            &self.default_ds.clockQuality,
+            self.default_ds.priority2.into_inner(),
+            0,
+            &identity,
+            &identity,
+        )
+    }
+
+    /// Announce body transmitted on master ports
+    pub fn announce(&self) -> body::Announce {
+        body::Announce {
+            originTimestamp: Timestamp {
+                secondsField: Int::new(0),
+                nanosecondsField: Int::new(0),
+            },
+            currentUtcOffset: self.time_properties_ds.currentUtcOffset,
+            _reserved: Int::new(0),
+            grandmasterPriority1: self.parent_ds.grandmasterPriority1,
+            grandmasterClockQuality: self.parent_ds.grandmasterClockQuality,
+            grandmasterPriority2: self.parent_ds.grandmasterPriority2,
+            grandmasterIdentity: self.parent_ds.grandmasterIdentity,
+            stepsRemoved: self.current_ds.stepsRemoved,
+            timeSource: self.time_properties_ds.timeSource,
+        }
+    }
+
+    /// Whether `message` is of the clock's domain and not one of its own
+    pub fn accepts(&self, message: &Message) -> bool {
+        message.header.domainNumber.into_inner() == self.domain_number()
+            && message.header.sourcePortIdentity.clockIdentity != self.identity()
+    }
+
+    /// Processes a message received on port with index `port`
+    pub fn handle(&mut self, port: usize, message: &Message, now: Instant) {
+        if !self.accepts(message) {
+            return;
+        }
+        if message.header.messageType == Enumeration::Enum(values::MessageType::Pdelay_Req) {
+            self.ports[port].peer_delay_requested();
+        }
+        if let Body::Announce(announce) = &message.body {
+            let port = &mut self.ports[port];
+            if announce.stepsRemoved.into_inner() >= 255
+                || matches!(port.state, PortState::DISABLED | PortState::FAULTY)
+            {
+                return;
+            }
+            port.add_foreign_master(
+                message.header.sourcePortIdentity,
+                *announce,
+                message.header.flagField[1],
+                now,
+            );
+        }
+    }
+
+    /// Takes the timestamps of an event message received at `received` on port
+    /// with index `port`, updating currentDS and the servo statistics with the
+    /// measurement it completes
+    pub fn measure(
+        &mut self,
+        port: usize,
+        message: &Message,
+        received: Duration,
+    ) -> Option<Measurement> {
+        if !self.accepts(message)
+            || self.ports[port].state != PortState::SLAVE
+            || message.header.sourcePortIdentity != self.parent_ds.parentPortIdentity
+        {
+            return None;
+        }
+        let measurement = self.ports[port].measure(message, received)?;
+        self.current_ds.offsetFromMaster =
+            TimeInterval::new(measurement.offset.saturating_mul(1 << 16));
+        self.current_ds.meanPathDelay =
+            TimeInterval::new(measurement.mean_path_delay.saturating_mul(1 << 16));
+        if let Some(summary) = self.stats.add(
+            received,
+            measurement.offset as f64,
+            measurement.freq,
+            Some(measurement.mean_path_delay as f64),
+        ) {
+            self.summary = Some(summary);
+        }
+        Some(measurement)
+    }
+
+    /// Records the transmit time of a message sent on port with index `port`
+    pub fn transmitted(&mut self, port: usize, message: &Message, sent: Duration) {
+        self.ports[port].transmitted(message, sent)
+    }
+
+    /// Summary of the servo statistics completed since the last call
+    pub fn take_summary(&mut self) -> Option<ServoSummary> {
+        self.summary.take()
+    }
+
+    /// Runs the state decision algorithm and returns Announce and Delay_Req
+    /// messages that are due
+    pub fn poll(&mut self, now: Instant) -> Vec<Output> {
+        for port in self.ports.iter_mut() {
+            port.expire_foreign_masters(now);
+        }
+        self.state_decision(now);
+
+        let announce = self.announce();
+        let (domain_number, flags) = (self.domain_number(), self.time_properties_ds.flags);
+        let mut out = Vec::new();
+        for (idx, port) in self.ports.iter_mut().enumerate() {
+            if port.state == PortState::SLAVE
+                && port.delay_mechanism() != values::DelayMechanism::P2P
+                && port.synchronizing()
+                && now >= port.next_delay_req
+            {
+                port.next_delay_req = now + port.delay_req_interval();
+                out.push(Output {
+                    port: idx,
+                    message: Message {
+                        header: Header::new(
+                            values::MessageType::Delay_Req,
+                            0,
+                            domain_number,
+                            port.identity,
+                            port.delay_req_sequence.into_inner(),
+                            0x7F,
+                        ),
+                        body: Body::Delay_Req(body::Delay_Req {
+                            originTimestamp: Timestamp {
+                                secondsField: Int::new(0),
+                                nanosecondsField: Int::new(0),
+                            },
+                        }),
+                    }
+                    .finalize(),
+                });
+                port.delay_req_sequence = port.delay_req_sequence.wrapping_add(Int::new(1));
+            }
+            if port.state != PortState::MASTER {
+                port.next_announce = now;
+                continue;
+            }
+            if now < port.next_announce {
+                continue;
+            }
+            port.next_announce = now + port.announce_interval();
+            let mut header = Header::new(
+                values::MessageType::Announce,
+                0,
+                domain_number,
+                port.identity,
+                port.announce_sequence.into_inner(),
+                port.config.log_announce_interval,
+            );
+            header.flagField[1] = flags;
+            out.push(Output {
+                port: idx,
+                message: Message {
+                    header,
+                    body: Body::Announce(announce),
+                }
+                .finalize(),
+            });
+            port.announce_sequence = port.announce_sequence.wrapping_add(Int::new(1));
+        }
+        out
+    }
+
+    /// State decision algorithm, figure 26 of IEEE 1588-2008
+    fn state_decision(&mut self, now: Instant) {
+        let d0 = self.default_dataset();
+        let erbest: Vec<_> = self
+            .ports
+            .iter()
+            .map(|port| {
+                port.best_foreign_master(now)
+                    .map(|(dataset, &identity, master)| {
+                        (dataset, identity, master.announce, master.flags)
+                    })
+            })
+            .collect();
+        let mut ebest = None;
+        for (idx, candidate) in erbest.iter().enumerate() {
+            if let Some(candidate) = candidate {
+                if ebest.is_none_or(|(_, best): (usize, (Dataset, _, _, _))| {
+                    candidate.0.compare(&best.0).is_better()
+                }) {
+                    ebest = Some((idx, *candidate));
+                }
+            }
+        }
+
+        let mut parent = None;
+        for (idx, port) in self.ports.iter_mut().enumerate() {
+            if matches!(port.state, PortState::DISABLED | PortState::FAULTY) {
+                continue;
+            }
+            let state = if erbest[idx].is_none()
+                && port.state == PortState::LISTENING
+                && now < port.announce_receipt_deadline
+            {
+                PortState::LISTENING
+            } else if (1..=127).contains(&self.default_ds.clockQuality.clockClass.into_inner()) {
+                match &erbest[idx] {
+                    Some(erbest) if !d0.compare(&erbest.0).is_better() => PortState::PASSIVE,
+                    _ => PortState::MASTER,
+                }
+            } else {
+                match (&ebest, &erbest[idx]) {
+                    (Some((_, ebest)), _) if d0.compare(&ebest.0).is_better() => PortState::MASTER,
+                    (Some((best_port, ebest)), _) if *best_port == idx => {
+                        parent = Some((ebest.1, ebest.2, ebest.3));
+                        PortState::SLAVE
+                    }
+                    (Some((_, ebest)), Some(erbest))
+                        if ebest.0.compare(&erbest.0) == Comparison::BetterByTopology =>
+                    {
+                        PortState::PASSIVE
+                    }
+                    _ => PortState::MASTER,
+                }
+            };
+            port.state = match state {
+                // Slave only clocks never become master
+                PortState::MASTER if self.default_ds.flags.into_inner() & DEFAULT_SO != 0 => {
+                    PortState::LISTENING
+                }
+                state => state,
+            };
+            if port.state != PortState::SLAVE {
+                port.reset_exchange();
+            }
+            if port.state == PortState::LISTENING && now >= port.announce_receipt_deadline {
+                port.reset_announce_receipt_timeout(now);
+            }
+        }
+        match parent {
+            Some((identity, announce, flags)) => {
+                if identity != self.parent_ds.parentPortIdentity {
+                    for port in self.ports.iter_mut() {
+                        port.reset_exchange();
+                    }
+                }
+                self.follow(identity, &announce, flags)
+            }
+            None => self.become_grandmaster(),
+        }
+    }
+
+    /// Data set updates for a clock that is its own grandmaster, M1 and M2 in
+    /// Table 13 of IEEE 1588-2008
+    fn become_grandmaster(&mut self) {
+        let identity = self.identity();
+        self.current_ds.stepsRemoved = Int::new(0);
+        self.current_ds.offsetFromMaster = TimeInterval::new(0);
+        self.current_ds.meanPathDelay = TimeInterval::new(0);
+        self.parent_ds.parentPortIdentity = PortIdentity {
+            clockIdentity: identity,
+            portNumber: Int::new(0),
+        };
+        self.parent_ds.grandmasterIdentity = identity;
+        self.parent_ds.grandmasterPriority1 = self.default_ds.priority1;
+        self.parent_ds.grandmasterClockQuality = self.default_ds.clockQuality;
+        self.parent_ds.grandmasterPriority2 = self.default_ds.priority2;
+        self.time_properties_ds = local_time_properties();
+    }
+
+    /// Data set updates for a clock with a slave port, S1 in Table 16 of IEEE 1588-2008
+    fn follow(&mut self, parent: PortIdentity, announce: &body::Announce, flags: Octet) {
+        self.current_ds.stepsRemoved = Int::new(announce.stepsRemoved.into_inner() + 1);
+        self.parent_ds.parentPortIdentity = parent;
+        self.parent_ds.grandmasterIdentity = announce.grandmasterIdentity;
+        self.parent_ds.grandmasterPriority1 = announce.grandmasterPriority1;
+        self.parent_ds.grandmasterClockQuality = announce.grandmasterClockQuality;
+        self.parent_ds.grandmasterPriority2 = announce.grandmasterPriority2;
+        self.time_properties_ds = TimePropertiesDS {
+            currentUtcOffset: announce.currentUtcOffset,
+            flags,
+            timeSource: announce.timeSource,
+        };
+    }
+}
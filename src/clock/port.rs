@@ -0,0 +1,290 @@
+use crate::clock::bmc::Dataset;
+use crate::clock::transparent::TWO_STEP_FLAG;
+use crate::config::{log_interval, DelayMechanism, PortConfig};
+use crate::protocol::types::datasets::PortDS;
+use crate::protocol::types::derived::{PortIdentity, TimeInterval, Timestamp};
+use crate::protocol::types::enums::values::{self, PortState};
+use crate::protocol::types::enums::Enumeration;
+use crate::protocol::types::message::{body, Body, Message};
+use crate::protocol::types::primitive::int::Int;
+use crate::protocol::types::primitive::{Octet, UInteger16};
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Number of Announce messages needed to qualify a foreign master
+pub const FOREIGN_MASTER_THRESHOLD: usize = 2;
+/// Window, in announce intervals, in which the threshold has to be reached
+pub const FOREIGN_MASTER_TIME_WINDOW: u32 = 4;
+
+fn nanoseconds(timestamp: &Timestamp) -> i128 {
+    i128::from(timestamp.secondsField.into_inner()) * 1_000_000_000
+        + i128::from(timestamp.nanosecondsField.into_inner())
+}
+
+/// correctionField in nanoseconds
+fn correction(message: &Message) -> i128 {
+    i128::from(message.header.correctionField.scaled() >> 16)
+}
+
+/// Timestamps of the delay request-response mechanism (IEEE 1588-2008, 11.3),
+/// in nanoseconds
+#[derive(Debug, Clone, Copy, Default)]
+struct Exchange {
+    /// sequenceId, t2 and correction of a two-step Sync waiting for its Follow_Up
+    sync: Option<(u16, i128, i128)>,
+    /// t2 and t2 - t1 less corrections
+    master_to_slave: Option<(i128, i128)>,
+    /// sequenceId and t3 of the last Delay_Req sent
+    delay_req: Option<(u16, i128)>,
+    /// t2 and offset of the previous measurement
+    last: Option<(i128, i64)>,
+}
+
+/// Result of a completed delay request-response exchange, in nanoseconds
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Measurement {
+    pub offset: i64,
+    pub mean_path_delay: i64,
+    /// Frequency adjustment in ppb compensating the drift of the offset since
+    /// the previous measurement
+    pub freq: Option<f64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ForeignMaster {
+    pub announce: body::Announce,
+    /// Second octet of the Announce flagField, holding the time properties flags
+    pub flags: Octet,
+    received: Vec<Instant>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Port {
+    pub identity: PortIdentity,
+    pub config: PortConfig,
+    pub state: PortState,
+    pub foreign_masters: HashMap<PortIdentity, ForeignMaster>,
+    /// Time after which a port without a master stops listening
+    pub announce_receipt_deadline: Instant,
+    pub next_announce: Instant,
+    pub announce_sequence: UInteger16,
+    pub next_delay_req: Instant,
+    pub delay_req_sequence: UInteger16,
+    exchange: Exchange,
+    /// Whether a Pdelay_Req was received, which makes Auto use P2P
+    peer_delay: bool,
+}
+
+impl Port {
+    pub fn new(identity: PortIdentity, config: PortConfig, now: Instant) -> Self {
+        let mut port = Port {
+            identity,
+            config,
+            state: PortState::LISTENING,
+            foreign_masters: HashMap::new(),
+            announce_receipt_deadline: now,
+            next_announce: now,
+            announce_sequence: UInteger16::default(),
+            next_delay_req: now,
+            delay_req_sequence: UInteger16::default(),
+            exchange: Exchange::default(),
+            peer_delay: false,
+        };
+        port.reset_announce_receipt_timeout(now);
+        port
+    }
+
+    /// portDS of this port
+    pub fn dataset(&self) -> PortDS {
+        PortDS {
+            portIdentity: self.identity,
+            portState: Enumeration::Enum(self.state),
+            logMinDelayReqInterval: Int::new(self.config.log_min_delay_req_interval),
+            peerMeanPathDelay: TimeInterval::new(0),
+            logAnnounceInterval: Int::new(self.config.log_announce_interval),
+            announceReceiptTimeout: Int::new(self.config.announce_receipt_timeout),
+            logSyncInterval: Int::new(self.config.log_sync_interval),
+            delayMechanism: Enumeration::Enum(self.delay_mechanism()),
+            logMinPdelayReqInterval: Int::new(self.config.log_min_pdelay_req_interval),
+            _reserved: Int::new(0),
+            versionNumber: Int::new(2),
+        }
+    }
+
+    /// Configured mechanism, Auto resolved to the one in use
+    pub fn delay_mechanism(&self) -> values::DelayMechanism {
+        match self.config.delay_mechanism {
+            DelayMechanism::E2E => values::DelayMechanism::E2E,
+            DelayMechanism::P2P => values::DelayMechanism::P2P,
+            DelayMechanism::Auto if self.peer_delay => values::DelayMechanism::P2P,
+            DelayMechanism::Auto => values::DelayMechanism::E2E,
+        }
+    }
+
+    /// Notes a Pdelay_Req from a peer
+    pub fn peer_delay_requested(&mut self) {
+        self.peer_delay = true;
+    }
+
+    pub fn announce_interval(&self) -> Duration {
+        log_interval(self.config.log_announce_interval)
+    }
+
+    pub fn announce_receipt_timeout(&self) -> Duration {
+        self.announce_interval() * self.config.announce_receipt_timeout.into()
+    }
+
+    pub fn delay_req_interval(&self) -> Duration {
+        log_interval(self.config.log_min_delay_req_interval)
+    }
+
+    /// Whether a Sync from the master was received, so that a Delay_Req is useful
+    pub fn synchronizing(&self) -> bool {
+        self.exchange.master_to_slave.is_some()
+    }
+
+    /// Forgets the timestamps of the exchange with the previous master
+    pub fn reset_exchange(&mut self) {
+        self.exchange = Exchange::default();
+    }
+
+    /// Records the transmit time of a Delay_Req, t3
+    pub fn transmitted(&mut self, message: &Message, sent: Duration) {
+        if let Body::Delay_Req(_) = message.body {
+            self.exchange.delay_req = Some((
+                message.header.sequenceId.into_inner(),
+                sent.as_nanos() as i128,
+            ));
+        }
+    }
+
+    /// Takes the timestamps of an event message of the master received at
+    /// `received` and returns the measurement it completes
+    pub fn measure(&mut self, message: &Message, received: Duration) -> Option<Measurement> {
+        let sequence_id = message.header.sequenceId.into_inner();
+        let exchange = &mut self.exchange;
+        let slave_to_master = match &message.body {
+            Body::Sync(sync) => {
+                let t2 = received.as_nanos() as i128;
+                if message.header.flagField[0].into_inner() & TWO_STEP_FLAG != 0 {
+                    exchange.sync = Some((sequence_id, t2, correction(message)));
+                } else {
+                    exchange.master_to_slave = Some((
+                        t2,
+                        t2 - nanoseconds(&sync.originTimestamp) - correction(message),
+                    ));
+                }
+                return None;
+            }
+            Body::Follow_Up(follow_up) => {
+                if let Some((_, t2, sync_correction)) = exchange
+                    .sync
+                    .take()
+                    .filter(|&(sequence, _, _)| sequence == sequence_id)
+                {
+                    exchange.master_to_slave = Some((
+                        t2,
+                        t2 - nanoseconds(&follow_up.preciseOriginTimestamp)
+                            - sync_correction
+                            - correction(message),
+                    ));
+                }
+                return None;
+            }
+            Body::Delay_Resp(delay_resp) if delay_resp.requestingPortIdentity == self.identity => {
+                let (_, t3) = exchange
+                    .delay_req
+                    .filter(|&(sequence, _)| sequence == sequence_id)?;
+                nanoseconds(&delay_resp.receiveTimestamp) - t3 - correction(message)
+            }
+            _ => return None,
+        };
+        let (t2, master_to_slave) = exchange.master_to_slave?;
+        let mean_path_delay = ((master_to_slave + slave_to_master) / 2) as i64;
+        let offset = master_to_slave as i64 - mean_path_delay;
+        let freq =
+            exchange
+                .last
+                .filter(|&(last_t2, _)| t2 > last_t2)
+                .map(|(last_t2, last_offset)| {
+                    -((offset - last_offset) as f64) / (t2 - last_t2) as f64 * 1e9
+                });
+        exchange.last = Some((t2, offset));
+        Some(Measurement {
+            offset,
+            mean_path_delay,
+            freq,
+        })
+    }
+
+    /// Restarts the timers with the configured intervals, e.g. after they changed
+    pub fn restart_timers(&mut self, now: Instant) {
+        self.next_announce = now + self.announce_interval();
+        self.next_delay_req = now + self.delay_req_interval();
+        self.reset_announce_receipt_timeout(now);
+    }
+
+    pub fn reset_announce_receipt_timeout(&mut self, now: Instant) {
+        self.announce_receipt_deadline = now + self.announce_receipt_timeout();
+    }
+
+    /// Records an Announce message received from a foreign master
+    pub fn add_foreign_master(
+        &mut self,
+        identity: PortIdentity,
+        announce: body::Announce,
+        flags: Octet,
+        now: Instant,
+    ) {
+        let window = self.announce_interval() * FOREIGN_MASTER_TIME_WINDOW;
+        let master = self
+            .foreign_masters
+            .entry(identity)
+            .or_insert_with(|| ForeignMaster {
+                announce,
+                flags,
+                received: Vec::new(),
+            });
+        master.announce = announce;
+        master.flags = flags;
+        master.received.retain(|&at| at + window > now);
+        master.received.push(now);
+    }
+
+    /// Forgets foreign masters that stopped sending Announce messages
+    pub fn expire_foreign_masters(&mut self, now: Instant) {
+        let timeout = self.announce_receipt_timeout();
+        self.foreign_masters
+            .retain(|_, master| master.received.last().is_some_and(|&at| at + timeout > now));
+    }
+
+    /// Best qualified foreign master seen on this port, Erbest in IEEE 1588
+    pub fn best_foreign_master(
+        &self,
+        now: Instant,
+    ) -> Option<(Dataset, &PortIdentity, &ForeignMaster)> {
+        let window = self.announce_interval() * FOREIGN_MASTER_TIME_WINDOW;
+        self.foreign_masters
+            .iter()
+            .filter(|(_, master)| {
+                master
+                    .received
+                    .iter()
+                    .filter(|&&at| at + window > now)
+                    .count()
+                    >= FOREIGN_MASTER_THRESHOLD
+            })
+            .map(|(identity, master)| {
+                (
+                    Dataset::from_announce(&master.announce, identity, &self.identity),
+                    identity,
+                    master,
+                )
+            })
+            .fold(None, |best, (dataset, identity, master)| match best {
+                Some((best_dataset, _, _)) if !dataset.compare(&best_dataset).is_better() => best,
+                _ => Some((dataset, identity, master)),
+            })
+    }
+}
@@ -0,0 +1,202 @@
+use crate::capture::{extract_ptp, Direction, Packet};
+use crate::clock::Clock;
+use crate::protocol::parser::parse_ptp_message;
+use crate::protocol::types::derived::{ClockIdentity, PortIdentity};
+use crate::protocol::types::enums::values::PortState;
+use crate::protocol::types::message::Body;
+use crate::stats::{Counters, ServoSummary};
+
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Interval at which the daemon runs the clock's timers, reproduced between packets
+pub const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Change of the clock's state observed while replaying
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Event {
+    PortState {
+        port: usize,
+        state: PortState,
+    },
+    Parent(PortIdentity),
+    /// offsetFromMaster and meanPathDelay of a slave port in nanoseconds
+    Measurement {
+        port: usize,
+        offset: i64,
+        mean_path_delay: i64,
+    },
+    /// Statistics of the measurements of the last summary interval
+    Summary(ServoSummary),
+}
+
+/// Event with the capture timestamp of the packet or timer that caused it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Record {
+    pub timestamp: Duration,
+    pub event: Event,
+}
+
+impl fmt::Display for Record {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}.{:09} ",
+            self.timestamp.as_secs(),
+            self.timestamp.subsec_nanos()
+        )?;
+        match self.event {
+            Event::PortState { port, state } => write!(f, "port {} state {}", port + 1, state),
+            Event::Parent(parent) => write!(f, "parent {}", parent),
+            Event::Measurement {
+                port,
+                offset,
+                mean_path_delay,
+            } => write!(
+                f,
+                "port {} offset {} ns mean path delay {} ns",
+                port + 1,
+                offset,
+                mean_path_delay
+            ),
+            Event::Summary(summary) => write!(f, "summary {}", summary),
+        }
+    }
+}
+
+/// Drives a clock from captured packets, using the capture timestamps as a
+/// virtual clock so that a capture always reproduces the same decisions
+#[derive(Debug)]
+pub struct Replay {
+    pub clock: Clock,
+    /// Virtual time of the first packet, the clock is expected to be created at it
+    start: Instant,
+    first: Option<Duration>,
+    next_poll: Duration,
+    states: Vec<PortState>,
+    parent: PortIdentity,
+    pub counters: Counters,
+    pub records: Vec<Record>,
+}
+
+impl Replay {
+    pub fn new(clock: Clock, start: Instant) -> Self {
+        Replay {
+            states: clock.ports.iter().map(|port| port.state).collect(),
+            parent: clock.parent_ds.parentPortIdentity,
+            counters: Counters::default(),
+            clock,
+            start,
+            first: None,
+            next_poll: Duration::from_secs(0),
+            records: Vec::new(),
+        }
+    }
+
+    fn instant(&self, timestamp: Duration) -> Instant {
+        self.start + timestamp.saturating_sub(self.first.unwrap_or(timestamp))
+    }
+
+    /// Runs the clock's timers and records the resulting changes
+    fn poll(&mut self, timestamp: Duration) {
+        self.clock.poll(self.instant(timestamp));
+        for (idx, port) in self.clock.ports.iter().enumerate() {
+            if port.state != self.states[idx] {
+                self.states[idx] = port.state;
+                self.records.push(Record {
+                    timestamp,
+                    event: Event::PortState {
+                        port: idx,
+                        state: port.state,
+                    },
+                });
+            }
+        }
+        if self.clock.parent_ds.parentPortIdentity != self.parent {
+            self.parent = self.clock.parent_ds.parentPortIdentity;
+            self.records.push(Record {
+                timestamp,
+                event: Event::Parent(self.parent),
+            });
+        }
+    }
+
+    /// Feeds a packet captured on the port with the same index as its
+    /// interface, transmitted packets only provide Delay_Req timestamps when
+    /// they were taken by the kernel
+    pub fn packet(&mut self, packet: &Packet) {
+        let port = packet.interface as usize;
+        let message = match extract_ptp(packet.link_type, &packet.data)
+            .map(|datagram| parse_ptp_message(datagram.payload))
+        {
+            Some(Ok((_, message))) if port < self.clock.ports.len() => message,
+            Some(Err(_)) => return self.counters.unparseable(),
+            _ => return,
+        };
+        let timestamp = packet.timestamp;
+        if self.first.is_none() {
+            self.first = Some(timestamp);
+            self.next_poll = timestamp;
+        }
+        while self.next_poll <= timestamp {
+            self.poll(self.next_poll);
+            self.next_poll += POLL_INTERVAL;
+        }
+
+        if packet.direction == Direction::Outbound {
+            self.counters.sent(&message);
+            if !packet.estimated {
+                self.clock.transmitted(port, &message, timestamp);
+            }
+            return;
+        }
+        if self.clock.accepts(&message) {
+            self.counters.received(&message);
+        } else {
+            self.counters.dropped(&message);
+        }
+        self.clock.handle(port, &message, self.instant(timestamp));
+        // A Sync received without a kernel timestamp can't be measured
+        let measurement = match message.body {
+            Body::Sync(_) if packet.estimated => None,
+            _ => self.clock.measure(port, &message, timestamp),
+        };
+        if let Some(measurement) = measurement {
+            self.records.push(Record {
+                timestamp,
+                event: Event::Measurement {
+                    port,
+                    offset: measurement.offset,
+                    mean_path_delay: measurement.mean_path_delay,
+                },
+            });
+        }
+        if let Some(summary) = self.clock.take_summary() {
+            self.records.push(Record {
+                timestamp,
+                event: Event::Summary(summary),
+            });
+        }
+        self.poll(timestamp);
+    }
+
+    /// Feeds all packets, then runs the timers up to the end of the capture
+    pub fn run(&mut self, packets: &[Packet]) {
+        for packet in packets {
+            self.packet(packet);
+        }
+        if let Some(last) = packets.last() {
+            self.poll(last.timestamp);
+        }
+    }
+}
+
+/// Identity of the clock that transmitted the first outbound PTP message of a capture
+pub fn recorded_identity(packets: &[Packet]) -> Option<ClockIdentity> {
+    packets
+        .iter()
+        .filter(|packet| packet.direction == Direction::Outbound)
+        .filter_map(|packet| extract_ptp(packet.link_type, &packet.data))
+        .find_map(|datagram| parse_ptp_message(datagram.payload).ok())
+        .map(|(_, message)| message.header.sourcePortIdentity.clockIdentity)
+}
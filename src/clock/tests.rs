@@ -0,0 +1,611 @@
+use super::*;
+use crate::config::PortConfig;
+use crate::protocol::types::datasets;
+use crate::protocol::types::primitive::int::BitSerialize;
+use crate::protocol::types::primitive::UInteger16;
+
+use std::time::Duration;
+
+fn identity(last: u8) -> ClockIdentity {
+    [
+        Int::new(0),
+        Int::new(0),
+        Int::new(0),
+        Int::new(0xFF),
+        Int::new(0xFE),
+        Int::new(0),
+        Int::new(0),
+        Int::new(last),
+    ]
+}
+
+#[test]
+pub fn test_boundary_clock() {
+    let start = Instant::now();
+    let interval = Duration::from_secs(2);
+    let gm_config = ClockConfig {
+        priority1: 10,
+        ..ClockConfig::default()
+    };
+    let gm = Clock::new(identity(1), gm_config, &[PortConfig::default()], start);
+    let mut bc = Clock::new(
+        identity(2),
+        ClockConfig::default(),
+        &[PortConfig::default(); 2],
+        start,
+    );
+    assert_eq!(bc.ports[1].identity.portNumber, Int::new(2));
+    assert_eq!(bc.ports[1].identity.clockIdentity, identity(2));
+    assert!(bc.poll(start).is_empty());
+    assert_eq!(bc.ports[0].state, PortState::LISTENING);
+
+    // Grandmaster becomes qualified after two Announce messages
+    let mut announce = gm.announce();
+    announce.stepsRemoved = Int::new(3);
+    let mut message = Message {
+        header: Header::new(
+            values::MessageType::Announce,
+            64,
+            0,
+            gm.ports[0].identity,
+            0,
+            1,
+        ),
+        body: Body::Announce(announce),
+    };
+    message.header.flagField[1] = Int::new(datasets::TIME_PTP | datasets::TIME_UTCV);
+    bc.handle(0, &message, start);
+    bc.poll(start);
+    assert_eq!(bc.ports[0].state, PortState::LISTENING);
+    bc.handle(0, &message, start + interval);
+    bc.poll(start + interval);
+    assert_eq!(bc.ports[0].state, PortState::SLAVE);
+    assert_eq!(bc.ports[1].state, PortState::LISTENING);
+
+    // Other port takes the master role once its announce receipt timeout expires
+    bc.handle(0, &message, start + interval * 2);
+    bc.handle(0, &message, start + interval * 3);
+    let out = bc.poll(start + interval * 3);
+    assert_eq!(bc.ports[0].state, PortState::SLAVE);
+    assert_eq!(bc.ports[1].state, PortState::MASTER);
+    assert_eq!(bc.parent_ds.parentPortIdentity, gm.ports[0].identity);
+    assert_eq!(bc.parent_ds.grandmasterIdentity, identity(1));
+    assert_eq!(bc.current_ds.stepsRemoved, Int::new(4));
+    assert_eq!(
+        bc.time_properties_ds.flags,
+        Int::new(datasets::TIME_PTP | datasets::TIME_UTCV)
+    );
+    assert_eq!(
+        bc.ports[1].dataset().portState,
+        Enumeration::Enum(values::PortState::MASTER)
+    );
+
+    // Announce forwarded downstream describes the grandmaster
+    assert_eq!(out.len(), 1);
+    assert_eq!(out[0].port, 1);
+    assert_eq!(
+        out[0].message.header.flagField[1],
+        bc.time_properties_ds.flags
+    );
+    assert_eq!(
+        out[0].message.header.sourcePortIdentity,
+        bc.ports[1].identity
+    );
+    match out[0].message.body {
+        Body::Announce(announce) => {
+            assert_eq!(announce.stepsRemoved, Int::new(4));
+            assert_eq!(announce.grandmasterIdentity, identity(1));
+            assert_eq!(announce.grandmasterPriority1, Int::new(10));
+        }
+        _ => panic!("Expected Announce"),
+    }
+
+    // Grandmaster disappears, the clock takes over on both ports
+    let later = start + interval * 10;
+    bc.poll(later);
+    assert_eq!(bc.ports[0].state, PortState::MASTER);
+    assert_eq!(bc.ports[1].state, PortState::MASTER);
+    assert!(!bc.has_parent());
+    assert_eq!(bc.parent_ds.grandmasterIdentity, identity(2));
+    assert_eq!(bc.current_ds.stepsRemoved, Int::new(0));
+
+    // sequenceId rolls over
+    bc.ports[0].announce_sequence = UInteger16::from(u16::MAX);
+    let out = bc.poll(later + interval * 5);
+    let announce = out.iter().find(|output| output.port == 0).unwrap();
+    assert_eq!(announce.message.header.sequenceId.into_inner(), u16::MAX);
+    assert_eq!(bc.ports[0].announce_sequence.into_inner(), 0);
+}
+
+#[test]
+pub fn test_datasets_serialize() {
+    use bitstream_io::{BigEndian, BitWriter};
+
+    let clock = Clock::new(
+        identity(1),
+        ClockConfig::default(),
+        &[PortConfig::default()],
+        Instant::now(),
+    );
+    fn size<T: BitSerialize<Vec<u8>, BigEndian>>(value: T) -> usize {
+        let mut writer = BitWriter::endian(Vec::new(), BigEndian);
+        value.bit_serialize(&mut writer).unwrap();
+        writer.into_writer().len()
+    }
+    assert_eq!(size(clock.default_ds), 20);
+    assert_eq!(size(clock.current_ds), 18);
+    assert_eq!(size(clock.parent_ds), 32);
+    assert_eq!(size(clock.time_properties_ds), 4);
+    assert_eq!(size(clock.ports[0].dataset()), 26);
+}
+
+#[test]
+pub fn test_dataset_comparison() {
+    let quality = ClockQuality {
+        clockClass: Int::new(248),
+        clockAccuracy: 0xFEu8.into(),
+        offsetScaledLogVariance: Int::new(0xFFFF),
+    };
+    let port = |clock, port| PortIdentity {
+        clockIdentity: identity(clock),
+        portNumber: Int::new(port),
+    };
+    let dataset = |gm, priority1, steps, sender, receiver| {
+        Dataset::new(
+            priority1,
+            &identity(gm),
+            &quality,
+            128,
+            steps,
+            &sender,
+            &receiver,
+        )
+    };
+
+    assert_eq!(
+        dataset(1, 127, 0, port(1, 1), port(3, 1)).compare(&dataset(
+            2,
+            128,
+            0,
+            port(2, 1),
+            port(3, 1)
+        )),
+        Comparison::Better
+    );
+    assert_eq!(
+        dataset(2, 128, 0, port(2, 1), port(3, 1)).compare(&dataset(
+            1,
+            128,
+            0,
+            port(1, 1),
+            port(3, 1)
+        )),
+        Comparison::Worse
+    );
+    // Same grandmaster, shorter path wins
+    assert_eq!(
+        dataset(1, 128, 1, port(4, 1), port(3, 1)).compare(&dataset(
+            1,
+            128,
+            3,
+            port(5, 1),
+            port(3, 2)
+        )),
+        Comparison::Better
+    );
+    assert_eq!(
+        dataset(1, 128, 2, port(4, 1), port(3, 1)).compare(&dataset(
+            1,
+            128,
+            1,
+            port(5, 1),
+            port(3, 2)
+        )),
+        Comparison::Worse
+    );
+    assert_eq!(
+        dataset(1, 128, 2, port(2, 1), port(3, 1)).compare(&dataset(
+            1,
+            128,
+            1,
+            port(5, 1),
+            port(3, 2)
+        )),
+        Comparison::WorseByTopology
+    );
+    // Same path length, decided by sender and receiver identities
+    assert_eq!(
+        dataset(1, 128, 1, port(4, 1), port(3, 1)).compare(&dataset(
+            1,
+            128,
+            1,
+            port(5, 1),
+            port(3, 2)
+        )),
+        Comparison::BetterByTopology
+    );
+    assert_eq!(
+        dataset(1, 128, 1, port(4, 1), port(3, 2)).compare(&dataset(
+            1,
+            128,
+            1,
+            port(4, 1),
+            port(3, 1)
+        )),
+        Comparison::WorseByTopology
+    );
+}
+
+#[test]
+pub fn test_transparent_clock() {
+    use transparent::{Mode, TransparentClock, TWO_STEP_FLAG};
+
+    let us = Duration::from_micros;
+    let scaled = |ns: i64| TimeInterval::new(ns << 16);
+    let master = PortIdentity {
+        clockIdentity: identity(1),
+        portNumber: Int::new(1),
+    };
+    let slave = PortIdentity {
+        clockIdentity: identity(2),
+        portNumber: Int::new(1),
+    };
+    let timestamp = Timestamp {
+        secondsField: Int::new(0),
+        nanosecondsField: Int::new(0),
+    };
+    let mut sync = Message {
+        header: Header::new(values::MessageType::Sync, 44, 0, master, 7, 0),
+        body: Body::Sync(body::Sync {
+            originTimestamp: timestamp,
+        }),
+    };
+    sync.header.flagField[0] = Int::new(TWO_STEP_FLAG);
+    let mut follow_up = Message {
+        header: Header::new(values::MessageType::Follow_Up, 44, 0, master, 7, 0),
+        body: Body::Follow_Up(body::Follow_Up {
+            preciseOriginTimestamp: timestamp,
+        }),
+    };
+    follow_up.header.correctionField = scaled(10);
+    let delay_req = Message {
+        header: Header::new(values::MessageType::Delay_Req, 44, 0, slave, 3, 0),
+        body: Body::Delay_Req(body::Delay_Req {
+            originTimestamp: timestamp,
+        }),
+    };
+    let delay_resp = Message {
+        header: Header::new(values::MessageType::Delay_Resp, 54, 0, master, 3, 0),
+        body: Body::Delay_Resp(body::Delay_Resp {
+            receiveTimestamp: timestamp,
+            requestingPortIdentity: slave,
+        }),
+    };
+
+    // Two-step: residence times end up in Follow_Up and Delay_Resp
+    let mut tc = TransparentClock::new(2, Mode::TwoStep);
+    let out = tc.forward(0, &sync, us(100), us(101));
+    assert_eq!(out.len(), 1);
+    assert_eq!(out[0].port, 1);
+    assert_eq!(out[0].message, sync);
+    tc.transmitted(1, &out[0].message, us(103));
+    let out = tc.forward(0, &follow_up, us(200), us(200));
+    assert_eq!(out[0].message.header.correctionField, scaled(10 + 3000));
+
+    let out = tc.forward(1, &delay_req, us(300), us(300));
+    assert_eq!(out[0].port, 0);
+    assert_eq!(out[0].message, delay_req);
+    tc.transmitted(0, &out[0].message, us(305));
+    let out = tc.forward(0, &delay_resp, us(400), us(400));
+    assert_eq!(out[0].port, 1);
+    assert_eq!(out[0].message.header.correctionField, scaled(5000));
+    // Residence times are used only once
+    let out = tc.forward(0, &delay_resp, us(400), us(400));
+    assert_eq!(out[0].message.header.correctionField, scaled(0));
+
+    // Three ports: every copy of the Delay_Resp carries the residence time, whichever
+    // port the requester is behind
+    let mut tc = TransparentClock::new(3, Mode::TwoStep);
+    let out = tc.forward(2, &delay_req, us(300), us(300));
+    assert_eq!(out.len(), 2);
+    tc.transmitted(0, &out[0].message, us(304));
+    tc.transmitted(1, &out[1].message, us(306));
+    let out = tc.forward(0, &delay_resp, us(400), us(400));
+    assert_eq!(out.len(), 2);
+    assert_eq!(out[0].port, 1);
+    assert_eq!(out[1].port, 2);
+    assert_eq!(out[0].message.header.correctionField, scaled(4000));
+    assert_eq!(out[1].message.header.correctionField, scaled(4000));
+    // The copy forwarded on port 1 isn't kept around
+    let out = tc.forward(1, &delay_resp, us(400), us(400));
+    assert_eq!(out[1].message.header.correctionField, scaled(0));
+
+    // One-step: residence time is added to the event message
+    let mut tc = TransparentClock::new(3, Mode::OneStep);
+    let out = tc.forward(2, &sync, us(100), us(102));
+    assert_eq!(out.len(), 2);
+    assert_eq!(out[1].port, 1);
+    assert_eq!(out[1].message.header.correctionField, scaled(2000));
+    let out = tc.forward(2, &follow_up, us(100), us(102));
+    assert_eq!(out[0].message, follow_up);
+}
+
+#[test]
+pub fn test_transparent_clock_relay() {
+    use crate::protocol::parser::parse_ptp_message;
+    use std::collections::VecDeque;
+    use transparent::{Mode, TransparentClock, TWO_STEP_FLAG};
+
+    let us = Duration::from_micros;
+    let master = PortIdentity {
+        clockIdentity: identity(1),
+        portNumber: Int::new(1),
+    };
+    let timestamp = Timestamp {
+        secondsField: Int::new(0),
+        nanosecondsField: Int::new(0),
+    };
+    let mut sync = Message {
+        header: Header::new(values::MessageType::Sync, 44, 0, master, 7, 0),
+        body: Body::Sync(body::Sync {
+            originTimestamp: timestamp,
+        }),
+    };
+    sync.header.flagField[0] = Int::new(TWO_STEP_FLAG);
+    let follow_up = Message {
+        header: Header::new(values::MessageType::Follow_Up, 44, 0, master, 7, 0),
+        body: Body::Follow_Up(body::Follow_Up {
+            preciseOriginTimestamp: timestamp,
+        }),
+    };
+
+    // Two in-memory ports, the sender fails while `link_up` is false
+    let mut wire: Vec<VecDeque<Vec<u8>>> = vec![VecDeque::new(), VecDeque::new()];
+    let mut tc = TransparentClock::new(2, Mode::TwoStep);
+    let relay = |tc: &mut TransparentClock,
+                 wire: &mut Vec<VecDeque<Vec<u8>>>,
+                 message: Message,
+                 link_up: bool| {
+        let bytes = message.to_bytes();
+        let (_, received) = parse_ptp_message(&bytes).unwrap();
+        tc.relay(0, &received, us(100), us(100), |forward| {
+            if !link_up {
+                return None;
+            }
+            wire[forward.port].push_back(forward.message.clone().to_bytes());
+            Some(us(102))
+        });
+    };
+
+    relay(&mut tc, &mut wire, sync.clone(), true);
+    relay(&mut tc, &mut wire, follow_up.clone(), true);
+    assert!(wire[0].is_empty());
+    let (_, sent) = parse_ptp_message(&wire[1].pop_front().unwrap()).unwrap();
+    assert_eq!(sent, sync);
+    let (_, sent) = parse_ptp_message(&wire[1].pop_front().unwrap()).unwrap();
+    assert_eq!(sent.header.correctionField, TimeInterval::new(2000 << 16));
+
+    // A Sync that couldn't be sent gives no residence time to its Follow_Up
+    relay(&mut tc, &mut wire, sync, false);
+    relay(&mut tc, &mut wire, follow_up.clone(), true);
+    let (_, sent) = parse_ptp_message(&wire[1].pop_front().unwrap()).unwrap();
+    assert_eq!(sent, follow_up);
+    assert!(wire[1].is_empty());
+}
+
+#[test]
+pub fn test_delay_request_response() {
+    let start = Instant::now();
+    let base = Duration::from_secs(1_700_000_000);
+    let ms = Duration::from_millis;
+    let us = Duration::from_micros;
+    let gm_config = ClockConfig {
+        priority1: 10,
+        ..ClockConfig::default()
+    };
+    let gm = Clock::new(identity(1), gm_config, &[PortConfig::default()], start);
+    let master = gm.ports[0].identity;
+    let mut clock = Clock::new(
+        identity(2),
+        ClockConfig::default(),
+        &[PortConfig::default()],
+        start,
+    );
+    let announce = Message {
+        header: Header::new(values::MessageType::Announce, 64, 0, master, 0, 1),
+        body: Body::Announce(gm.announce()),
+    };
+    clock.handle(0, &announce, start);
+    clock.handle(0, &announce, start + ms(10));
+    assert!(clock.poll(start + ms(10)).is_empty());
+    assert_eq!(clock.ports[0].state, PortState::SLAVE);
+
+    // 60 us path delay with the slave 40 us ahead of the master, the frequency
+    // of the slave is 1 ppm too high
+    let exchange = |clock: &mut Clock, at: Duration, drift: Duration, sequence_id| {
+        let sync = Message {
+            header: Header::new(values::MessageType::Sync, 44, 0, master, sequence_id, 0),
+            body: Body::Sync(body::Sync {
+                originTimestamp: Timestamp::from(base + at),
+            }),
+        };
+        assert_eq!(clock.measure(0, &sync, base + at + us(100) + drift), None);
+        let out = clock.poll(start + at);
+        assert_eq!(out.len(), 1);
+        assert_eq!(
+            out[0].message.header.messageType,
+            Enumeration::Enum(values::MessageType::Delay_Req)
+        );
+        clock.transmitted(0, &out[0].message, base + at + ms(1) + drift);
+        let delay_resp = Message {
+            header: Header::new(
+                values::MessageType::Delay_Resp,
+                54,
+                0,
+                master,
+                out[0].message.header.sequenceId.into_inner(),
+                0,
+            ),
+            body: Body::Delay_Resp(body::Delay_Resp {
+                receiveTimestamp: Timestamp::from(base + at + ms(1) + us(20)),
+                requestingPortIdentity: clock.ports[0].identity,
+            }),
+        };
+        clock.measure(0, &delay_resp, base + at + ms(2))
+    };
+    let measurement = exchange(&mut clock, ms(100), us(0), 0).unwrap();
+    assert_eq!(measurement.offset, 40_000);
+    assert_eq!(measurement.mean_path_delay, 60_000);
+    assert_eq!(measurement.freq, None);
+    assert_eq!(clock.take_summary(), None);
+    assert_eq!(
+        clock.current_ds.meanPathDelay,
+        TimeInterval::from_nanoseconds(60_000.0)
+    );
+    let measurement = exchange(&mut clock, ms(1100), us(1), 1).unwrap();
+    assert_eq!(measurement.offset, 41_000);
+    assert!((measurement.freq.unwrap() + 1000.0).abs() < 1.0);
+    assert_eq!(
+        clock.current_ds.offsetFromMaster,
+        TimeInterval::from_nanoseconds(41_000.0)
+    );
+    // Both measurements fall into the one second summary interval
+    let summary = clock.take_summary().unwrap();
+    assert_eq!(summary.offset.count, 2);
+    assert_eq!(summary.offset.max, 41_000.0);
+    assert_eq!(summary.delay.unwrap().mean, 60_000.0);
+    assert_eq!(summary.freq.unwrap().count, 1);
+    assert_eq!(clock.take_summary(), None);
+}
+
+#[test]
+pub fn test_replay() {
+    use crate::capture::writer::ipv4_udp;
+    use crate::capture::{Direction, Packet, LINKTYPE_RAW};
+    use replay::{recorded_identity, Event, Record, Replay};
+
+    let base = Duration::from_secs(1_700_000_000);
+    let ms = Duration::from_millis;
+    let timestamp = Timestamp::from;
+    let gm_config = ClockConfig {
+        priority1: 10,
+        ..ClockConfig::default()
+    };
+    let start = Instant::now();
+    let gm = Clock::new(identity(1), gm_config, &[PortConfig::default()], start);
+    let master = gm.ports[0].identity;
+    let slave = PortIdentity {
+        clockIdentity: identity(2),
+        portNumber: Int::new(1),
+    };
+    let packet = |at: Duration, direction, message: Message| Packet {
+        timestamp: base + at,
+        interface: 0,
+        direction,
+        link_type: LINKTYPE_RAW,
+        data: ipv4_udp(
+            "192.168.1.10:319".parse().unwrap(),
+            "224.0.1.129:319".parse().unwrap(),
+            &message.to_bytes(),
+        ),
+        estimated: false,
+    };
+    let announce = |sequence_id| Message {
+        header: Header::new(values::MessageType::Announce, 64, 0, master, sequence_id, 1),
+        body: Body::Announce(gm.announce()),
+    };
+    let mut sync = Message {
+        header: Header::new(values::MessageType::Sync, 44, 0, master, 1, 0),
+        body: Body::Sync(body::Sync {
+            originTimestamp: timestamp(Duration::from_secs(0)),
+        }),
+    };
+    sync.header.flagField[0] = Int::new(transparent::TWO_STEP_FLAG);
+    let mut follow_up = Message {
+        header: Header::new(values::MessageType::Follow_Up, 44, 0, master, 1, 0),
+        body: Body::Follow_Up(body::Follow_Up {
+            preciseOriginTimestamp: timestamp(base + ms(3000)),
+        }),
+    };
+    follow_up.header.correctionField = TimeInterval::new(5_000 << 16);
+    let delay_req = Message {
+        header: Header::new(values::MessageType::Delay_Req, 44, 0, slave, 9, 0x7F),
+        body: Body::Delay_Req(body::Delay_Req {
+            originTimestamp: timestamp(Duration::from_secs(0)),
+        }),
+    };
+    // 60 us path delay with the slave 40 us ahead of the master
+    let delay_resp = Message {
+        header: Header::new(values::MessageType::Delay_Resp, 54, 0, master, 9, 0),
+        body: Body::Delay_Resp(body::Delay_Resp {
+            receiveTimestamp: timestamp(base + ms(3100) + Duration::from_micros(20)),
+            requestingPortIdentity: slave,
+        }),
+    };
+    let packets = vec![
+        packet(ms(0), Direction::Inbound, announce(0)),
+        packet(ms(2000), Direction::Inbound, announce(1)),
+        packet(
+            ms(3000) + Duration::from_micros(105),
+            Direction::Inbound,
+            sync,
+        ),
+        packet(ms(3001), Direction::Inbound, follow_up),
+        packet(ms(3100), Direction::Outbound, delay_req),
+        packet(ms(3101), Direction::Inbound, delay_resp),
+    ];
+    assert_eq!(recorded_identity(&packets), Some(identity(2)));
+
+    let run = |start, packets: &[Packet]| {
+        let clock = Clock::new(
+            identity(2),
+            ClockConfig::default(),
+            &[PortConfig::default()],
+            start,
+        );
+        let mut replay = Replay::new(clock, start);
+        replay.run(packets);
+        replay
+    };
+    let replay = run(start, &packets);
+    assert_eq!(
+        replay.records,
+        vec![
+            Record {
+                timestamp: base + ms(2000),
+                event: Event::PortState {
+                    port: 0,
+                    state: PortState::SLAVE
+                }
+            },
+            Record {
+                timestamp: base + ms(2000),
+                event: Event::Parent(master)
+            },
+            Record {
+                timestamp: base + ms(3101),
+                event: Event::Measurement {
+                    port: 0,
+                    offset: 40_000,
+                    mean_path_delay: 60_000
+                }
+            },
+        ]
+    );
+    assert_eq!(
+        replay.clock.current_ds.offsetFromMaster,
+        TimeInterval::from_nanoseconds(40_000.0)
+    );
+    assert_eq!(replay.counters.received.iter().sum::<u64>(), 5);
+    assert_eq!(replay.counters.sent.iter().sum::<u64>(), 1);
+    // The virtual clock makes the outcome independent of the wall clock
+    assert_eq!(
+        run(start + Duration::from_secs(1000), &packets).records,
+        replay.records
+    );
+
+    // A Sync without a kernel receive timestamp completes no measurement
+    let mut estimated = packets.clone();
+    estimated[2].estimated = true;
+    assert_eq!(run(start, &estimated).records, replay.records[..2]);
+}
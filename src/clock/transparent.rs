@@ -0,0 +1,213 @@
+use crate::protocol::types::derived::PortIdentity;
+use crate::protocol::types::enums::{values, Enumeration};
+use crate::protocol::types::message::{Body, Header, Message};
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// twoStepFlag in the first octet of flagField
+pub const TWO_STEP_FLAG: u8 = 0x02;
+/// Residence times kept while waiting for the matching Follow_Up or Delay_Resp
+const MAX_PENDING: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Residence time is added to the event message itself
+    OneStep,
+    /// Residence time is added to the Follow_Up or Delay_Resp
+    TwoStep,
+}
+
+/// Copy of a received message to be transmitted on `port`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Forward {
+    pub port: usize,
+    pub message: Message,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Residence {
+    source: PortIdentity,
+    sequence_id: u16,
+    message_type: values::MessageType,
+    egress: usize,
+    received: Duration,
+    residence: Option<Duration>,
+}
+
+/// Adds `residence` to correctionField
+pub fn add_correction(header: &mut Header, residence: Duration) {
+    header.correctionField += residence.into();
+}
+
+fn message_type(message: &Message) -> Option<values::MessageType> {
+    match message.header.messageType {
+        Enumeration::Enum(message_type) => Some(message_type),
+        Enumeration::Unknown(_) => None,
+    }
+}
+
+/// End-to-end transparent clock (IEEE 1588-2008, 10.2 and 11.5.2)
+#[derive(Debug)]
+pub struct TransparentClock {
+    ports: usize,
+    mode: Mode,
+    pending: VecDeque<Residence>,
+}
+
+impl TransparentClock {
+    pub fn new(ports: usize, mode: Mode) -> Self {
+        TransparentClock {
+            ports,
+            mode,
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Forwards a message received on port `ingress` at `received` to every other
+    /// port. `now` is used as the transmit time of one-step corrections, which
+    /// are also applied in two-step mode to Sync messages without a Follow_Up.
+    pub fn forward(
+        &mut self,
+        ingress: usize,
+        message: &Message,
+        received: Duration,
+        now: Duration,
+    ) -> Vec<Forward> {
+        let message_type = message_type(message);
+        let two_step = message.header.flagField[0].into_inner() & TWO_STEP_FLAG != 0;
+        // The Delay_Req was forwarded towards the master on `ingress`, its residence
+        // time goes into every copy as the requester may be behind any other port
+        let delay_resp_residence = match (&message.body, message_type) {
+            (Body::Delay_Resp(delay_resp), Some(values::MessageType::Delay_Resp)) => {
+                let (source, sequence_id) = (
+                    delay_resp.requestingPortIdentity,
+                    message.header.sequenceId.into_inner(),
+                );
+                let residence =
+                    self.take(source, sequence_id, values::MessageType::Delay_Req, ingress);
+                // Copies of the Delay_Req sent on other ports won't be answered
+                self.forget(source, sequence_id, values::MessageType::Delay_Req);
+                residence
+            }
+            _ => None,
+        };
+        let mut out = Vec::new();
+        for egress in (0..self.ports).filter(|&port| port != ingress) {
+            let mut message = message.clone();
+            match message_type {
+                Some(values::MessageType::Sync) if self.mode == Mode::TwoStep && two_step => {
+                    self.expect(&message, egress, received)
+                }
+                Some(values::MessageType::Delay_Req) if self.mode == Mode::TwoStep => {
+                    self.expect(&message, egress, received)
+                }
+                Some(message_type) if message_type.is_event() => {
+                    add_correction(&mut message.header, now.saturating_sub(received))
+                }
+                Some(values::MessageType::Follow_Up) => {
+                    if let Some(residence) = self.take(
+                        message.header.sourcePortIdentity,
+                        message.header.sequenceId.into_inner(),
+                        values::MessageType::Sync,
+                        egress,
+                    ) {
+                        add_correction(&mut message.header, residence)
+                    }
+                }
+                Some(values::MessageType::Delay_Resp) => {
+                    if let Some(residence) = delay_resp_residence {
+                        add_correction(&mut message.header, residence)
+                    }
+                }
+                _ => (),
+            }
+            out.push(Forward {
+                port: egress,
+                message,
+            });
+        }
+        out
+    }
+
+    /// Forwards a message like `forward` and transmits the copies with `send`, which
+    /// returns the transmit time of event messages that actually left
+    pub fn relay<F>(
+        &mut self,
+        ingress: usize,
+        message: &Message,
+        received: Duration,
+        now: Duration,
+        mut send: F,
+    ) where
+        F: FnMut(&Forward) -> Option<Duration>,
+    {
+        for forward in self.forward(ingress, message, received, now) {
+            if let Some(sent) = send(&forward) {
+                self.transmitted(forward.port, &forward.message, sent);
+            }
+        }
+    }
+
+    /// Records the transmit time of an event message previously returned by `forward`
+    pub fn transmitted(&mut self, egress: usize, message: &Message, sent: Duration) {
+        let (source, sequence_id) = (
+            message.header.sourcePortIdentity,
+            message.header.sequenceId.into_inner(),
+        );
+        if let Some(pending) = self.pending.iter_mut().find(|pending| {
+            pending.source == source
+                && pending.sequence_id == sequence_id
+                && Some(pending.message_type) == message_type(message)
+                && pending.egress == egress
+        }) {
+            pending.residence = Some(sent.saturating_sub(pending.received));
+        }
+    }
+
+    fn expect(&mut self, message: &Message, egress: usize, received: Duration) {
+        if self.pending.len() == MAX_PENDING {
+            self.pending.pop_front();
+        }
+        self.pending.push_back(Residence {
+            source: message.header.sourcePortIdentity,
+            sequence_id: message.header.sequenceId.into_inner(),
+            message_type: message_type(message).unwrap(),
+            egress,
+            received,
+            residence: None,
+        });
+    }
+
+    fn take(
+        &mut self,
+        source: PortIdentity,
+        sequence_id: u16,
+        message_type: values::MessageType,
+        egress: usize,
+    ) -> Option<Duration> {
+        let idx = self.pending.iter().position(|pending| {
+            pending.source == source
+                && pending.sequence_id == sequence_id
+                && pending.message_type == message_type
+                && pending.egress == egress
+        })?;
+        self.pending
+            .remove(idx)
+            .and_then(|pending| pending.residence)
+    }
+
+    /// Drops what is left of a message forwarded to several ports once it was answered
+    fn forget(
+        &mut self,
+        source: PortIdentity,
+        sequence_id: u16,
+        message_type: values::MessageType,
+    ) {
+        self.pending.retain(|pending| {
+            pending.source != source
+                || pending.sequence_id != sequence_id
+                || pending.message_type != message_type
+        });
+    }
+}
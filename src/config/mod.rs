@@ -0,0 +1,529 @@
+use crate::management::DEFAULT_UDS_PATH;
+use crate::protocol::types::derived::{parse_clock_identity, ClockIdentity};
+use crate::unicast::{Limits, DEFAULT_DURATION};
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::net::Ipv4Addr;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+use std::time::Duration;
+
+#[cfg(test)]
+mod tests;
+
+/// log2 intervals in seconds accepted for message intervals, as by linuxptp
+pub const LOG_INTERVAL_RANGE: RangeInclusive<i8> = -7..=16;
+
+/// Converts a log2 interval in seconds to a duration, clamped to `LOG_INTERVAL_RANGE`
+pub fn log_interval(log: i8) -> Duration {
+    let log = log.clamp(*LOG_INTERVAL_RANGE.start(), *LOG_INTERVAL_RANGE.end());
+    Duration::from_secs_f64(2f64.powi(log.into()))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ClockType {
+    OrdinaryClock,
+    BoundaryClock,
+    E2eTransparentClock,
+}
+
+/// Auto starts with E2E and switches to P2P once a peer sends Pdelay_Req
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DelayMechanism {
+    E2E,
+    P2P,
+    Auto,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Transport {
+    UDPv4,
+    UDPv6,
+    L2,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimeStamping {
+    Hardware,
+    Software,
+    Legacy,
+    OneStep,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Profile {
+    Default,
+    G8265_1,
+    G8275_1,
+    G8275_2,
+    GPtp,
+}
+
+macro_rules! impl_from_str {
+    ($name:ident {$($value:literal => $variant:ident),*}) => {
+        impl FromStr for $name {
+            type Err = ();
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                match s {
+                    $(
+                        $value => Ok($name::$variant),
+                    )*
+                    _ => Err(()),
+                }
+            }
+        }
+    };
+}
+
+impl_from_str!(ClockType {
+    "OC" => OrdinaryClock,
+    "BC" => BoundaryClock,
+    "E2E_TC" => E2eTransparentClock
+});
+impl_from_str!(DelayMechanism { "E2E" => E2E, "P2P" => P2P, "Auto" => Auto });
+impl_from_str!(Transport { "UDPv4" => UDPv4, "UDPv6" => UDPv6, "L2" => L2 });
+impl_from_str!(TimeStamping {
+    "hardware" => Hardware,
+    "software" => Software,
+    "legacy" => Legacy,
+    "onestep" => OneStep
+});
+impl_from_str!(Profile {
+    "default" => Default,
+    "g8265.1" => G8265_1,
+    "g8275.1" => G8275_1,
+    "g8275.2" => G8275_2,
+    "gPTP" => GPtp
+});
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ServoConfig {
+    pub pi_proportional_const: f64,
+    pub pi_integral_const: f64,
+    /// Offset in seconds above which the clock is stepped, 0 disables stepping
+    pub step_threshold: f64,
+    /// Same as `step_threshold` but applied only to the first update
+    pub first_step_threshold: f64,
+    /// Maximum frequency adjustment in ppb
+    pub max_frequency: f64,
+}
+
+impl Default for ServoConfig {
+    fn default() -> Self {
+        ServoConfig {
+            pi_proportional_const: 0.7,
+            pi_integral_const: 0.3,
+            step_threshold: 0.0,
+            first_step_threshold: 0.00002,
+            max_frequency: 900000000.0,
+        }
+    }
+}
+
+/// Options that apply to the whole clock, only allowed in `[global]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClockConfig {
+    /// Derived from the first interface's MAC address when `None`
+    pub clock_identity: Option<ClockIdentity>,
+    pub clock_type: ClockType,
+    pub two_step_flag: bool,
+    pub domain_number: u8,
+    pub priority1: u8,
+    pub priority2: u8,
+    pub clock_class: u8,
+    pub clock_accuracy: u8,
+    pub offset_scaled_log_variance: u16,
+    pub time_stamping: TimeStamping,
+    pub profile: Profile,
+    pub servo: ServoConfig,
+    /// Unix socket accepting management messages
+    pub uds_address: String,
+    /// pcapng file recording every PTP datagram, empty disables capturing
+    pub capture_file: String,
+    /// Size in bytes after which the capture file is rotated, 0 disables rotation
+    pub capture_file_size: u64,
+    /// Number of rotated capture files kept
+    pub capture_file_count: usize,
+    /// log2 of the interval in seconds between statistics summaries
+    pub summary_interval: i8,
+    /// TCP address serving Prometheus metrics, e.g. `127.0.0.1:9100`, empty disables it
+    pub metrics_address: String,
+    /// Text returned for the USER_DESCRIPTION management ID
+    pub user_description: String,
+    /// Duration in seconds requested for unicast transmission grants
+    pub unicast_req_duration: u32,
+    /// Whether unicast transmission requests of other ports are granted
+    pub unicast_listen: bool,
+    pub unicast_limits: Limits,
+}
+
+impl Default for ClockConfig {
+    fn default() -> Self {
+        ClockConfig {
+            clock_identity: None,
+            clock_type: ClockType::OrdinaryClock,
+            two_step_flag: true,
+            domain_number: 0,
+            priority1: 128,
+            priority2: 128,
+            clock_class: 248,
+            clock_accuracy: 0xFE,
+            offset_scaled_log_variance: 0xFFFF,
+            time_stamping: TimeStamping::Hardware,
+            profile: Profile::Default,
+            servo: ServoConfig::default(),
+            uds_address: DEFAULT_UDS_PATH.to_string(),
+            capture_file: String::new(),
+            capture_file_size: 10_000_000,
+            capture_file_count: 4,
+            summary_interval: 0,
+            metrics_address: String::new(),
+            user_description: String::new(),
+            unicast_req_duration: DEFAULT_DURATION,
+            unicast_listen: false,
+            unicast_limits: Limits::default(),
+        }
+    }
+}
+
+/// Options that can be set globally and overridden per interface
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PortConfig {
+    pub log_announce_interval: i8,
+    pub log_sync_interval: i8,
+    pub log_min_delay_req_interval: i8,
+    pub log_min_pdelay_req_interval: i8,
+    pub announce_receipt_timeout: u8,
+    pub delay_mechanism: DelayMechanism,
+    pub network_transport: Transport,
+    /// Master asked for unicast Announce, Sync and Delay_Resp messages
+    pub unicast_master: Option<Ipv4Addr>,
+}
+
+impl Default for PortConfig {
+    fn default() -> Self {
+        PortConfig {
+            log_announce_interval: 1,
+            log_sync_interval: 0,
+            log_min_delay_req_interval: 0,
+            log_min_pdelay_req_interval: 0,
+            announce_receipt_timeout: 3,
+            delay_mechanism: DelayMechanism::E2E,
+            network_transport: Transport::UDPv4,
+            unicast_master: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Interface {
+    pub name: String,
+    pub port: PortConfig,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Config {
+    pub clock: ClockConfig,
+    pub port: PortConfig,
+    pub interfaces: Vec<Interface>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ErrorKind {
+    Syntax(String),
+    UnknownKey(String),
+    /// Clock-wide key used in an interface section
+    NotPortKey(String),
+    InvalidValue {
+        key: String,
+        value: String,
+    },
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrorKind::Syntax(line) => write!(f, "malformed line '{}'", line),
+            ErrorKind::UnknownKey(key) => write!(f, "unknown key '{}'", key),
+            ErrorKind::NotPortKey(key) => {
+                write!(f, "key '{}' is only allowed in the [global] section", key)
+            }
+            ErrorKind::InvalidValue { key, value } => {
+                write!(f, "invalid value '{}' for key '{}'", value, key)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Error {
+    pub line: usize,
+    pub kind: ErrorKind,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.kind)
+    }
+}
+
+impl std::error::Error for Error {}
+
+fn parse_value<T: FromStr>(key: &str, value: &str) -> Result<T, ErrorKind> {
+    value.parse().map_err(|_| ErrorKind::InvalidValue {
+        key: key.to_string(),
+        value: value.to_string(),
+    })
+}
+
+/// Like `parse_value` but also accepts `0x` prefixed hexadecimal integers
+fn parse_int<T: TryFrom<u64> + FromStr>(key: &str, value: &str) -> Result<T, ErrorKind> {
+    match value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        Some(hex) => u64::from_str_radix(hex, 16)
+            .ok()
+            .and_then(|val| T::try_from(val).ok())
+            .ok_or_else(|| ErrorKind::InvalidValue {
+                key: key.to_string(),
+                value: value.to_string(),
+            }),
+        None => parse_value(key, value),
+    }
+}
+
+fn parse_nonnegative(key: &str, value: &str) -> Result<f64, ErrorKind> {
+    match parse_value::<f64>(key, value)? {
+        val if val >= 0.0 && val.is_finite() => Ok(val),
+        _ => Err(ErrorKind::InvalidValue {
+            key: key.to_string(),
+            value: value.to_string(),
+        }),
+    }
+}
+
+fn parse_bool(key: &str, value: &str) -> Result<bool, ErrorKind> {
+    match value {
+        "0" => Ok(false),
+        "1" => Ok(true),
+        _ => Err(ErrorKind::InvalidValue {
+            key: key.to_string(),
+            value: value.to_string(),
+        }),
+    }
+}
+
+/// `000000.0000.000000` stands for the identity derived from the MAC address
+fn parse_clock_identity_value(key: &str, value: &str) -> Result<Option<ClockIdentity>, ErrorKind> {
+    match parse_clock_identity(value) {
+        Some(identity) if identity.iter().all(|octet| octet.into_inner() == 0) => Ok(None),
+        Some(identity) => Ok(Some(identity)),
+        None => Err(ErrorKind::InvalidValue {
+            key: key.to_string(),
+            value: value.to_string(),
+        }),
+    }
+}
+
+fn parse_positive(key: &str, value: &str) -> Result<u8, ErrorKind> {
+    match parse_int(key, value)? {
+        0 => Err(ErrorKind::InvalidValue {
+            key: key.to_string(),
+            value: value.to_string(),
+        }),
+        val => Ok(val),
+    }
+}
+
+fn parse_log_interval(key: &str, value: &str) -> Result<i8, ErrorKind> {
+    match parse_int(key, value)? {
+        log if LOG_INTERVAL_RANGE.contains(&log) => Ok(log),
+        _ => Err(ErrorKind::InvalidValue {
+            key: key.to_string(),
+            value: value.to_string(),
+        }),
+    }
+}
+
+impl PortConfig {
+    /// Returns `Ok(false)` when `key` isn't a port option
+    pub fn set(&mut self, key: &str, value: &str) -> Result<bool, ErrorKind> {
+        match key {
+            "logAnnounceInterval" => self.log_announce_interval = parse_log_interval(key, value)?,
+            "logSyncInterval" => self.log_sync_interval = parse_log_interval(key, value)?,
+            "logMinDelayReqInterval" => {
+                self.log_min_delay_req_interval = parse_log_interval(key, value)?
+            }
+            "logMinPdelayReqInterval" => {
+                self.log_min_pdelay_req_interval = parse_log_interval(key, value)?
+            }
+            "announceReceiptTimeout" => self.announce_receipt_timeout = parse_positive(key, value)?,
+            "delay_mechanism" => self.delay_mechanism = parse_value(key, value)?,
+            "network_transport" => self.network_transport = parse_value(key, value)?,
+            "unicast_master" => self.unicast_master = Some(parse_value(key, value)?),
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+}
+
+impl ClockConfig {
+    /// Returns `Ok(false)` when `key` isn't a clock option
+    pub fn set(&mut self, key: &str, value: &str) -> Result<bool, ErrorKind> {
+        match key {
+            "clockIdentity" => self.clock_identity = parse_clock_identity_value(key, value)?,
+            "clock_type" => self.clock_type = parse_value(key, value)?,
+            "twoStepFlag" => self.two_step_flag = parse_bool(key, value)?,
+            "domainNumber" => self.domain_number = parse_int(key, value)?,
+            "priority1" => self.priority1 = parse_int(key, value)?,
+            "priority2" => self.priority2 = parse_int(key, value)?,
+            "clockClass" => self.clock_class = parse_int(key, value)?,
+            "clockAccuracy" => self.clock_accuracy = parse_int(key, value)?,
+            "offsetScaledLogVariance" => self.offset_scaled_log_variance = parse_int(key, value)?,
+            "time_stamping" => self.time_stamping = parse_value(key, value)?,
+            "profile" => self.profile = parse_value(key, value)?,
+            "pi_proportional_const" => {
+                self.servo.pi_proportional_const = parse_nonnegative(key, value)?
+            }
+            "pi_integral_const" => self.servo.pi_integral_const = parse_nonnegative(key, value)?,
+            "step_threshold" => self.servo.step_threshold = parse_nonnegative(key, value)?,
+            "first_step_threshold" => {
+                self.servo.first_step_threshold = parse_nonnegative(key, value)?
+            }
+            "max_frequency" => self.servo.max_frequency = parse_nonnegative(key, value)?,
+            "uds_address" => self.uds_address = value.to_string(),
+            "capture_file" => self.capture_file = value.to_string(),
+            "capture_file_size" => self.capture_file_size = parse_int(key, value)?,
+            "capture_file_count" => self.capture_file_count = parse_int(key, value)?,
+            "summary_interval" => self.summary_interval = parse_log_interval(key, value)?,
+            "metrics_address" => self.metrics_address = value.to_string(),
+            "userDescription" => self.user_description = value.to_string(),
+            "unicast_req_duration" => self.unicast_req_duration = parse_int(key, value)?,
+            "unicast_listen" => self.unicast_listen = parse_bool(key, value)?,
+            "unicast_max_grants" => self.unicast_limits.max_grants = parse_int(key, value)?,
+            "unicast_min_log_period" => self.unicast_limits.min_log_period = parse_int(key, value)?,
+            "unicast_max_duration" => self.unicast_limits.max_duration = parse_int(key, value)?,
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+}
+
+impl Config {
+    /// Parses a linuxptp style configuration, e.g.
+    ///
+    /// ```text
+    /// [global]
+    /// domainNumber 24
+    /// logSyncInterval -3
+    ///
+    /// [eth0]
+    /// delay_mechanism P2P
+    /// ```
+    pub fn parse(input: &str) -> Result<Config, Error> {
+        let mut config = Config::default();
+        let mut section: Option<usize> = None;
+        // Interface options are applied after the whole [global] section is known
+        let mut interface_options = Vec::new();
+
+        for (idx, line) in input.lines().enumerate() {
+            let line_no = idx + 1;
+            let line = match line.find(['#', ';']) {
+                Some(pos) => &line[..pos],
+                None => line,
+            }
+            .trim();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[') {
+                let name = name.strip_suffix(']').map(str::trim).ok_or(Error {
+                    line: line_no,
+                    kind: ErrorKind::Syntax(line.to_string()),
+                })?;
+                section = if name == "global" {
+                    None
+                } else {
+                    Some(config.interface_index(name))
+                };
+                continue;
+            }
+            let mut split = line.splitn(2, char::is_whitespace);
+            let (key, value) = match (split.next(), split.next().map(str::trim)) {
+                (Some(key), Some(value)) if !value.is_empty() => (key, value),
+                _ => {
+                    return Err(Error {
+                        line: line_no,
+                        kind: ErrorKind::Syntax(line.to_string()),
+                    })
+                }
+            };
+            let error = |kind| Error {
+                line: line_no,
+                kind,
+            };
+            match section {
+                None => config.set(key, value).map_err(error)?,
+                Some(iface) => {
+                    let mut port = PortConfig::default();
+                    if !port.set(key, value).map_err(error)? {
+                        let mut clock = ClockConfig::default();
+                        return Err(error(match clock.set(key, value) {
+                            Ok(true) => ErrorKind::NotPortKey(key.to_string()),
+                            Ok(false) => ErrorKind::UnknownKey(key.to_string()),
+                            Err(kind) => kind,
+                        }));
+                    }
+                    interface_options.push((iface, key, value));
+                }
+            }
+        }
+
+        for iface in config.interfaces.iter_mut() {
+            iface.port = config.port;
+        }
+        for (iface, key, value) in interface_options {
+            config.interfaces[iface].port.set(key, value).unwrap();
+        }
+        Ok(config)
+    }
+
+    pub fn load(path: &str) -> Result<Config, Box<dyn std::error::Error>> {
+        Ok(Config::parse(&std::fs::read_to_string(path)?)?)
+    }
+
+    /// Sets a `[global]` option, overriding it in every interface as well.
+    /// Used for command line flags, which take precedence over the file.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), ErrorKind> {
+        if self.port.set(key, value)? {
+            for iface in self.interfaces.iter_mut() {
+                iface.port.set(key, value)?;
+            }
+            Ok(())
+        } else if self.clock.set(key, value)? {
+            Ok(())
+        } else {
+            Err(ErrorKind::UnknownKey(key.to_string()))
+        }
+    }
+
+    /// Adds an interface with the global port options unless it's already configured
+    pub fn add_interface(&mut self, name: &str) {
+        self.interface_index(name);
+    }
+
+    fn interface_index(&mut self, name: &str) -> usize {
+        match self.interfaces.iter().position(|iface| iface.name == name) {
+            Some(idx) => idx,
+            None => {
+                self.interfaces.push(Interface {
+                    name: name.to_string(),
+                    port: self.port,
+                });
+                self.interfaces.len() - 1
+            }
+        }
+    }
+}
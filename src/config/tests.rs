@@ -0,0 +1,162 @@
+use super::*;
+
+#[test]
+pub fn test_parse() {
+    let config = Config::parse(
+        "
+# Telecom grandmaster
+[global]
+clockIdentity      b827eb.fffe.92b1a6
+clock_type         E2E_TC
+twoStepFlag        0
+domainNumber       24
+priority1          100
+clockAccuracy      0x21 ; 100 ns
+logSyncInterval    -4
+time_stamping      software
+pi_integral_const  0.5
+profile            g8275.1
+uds_address        /tmp/ptp
+capture_file       /tmp/ptp.pcapng
+capture_file_size  0x100000
+summary_interval   -2
+metrics_address    127.0.0.1:9100
+userDescription    grandmaster, rack 4
+unicast_listen     1
+unicast_max_grants 8
+unicast_min_log_period -4
+
+[eth0]
+delay_mechanism    P2P
+unicast_master     192.168.1.1
+
+[eth1]
+logSyncInterval    -3
+",
+    )
+    .unwrap();
+    assert_eq!(
+        config.clock.clock_identity,
+        Some(parse_clock_identity("b827eb.fffe.92b1a6").unwrap())
+    );
+    assert_eq!(config.clock.clock_type, ClockType::E2eTransparentClock);
+    assert!(!config.clock.two_step_flag);
+    assert_eq!(config.clock.domain_number, 24);
+    assert_eq!(config.clock.priority1, 100);
+    assert_eq!(config.clock.priority2, 128);
+    assert_eq!(config.clock.clock_accuracy, 0x21);
+    assert_eq!(config.clock.time_stamping, TimeStamping::Software);
+    assert_eq!(config.clock.profile, Profile::G8275_1);
+    assert_eq!(config.clock.uds_address, "/tmp/ptp");
+    assert_eq!(config.clock.capture_file, "/tmp/ptp.pcapng");
+    assert_eq!(config.clock.capture_file_size, 0x100000);
+    assert_eq!(config.clock.capture_file_count, 4);
+    assert_eq!(config.clock.summary_interval, -2);
+    assert_eq!(config.clock.metrics_address, "127.0.0.1:9100");
+    assert_eq!(config.clock.user_description, "grandmaster, rack 4");
+    assert_eq!(config.clock.servo.pi_integral_const, 0.5);
+    assert!(config.clock.unicast_listen);
+    assert_eq!(config.clock.unicast_req_duration, 300);
+    assert_eq!(config.clock.unicast_limits.max_grants, 8);
+    assert_eq!(config.clock.unicast_limits.min_log_period, -4);
+    assert_eq!(config.clock.unicast_limits.max_duration, 1000);
+    assert_eq!(config.interfaces.len(), 2);
+    assert_eq!(config.interfaces[0].name, "eth0");
+    assert_eq!(config.interfaces[0].port.log_sync_interval, -4);
+    assert_eq!(
+        config.interfaces[0].port.delay_mechanism,
+        DelayMechanism::P2P
+    );
+    assert_eq!(
+        config.interfaces[0].port.unicast_master,
+        Some(Ipv4Addr::new(192, 168, 1, 1))
+    );
+    assert_eq!(config.interfaces[1].port.log_sync_interval, -3);
+    assert_eq!(config.interfaces[1].port.unicast_master, None);
+    assert_eq!(
+        config.interfaces[1].port.delay_mechanism,
+        DelayMechanism::E2E
+    );
+
+    let mut config = config;
+    config.set("logSyncInterval", "-5").unwrap();
+    config.set("priority1", "1").unwrap();
+    assert_eq!(config.interfaces[1].port.log_sync_interval, -5);
+    assert_eq!(config.clock.priority1, 1);
+}
+
+#[test]
+pub fn test_errors() {
+    let error = |input| Config::parse(input).unwrap_err();
+    assert_eq!(
+        error("[global]\npriority1 256\n"),
+        Error {
+            line: 2,
+            kind: ErrorKind::InvalidValue {
+                key: "priority1".to_string(),
+                value: "256".to_string()
+            }
+        }
+    );
+    assert_eq!(
+        error("\n[eth0]\ndomainNumber 1").to_string(),
+        "line 3: key 'domainNumber' is only allowed in the [global] section"
+    );
+    assert_eq!(
+        error("[global]\nfoo 1").kind,
+        ErrorKind::UnknownKey("foo".to_string())
+    );
+    assert_eq!(
+        error("clockIdentity b827eb.fffe.92b1a").kind,
+        ErrorKind::InvalidValue {
+            key: "clockIdentity".to_string(),
+            value: "b827eb.fffe.92b1a".to_string()
+        }
+    );
+    assert_eq!(
+        error("[eth0]\nfooBar 1").kind,
+        ErrorKind::UnknownKey("fooBar".to_string())
+    );
+    assert_eq!(
+        error("[eth0]\ndomainNumber 300").kind,
+        ErrorKind::InvalidValue {
+            key: "domainNumber".to_string(),
+            value: "300".to_string()
+        }
+    );
+    // Message intervals from 2^-7 to 2^16 s
+    assert_eq!(
+        error("[global]\nlogAnnounceInterval 100"),
+        Error {
+            line: 2,
+            kind: ErrorKind::InvalidValue {
+                key: "logAnnounceInterval".to_string(),
+                value: "100".to_string()
+            }
+        }
+    );
+    assert_eq!(
+        error("[eth0]\nlogMinPdelayReqInterval -8").kind,
+        ErrorKind::InvalidValue {
+            key: "logMinPdelayReqInterval".to_string(),
+            value: "-8".to_string()
+        }
+    );
+    assert!(Config::parse("logSyncInterval -7\nlogMinDelayReqInterval 16").is_ok());
+    assert_eq!(
+        error("summary_interval 64").kind,
+        ErrorKind::InvalidValue {
+            key: "summary_interval".to_string(),
+            value: "64".to_string()
+        }
+    );
+    assert_eq!(error("[global\n").line, 1);
+    assert_eq!(error("logSyncInterval\n").line, 1);
+    assert_eq!(
+        Config::default().set("network_transport", "UDPv5"),
+        Err(ErrorKind::InvalidValue {
+            key: "network_transport".to_string(),
+            value: "UDPv5".to_string()
+        })
+    );
+}
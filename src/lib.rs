@@ -0,0 +1,17 @@
+#[macro_use]
+extern crate bit_serialize_derive;
+#[macro_use]
+extern crate nom;
+#[macro_use]
+extern crate nix;
+#[macro_use]
+extern crate serde_derive;
+
+pub mod capture;
+pub mod clock;
+pub mod config;
+pub mod management;
+pub mod protocol;
+pub mod stats;
+pub mod transport;
+pub mod unicast;
@@ -1,51 +1,118 @@
 #[macro_use]
-extern crate bit_serialize_derive;
-#[macro_use]
-extern crate nom;
-#[macro_use]
 extern crate serde_derive;
-#[macro_use]
-extern crate nix;
-
-use mio::net::UdpSocket;
-use mio::{Events, Interest, Poll, Token};
-
-use nix::errno::Errno::EAGAIN;
-use nix::sys::socket::sockopt::ReceiveTimestamp;
-use nix::sys::socket::{recvmsg, setsockopt, MsgFlags};
-use nix::sys::time::TimeVal;
-use nix::sys::uio::IoVec;
-use nix::Error::Sys;
 
-use std::os::unix::io::AsRawFd;
+use mio::{Events, Poll, Token};
 
-use ifaces::interface::{Interface, Kind};
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
 
-mod protocol;
-use protocol::parser::parse_ptp_message;
+use ptp::capture::writer::{ipv4_udp, Writer};
+use ptp::capture::{self, Direction, Packet, LINKTYPE_RAW};
+use ptp::clock::replay::{recorded_identity, Replay};
+use ptp::clock::transparent::{Mode, TransparentClock};
+use ptp::clock::Clock;
+use ptp::config::{self, ClockType, Config, Transport};
+use ptp::management::server::UdsServer;
+use ptp::protocol::parser::parse_ptp_message;
+use ptp::protocol::types::derived::{clock_identity_from_mac, format_clock_identity, Timestamp};
+use ptp::protocol::types::enums::values::{self, PortState};
+use ptp::protocol::types::enums::Enumeration;
+use ptp::protocol::types::message::{Body, Message, Received};
+use ptp::protocol::types::primitive::int::Int;
+use ptp::stats::metrics::{self, MetricsServer};
+use ptp::stats::Counters;
+use ptp::transport::{self, mac_address, UdpPort, EVENT_PORT, GENERAL_PORT, PTP_PRIMARY_MULTICAST};
+use ptp::unicast::Negotiation;
 
 use docopt::Docopt;
 
-const EVENT_MSG: Token = Token(0);
-const GENERAL_MSG: Token = Token(0);
+/// Longest time between two runs of the clock's timers
+const TICK: Duration = Duration::from_millis(100);
+/// Token of the management socket, ports use tokens from 0
+const MANAGEMENT: Token = Token(usize::MAX);
+const METRICS: Token = Token(usize::MAX - 1);
 
-const USAGE: &'static str = "
+const USAGE: &str = "
 Rust PTP stack
 
 Usage:
-  ptp -i <iface> | --interface=<iface>
+  ptp [-f <file>] [-i <iface>]... [options]
   ptp (-h | --help)
 
 Options:
   -h --help                       Show this screen.
-  -i <iface> --interface=<iface>  Choose network interface
+  -i <iface> --interface=<iface>  Add a port on network interface
+  -f <file> --config=<file>       Read configuration from file
+  -E                              Use E2E delay mechanism
+  -P                              Use P2P delay mechanism
+  -A                              Choose delay mechanism automatically
+  -2                              Use IEEE 802.3 transport
+  -4                              Use UDP IPv4 transport
+  -6                              Use UDP IPv6 transport
+  -H                              Use hardware time stamping
+  -S                              Use software time stamping
+  --domain=<n>                    Set domainNumber
+  --priority1=<n>                 Set priority1
+  --priority2=<n>                 Set priority2
+  --capture=<file>                Record all PTP datagrams to a pcapng file
+  --replay=<file>                 Run the clock on a recorded capture instead of
+                                  live sockets and print its decisions
+  --json                          Print received messages as JSON, one per line
 ";
 
 #[derive(Debug, Deserialize)]
+#[allow(non_snake_case)]
 pub struct Args {
-    flag_interface: String,
+    flag_interface: Vec<String>,
+    flag_config: Option<String>,
+    flag_E: bool,
+    flag_P: bool,
+    flag_A: bool,
+    flag_2: bool,
+    flag_4: bool,
+    flag_6: bool,
+    flag_H: bool,
+    flag_S: bool,
+    flag_domain: Option<String>,
+    flag_priority1: Option<String>,
+    flag_priority2: Option<String>,
+    flag_capture: Option<String>,
+    flag_replay: Option<String>,
+    flag_json: bool,
+}
+
+impl Args {
+    /// Configuration keys set by the command line, applied over the config file
+    fn overrides(&self) -> Vec<(&'static str, &str)> {
+        let switches = [
+            (self.flag_E, "delay_mechanism", "E2E"),
+            (self.flag_P, "delay_mechanism", "P2P"),
+            (self.flag_A, "delay_mechanism", "Auto"),
+            (self.flag_2, "network_transport", "L2"),
+            (self.flag_4, "network_transport", "UDPv4"),
+            (self.flag_6, "network_transport", "UDPv6"),
+            (self.flag_H, "time_stamping", "hardware"),
+            (self.flag_S, "time_stamping", "software"),
+        ];
+        let options = [
+            ("domainNumber", &self.flag_domain),
+            ("priority1", &self.flag_priority1),
+            ("priority2", &self.flag_priority2),
+            ("capture_file", &self.flag_capture),
+        ];
+        switches
+            .iter()
+            .filter(|(set, _, _)| *set)
+            .map(|&(_, key, value)| (key, value))
+            .chain(
+                options
+                    .iter()
+                    .filter_map(|(key, value)| value.as_deref().map(|value| (*key, value))),
+            )
+            .collect()
+    }
 }
 
 fn main() {
@@ -53,55 +120,156 @@ fn main() {
         .and_then(|d| d.deserialize())
         .unwrap_or_else(|e| e.exit());
 
-    let iface_addr = if let std::net::IpAddr::V4(iface_addr) = Interface::get_all()
-        .expect("Couldn't get interfaces")
-        .iter()
-        .filter(|iface| iface.name == args.flag_interface && iface.kind == Kind::Ipv4)
-        .next()
-        .expect(&format!("Couldn't find iface: {}", args.flag_interface))
-        .addr
-        .expect(&format!(
-            "Could not get address of iface: {}",
-            args.flag_interface
-        ))
-        .ip()
-    {
-        iface_addr
-    } else {
-        panic!("Address invalid!")
+    let mut config = match &args.flag_config {
+        Some(path) => Config::load(path).unwrap_or_else(|err| {
+            eprintln!("{}: {}", path, err);
+            std::process::exit(1)
+        }),
+        None => Config::default(),
     };
+    for (key, value) in args.overrides() {
+        if let Err(err) = config.set(key, value) {
+            eprintln!("Command line: {}", err);
+            std::process::exit(1)
+        }
+    }
+    for iface in &args.flag_interface {
+        config.add_interface(iface);
+    }
+    if let Some(path) = &args.flag_replay {
+        replay(&config, path);
+        return;
+    }
+    if config.interfaces.is_empty() {
+        eprintln!("No interface given on the command line or in the config file");
+        std::process::exit(1)
+    }
 
     let sigint = Arc::new(AtomicBool::new(false));
     signal_hook::flag::register(signal_hook::SIGINT, Arc::clone(&sigint)).unwrap();
 
-    let multicast_addr = "224.0.1.129".parse().unwrap();
-    let bind_addr_event = "0.0.0.0:319".parse().unwrap();
-    let bind_addr_general = "0.0.0.0:320".parse().unwrap();
-    let mut socket_general = UdpSocket::bind(bind_addr_general).unwrap();
-    let mut socket_event = UdpSocket::bind(bind_addr_event).unwrap();
-    assert!(setsockopt(socket_event.as_raw_fd(), ReceiveTimestamp, &true).is_ok());
-    socket_general
-        .join_multicast_v4(&multicast_addr, &iface_addr)
-        .unwrap();
-    socket_event
-        .join_multicast_v4(&multicast_addr, &iface_addr)
-        .unwrap();
     let mut poll = Poll::new().unwrap();
     let mut events = Events::with_capacity(128);
 
-    poll.registry()
-        .register(&mut socket_event, EVENT_MSG, Interest::READABLE)
-        .unwrap();
-    poll.registry()
-        .register(&mut socket_general, GENERAL_MSG, Interest::READABLE)
-        .unwrap();
+    let mut ports = Vec::new();
+    for (idx, iface) in config.interfaces.iter().enumerate() {
+        if iface.port.network_transport != Transport::UDPv4 {
+            panic!("Only UDPv4 transport is supported");
+        }
+        let mut port = UdpPort::open(&iface.name, iface_addr(&iface.name))
+            .unwrap_or_else(|err| panic!("Couldn't open port on {}: {}", iface.name, err));
+        port.register(poll.registry(), Token(2 * idx), Token(2 * idx + 1))
+            .unwrap();
+        println!("Port {} listening on: {}", idx + 1, iface.name);
+        ports.push(port);
+    }
 
-    println!(
-        "Listening on: General: {}, Event: {}, Multicast: {}",
-        bind_addr_general, bind_addr_event, multicast_addr
+    // Clock identity shared by all ports, built from the first port's MAC address
+    let identity = config.clock.clock_identity.unwrap_or_else(|| {
+        let iface = &config.interfaces[0].name;
+        clock_identity_from_mac(
+            mac_address(iface)
+                .unwrap_or_else(|err| panic!("Couldn't get MAC address of {}: {}", iface, err)),
+        )
+    });
+    println!("Clock identity: {}", format_clock_identity(&identity));
+    let port_configs: Vec<_> = config.interfaces.iter().map(|iface| iface.port).collect();
+    let mut clock = Clock::new(
+        identity,
+        config.clock.clone(),
+        &port_configs,
+        Instant::now(),
     );
+    let mut tc = match config.clock.clock_type {
+        ClockType::E2eTransparentClock => Some(TransparentClock::new(
+            ports.len(),
+            if config.clock.two_step_flag {
+                Mode::TwoStep
+            } else {
+                Mode::OneStep
+            },
+        )),
+        ClockType::OrdinaryClock if ports.len() > 1 => {
+            panic!("Ordinary clock can have only one port, use clock_type BC")
+        }
+        _ => None,
+    };
+    let mut recorder = match config.clock.capture_file.as_str() {
+        "" => None,
+        path => {
+            let names: Vec<_> = config
+                .interfaces
+                .iter()
+                .map(|iface| iface.name.clone())
+                .collect();
+            match Writer::create(
+                path,
+                &names,
+                config.clock.capture_file_size,
+                config.clock.capture_file_count,
+            ) {
+                Ok(writer) => {
+                    println!("Capturing to: {}", path);
+                    Some(Recorder {
+                        writer,
+                        addrs: names.iter().map(|name| iface_addr(name)).collect(),
+                    })
+                }
+                Err(err) => {
+                    eprintln!("Couldn't create capture file {}: {}", path, err);
+                    None
+                }
+            }
+        }
+    };
+    let management = match UdsServer::bind(&config.clock.uds_address) {
+        Ok(mut server) => {
+            server.register(poll.registry(), MANAGEMENT).unwrap();
+            println!("Management socket: {}", config.clock.uds_address);
+            Some(server)
+        }
+        Err(err) => {
+            eprintln!(
+                "Couldn't open management socket {}: {}",
+                config.clock.uds_address, err
+            );
+            None
+        }
+    };
+    let mut metrics = match config.clock.metrics_address.as_str() {
+        "" => None,
+        address => match address
+            .parse()
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidInput, err))
+            .and_then(MetricsServer::bind)
+        {
+            Ok(mut server) => {
+                server.register(poll.registry(), METRICS).unwrap();
+                println!("Metrics: http://{}/metrics", address);
+                Some(server)
+            }
+            Err(err) => {
+                eprintln!("Couldn't open metrics listener {}: {}", address, err);
+                None
+            }
+        },
+    };
+    let mut negotiations: Vec<_> = config
+        .interfaces
+        .iter()
+        .map(|iface| Negotiation::new(iface.port.unicast_master, &config.clock, &iface.port))
+        .collect();
+    let mut states = vec![None; ports.len()];
+    let mut counters = Counters::default();
+    // Counters at the last summary, the metrics report the running totals
+    let mut last_counters = counters;
+    // Last servo summary, also reported by the metrics
+    let mut servo_summary = None;
+    let summary_interval = config::log_interval(config.clock.summary_interval);
+    let mut next_summary = Instant::now() + summary_interval;
+
     while !sigint.load(Ordering::Relaxed) {
-        if let Err(err) = poll.poll(&mut events, None) {
+        if let Err(err) = poll.poll(&mut events, Some(TICK)) {
             if err.kind() == std::io::ErrorKind::Interrupted {
                 eprintln!("Poll interrupted");
             } else {
@@ -110,39 +278,343 @@ fn main() {
         }
 
         for event in &events {
-            if event.is_readable() {
-                let mut buf = [0u8; 64];
-                if event.token() == EVENT_MSG {
-                    let mut cmsg_buf = cmsg_space!(TimeVal);
-                    let iov_buf = IoVec::from_mut_slice(&mut buf[..]);
-                    let msg = match recvmsg(
-                        socket_event.as_raw_fd(),
-                        &[iov_buf],
-                        Some(&mut cmsg_buf),
-                        MsgFlags::MSG_WAITALL,
-                    ) {
-                        Ok(data) => data,
-                        Err(Sys(EAGAIN)) => continue,
-                        Err(err) => panic!("Couldn't read data from socket: {:?}", err),
-                    };
-                    println!(
-                        "Received ptp message from: {}",
-                        msg.address.unwrap().to_str()
-                    );
-                    for cmsg in msg.cmsgs() {
-                        println!("Ancillary data: {:?}", cmsg);
+            if event.token() == MANAGEMENT {
+                if let Some(server) = &management {
+                    if let Err(err) = server.serve(&mut clock, Instant::now()) {
+                        eprintln!("Couldn't read management request: {}", err);
                     }
-                } else if event.token() == GENERAL_MSG {
-                    let (_number_of_bytes, src_addr) = socket_general
-                        .recv_from(&mut buf)
-                        .expect("Didn't receive data");
-                    println!("Received ptp message from: {}", src_addr);
+                }
+                continue;
+            }
+            if let Some(server) = metrics.as_mut().filter(|server| server.owns(event.token())) {
+                if let Err(err) =
+                    server.serve(poll.registry(), event.token(), Instant::now(), || {
+                        metrics::render(&clock, &counters, servo_summary.as_ref())
+                    })
+                {
+                    eprintln!("Couldn't accept metrics connection: {}", err);
+                }
+                continue;
+            }
+            let idx = event.token().0 / 2;
+            loop {
+                let mut buf = [0u8; 1500];
+                let received = if event.token().0 % 2 == 0 {
+                    ports[idx]
+                        .recv_event(&mut buf)
+                        .map(|(len, addr, timestamp)| {
+                            // Source and timestamp are part of the JSON output
+                            if !args.flag_json {
+                                if let Some(addr) = addr {
+                                    println!("Received ptp message from: {}", addr);
+                                }
+                                if let Some(timestamp) = timestamp {
+                                    println!("Receive timestamp: {}", timestamp);
+                                }
+                            }
+                            (
+                                len,
+                                addr,
+                                EVENT_PORT,
+                                timestamp.map(|timestamp| Timestamp::from(timestamp).into()),
+                            )
+                        })
                 } else {
-                    panic!("Unknown token: {:?}", event.token())
+                    ports[idx].recv_general(&mut buf).map(|(len, addr)| {
+                        if !args.flag_json {
+                            println!("Received ptp message from: {}", addr);
+                        }
+                        (len, Some(addr), GENERAL_PORT, None)
+                    })
+                };
+                let (len, addr, local_port, timestamp) = match received {
+                    Ok(received) => received,
+                    Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => break,
+                    Err(err) => panic!("Couldn't read data from socket: {}", err),
                 };
-                let msg = parse_ptp_message(&buf).unwrap();
-                println!("{:#?}", msg.1);
+                record(
+                    &mut recorder,
+                    idx,
+                    Direction::Inbound,
+                    addr,
+                    local_port,
+                    timestamp,
+                    &buf[..len],
+                );
+                let timestamp = timestamp.unwrap_or_else(now);
+                match parse_ptp_message(&buf[..len]) {
+                    Ok((_, msg)) => {
+                        if tc.is_some() || clock.accepts(&msg) {
+                            counters.received(&msg);
+                        } else {
+                            counters.dropped(&msg);
+                        }
+                        if args.flag_json {
+                            let received = Received {
+                                source: addr.map_or_else(String::new, |addr| addr.to_string()),
+                                timestamp,
+                                message: &msg,
+                            };
+                            println!("{}", received.to_json());
+                        } else {
+                            println!("{:#?}", msg);
+                        }
+                        match &mut tc {
+                            Some(tc) => tc.relay(idx, &msg, timestamp, now(), |forward| {
+                                send(
+                                    &ports,
+                                    forward.port,
+                                    None,
+                                    forward.message.clone(),
+                                    &mut recorder,
+                                    &mut counters,
+                                )
+                            }),
+                            None => {
+                                if let Some(SocketAddr::V4(peer)) =
+                                    addr.filter(|_| clock.accepts(&msg))
+                                {
+                                    if let Some(response) = negotiations[idx].handle(
+                                        clock.ports[idx].identity,
+                                        peer.ip(),
+                                        &msg,
+                                        Instant::now(),
+                                    ) {
+                                        send(
+                                            &ports,
+                                            idx,
+                                            Some(*peer.ip()),
+                                            response,
+                                            &mut recorder,
+                                            &mut counters,
+                                        );
+                                    }
+                                }
+                                clock.handle(idx, &msg, Instant::now());
+                                if let Some(measurement) = clock.measure(idx, &msg, timestamp) {
+                                    println!(
+                                        "Port {} offset {} ns mean path delay {} ns",
+                                        idx + 1,
+                                        measurement.offset,
+                                        measurement.mean_path_delay
+                                    );
+                                }
+                                if let Some(summary) = clock.take_summary() {
+                                    println!("Servo {}", summary);
+                                    servo_summary = Some(summary);
+                                }
+                            }
+                        }
+                    }
+                    Err(err) => {
+                        counters.unparseable();
+                        eprintln!("Couldn't parse ptp message: {:?}", err)
+                    }
+                }
             }
         }
+
+        if let Some(server) = &mut metrics {
+            server.expire(poll.registry(), Instant::now());
+        }
+        if Instant::now() >= next_summary {
+            println!("{}", counters.since(&last_counters));
+            last_counters = counters;
+            next_summary += summary_interval;
+        }
+        if tc.is_some() {
+            continue;
+        }
+        for output in clock.poll(Instant::now()) {
+            if let Some(timestamp) = send(
+                &ports,
+                output.port,
+                None,
+                output.message.clone(),
+                &mut recorder,
+                &mut counters,
+            ) {
+                clock.transmitted(output.port, &output.message, timestamp);
+            }
+            // Announce messages also go to the ports that negotiated them
+            if let (Body::Announce(_), Some(server)) =
+                (&output.message.body, &negotiations[output.port].server)
+            {
+                for grant in server.grants(values::MessageType::Announce) {
+                    send(
+                        &ports,
+                        output.port,
+                        Some(grant.peer),
+                        output.message.clone(),
+                        &mut recorder,
+                        &mut counters,
+                    );
+                }
+            }
+        }
+        for (idx, negotiation) in negotiations.iter_mut().enumerate() {
+            let port = &clock.ports[idx];
+            for (peer, message) in negotiation.poll(
+                port.identity,
+                clock.domain_number(),
+                port.state == PortState::MASTER,
+                Instant::now(),
+            ) {
+                send(
+                    &ports,
+                    idx,
+                    Some(peer),
+                    message,
+                    &mut recorder,
+                    &mut counters,
+                );
+            }
+        }
+
+        for (port, state) in clock.ports.iter().zip(states.iter_mut()) {
+            if *state != Some(port.state) {
+                println!(
+                    "Port {} state: {}",
+                    port.identity.portNumber.into_inner(),
+                    port.state
+                );
+                *state = Some(port.state);
+            }
+        }
+    }
+}
+
+/// Time since the Unix epoch, the reference of kernel timestamps
+fn now() -> Duration {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+}
+
+/// Replays a capture with a port per captured interface, unless interfaces are configured
+fn replay(config: &Config, path: &str) {
+    let packets = capture::read_file(path).unwrap_or_else(|err| {
+        eprintln!("{}: {}", path, err);
+        std::process::exit(1)
+    });
+    let port_configs: Vec<_> = if config.interfaces.is_empty() {
+        let ports = packets
+            .iter()
+            .map(|packet| packet.interface as usize + 1)
+            .max();
+        vec![config.port; ports.unwrap_or(1)]
+    } else {
+        config.interfaces.iter().map(|iface| iface.port).collect()
+    };
+    let identity = config
+        .clock
+        .clock_identity
+        .or_else(|| recorded_identity(&packets))
+        .unwrap_or([Int::new(0); 8]);
+    println!("Clock identity: {}", format_clock_identity(&identity));
+    let start = Instant::now();
+    let clock = Clock::new(identity, config.clock.clone(), &port_configs, start);
+    let mut replay = Replay::new(clock, start);
+    replay.run(&packets);
+    for record in &replay.records {
+        println!("{}", record);
+    }
+    println!("{}", replay.counters);
+}
+
+/// Records the datagrams of all ports to a pcapng file
+struct Recorder {
+    writer: Writer,
+    /// Address of each port's interface
+    addrs: Vec<Ipv4Addr>,
+}
+
+/// Records a datagram received by or sent from port `idx`, `peer` being the
+/// sender of received datagrams. Without a kernel `timestamp` the system time
+/// is recorded and flagged. Capturing stops on the first write error.
+fn record(
+    recorder: &mut Option<Recorder>,
+    idx: usize,
+    direction: Direction,
+    peer: Option<SocketAddr>,
+    local_port: u16,
+    timestamp: Option<Duration>,
+    data: &[u8],
+) {
+    let rec = match recorder {
+        Some(rec) => rec,
+        None => return,
+    };
+    let local = SocketAddrV4::new(rec.addrs[idx], local_port);
+    let peer = match peer {
+        Some(SocketAddr::V4(peer)) => peer,
+        _ if direction == Direction::Outbound => {
+            SocketAddrV4::new(PTP_PRIMARY_MULTICAST, local_port)
+        }
+        _ => SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, local_port),
+    };
+    let data = match direction {
+        Direction::Outbound => ipv4_udp(local, peer, data),
+        // Received datagrams may also have been sent to the multicast group
+        _ => ipv4_udp(peer, local, data),
+    };
+    let packet = Packet {
+        timestamp: timestamp.unwrap_or_else(now),
+        interface: idx as u32,
+        direction,
+        link_type: LINKTYPE_RAW,
+        data,
+        estimated: timestamp.is_none(),
+    };
+    if let Err(err) = rec.writer.write(&packet) {
+        eprintln!("Couldn't write capture, capturing stopped: {}", err);
+        *recorder = None;
     }
 }
+
+/// Sends a message on port `idx`'s event or general socket to `to`, or to the
+/// multicast group, returns the kernel transmit timestamp of an event message
+fn send(
+    ports: &[UdpPort],
+    idx: usize,
+    to: Option<Ipv4Addr>,
+    message: Message,
+    recorder: &mut Option<Recorder>,
+    counters: &mut Counters,
+) -> Option<Duration> {
+    let event = match message.header.messageType {
+        Enumeration::Enum(message_type) => message_type.is_event(),
+        Enumeration::Unknown(_) => false,
+    };
+    let buf = message.clone().to_bytes();
+    let to_addr = to.unwrap_or(PTP_PRIMARY_MULTICAST);
+    let local_port = if event { EVENT_PORT } else { GENERAL_PORT };
+    let sent = if event {
+        ports[idx].send_event_timestamped(&buf, to_addr)
+    } else {
+        ports[idx].send_general_to(&buf, to_addr).map(|_| None)
+    };
+    match sent {
+        Ok(timestamp) => {
+            counters.sent(&message);
+            record(
+                recorder,
+                idx,
+                Direction::Outbound,
+                Some(SocketAddr::new(to_addr.into(), local_port)),
+                local_port,
+                timestamp,
+                &buf,
+            );
+            timestamp
+        }
+        Err(err) => {
+            eprintln!("Couldn't send message: {}", err);
+            None
+        }
+    }
+}
+
+fn iface_addr(name: &str) -> Ipv4Addr {
+    transport::iface_addr(name).unwrap_or_else(|err| panic!("{}", err))
+}
@@ -0,0 +1,393 @@
+use crate::protocol::types::datasets::*;
+use crate::protocol::types::derived::{
+    format_clock_identity, ClockQuality, PortIdentity, TimeInterval,
+};
+use crate::protocol::types::enums::{self, values, Enumeration};
+use crate::protocol::types::message::tlv::{ManagementData, Value};
+use crate::protocol::types::message::{body, tlv, Body, Header, Message, Tlv};
+use crate::protocol::types::primitive::int::Int;
+use crate::protocol::types::primitive::Enumeration8;
+
+use std::str::FromStr;
+
+pub mod server;
+
+#[cfg(test)]
+mod tests;
+
+/// Unix socket on which the daemon accepts management messages by default
+pub const DEFAULT_UDS_PATH: &str = "/var/run/ptp";
+
+/// Target port identity addressing all ports of all clocks
+pub fn all_ports() -> PortIdentity {
+    PortIdentity {
+        clockIdentity: [Int::new(0xFF); 8],
+        portNumber: Int::new(0xFFFF),
+    }
+}
+
+/// Wraps management TLVs in a Management message
+pub fn management(
+    source: PortIdentity,
+    target: PortIdentity,
+    domain: u8,
+    sequence_id: u16,
+    boundary_hops: u8,
+    action: values::ActionField,
+    tlvs: Vec<Tlv>,
+) -> Message {
+    Message {
+        header: Header::new(
+            values::MessageType::Management,
+            0,
+            domain,
+            source,
+            sequence_id,
+            0x7F,
+        ),
+        body: Body::Management(body::Management {
+            targetPortIdentity: target,
+            startingBoundaryHops: Int::new(boundary_hops),
+            boundaryHops: Int::new(boundary_hops),
+            _reserved1: Int::new(0),
+            actionField: Enumeration::Enum(action),
+            _reserved2: Int::new(0),
+            tlvs,
+        }),
+    }
+    .finalize()
+}
+
+/// Management request in pmc notation, e.g. `GET CURRENT_DATA_SET` or `SET PRIORITY1 100`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Request {
+    pub action: values::ActionField,
+    pub id: values::ManagementId,
+    pub data: ManagementData,
+}
+
+impl Request {
+    pub fn tlv(self) -> Tlv {
+        Tlv::Management(tlv::Management::new(self.id, self.data))
+    }
+}
+
+fn parse_arg<T: FromStr>(id: values::ManagementId, value: Option<&str>) -> Result<T, String> {
+    let value = value.ok_or_else(|| format!("{} needs a value", id.name()))?;
+    value
+        .parse()
+        .map_err(|_| format!("invalid value '{}' for {}", value, id.name()))
+}
+
+impl FromStr for Request {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use values::ManagementId::*;
+
+        let mut words = s.split_whitespace();
+        let action = match words.next().map(str::to_uppercase).as_deref() {
+            Some("GET") => values::ActionField::GET,
+            Some("SET") => values::ActionField::SET,
+            Some("CMD") | Some("COMMAND") => values::ActionField::COMMAND,
+            Some(action) => return Err(format!("unknown action '{}'", action)),
+            None => return Err("empty command".to_string()),
+        };
+        let id = words.next().ok_or("missing management ID")?;
+        let id = values::ManagementId::from_str(&id.to_uppercase())
+            .map_err(|_| format!("unknown management ID '{}'", id))?;
+        let value = words.next();
+        if let Some(extra) = words.next() {
+            return Err(format!("unexpected argument '{}'", extra));
+        }
+        if action != values::ActionField::SET {
+            return match value {
+                Some(value) => Err(format!("unexpected argument '{}'", value)),
+                None => Ok(Request {
+                    action,
+                    id,
+                    data: ManagementData::empty(),
+                }),
+            };
+        }
+
+        let data = match id {
+            PRIORITY1 => ManagementData::Priority1(Value::new(Int::new(parse_arg(id, value)?))),
+            PRIORITY2 => ManagementData::Priority2(Value::new(Int::new(parse_arg(id, value)?))),
+            DOMAIN => ManagementData::Domain(Value::new(Int::new(parse_arg(id, value)?))),
+            SLAVE_ONLY => match parse_arg::<u8>(id, value)? {
+                flag @ 0..=1 => ManagementData::Slave_Only(Value::new(Int::new(flag))),
+                flag => return Err(format!("invalid value '{}' for {}", flag, id.name())),
+            },
+            LOG_ANNOUNCE_INTERVAL => {
+                ManagementData::Log_Announce_Interval(Value::new(Int::new(parse_arg(id, value)?)))
+            }
+            ANNOUNCE_RECEIPT_TIMEOUT => ManagementData::Announce_Receipt_Timeout(Value::new(
+                Int::new(parse_arg(id, value)?),
+            )),
+            LOG_SYNC_INTERVAL => {
+                ManagementData::Log_Sync_Interval(Value::new(Int::new(parse_arg(id, value)?)))
+            }
+            DELAY_MECHANISM => {
+                let mechanism: values::DelayMechanism = parse_arg(id, value)?;
+                ManagementData::Delay_Mechanism(Value::new(mechanism.into()))
+            }
+            LOG_MIN_PDELAY_REQ_INTERVAL => ManagementData::Log_Min_Pdelay_Req_Interval(Value::new(
+                Int::new(parse_arg(id, value)?),
+            )),
+            USER_DESCRIPTION => ManagementData::User_Description(tlv::User_Description::new(
+                &parse_arg::<String>(id, value)?,
+            )),
+            _ => return Err(format!("{} can't be set", id.name())),
+        };
+        Ok(Request { action, id, data })
+    }
+}
+
+fn field(name: &str, value: String) -> (String, String) {
+    (name.to_string(), value)
+}
+
+fn flag(flags: u8, mask: u8) -> String {
+    ((flags & mask != 0) as u8).to_string()
+}
+
+fn port_identity(identity: &PortIdentity) -> String {
+    format!(
+        "{}-{}",
+        format_clock_identity(&identity.clockIdentity),
+        identity.portNumber.into_inner()
+    )
+}
+
+/// Nanoseconds with one decimal
+fn time_interval(interval: TimeInterval) -> String {
+    format!("{:.1}", interval.nanoseconds())
+}
+
+fn enumeration8<T: Into<Enumeration8>>(value: T) -> String {
+    format!("0x{:02x}", value.into().into_inner())
+}
+
+fn port_state(state: enums::PortState) -> String {
+    match state {
+        Enumeration::Enum(state) => state.to_string(),
+        Enumeration::Unknown(state) => state.to_string(),
+    }
+}
+
+/// Known mechanisms by name, 0 being linuxptp's automatic selection
+fn delay_mechanism(mechanism: enums::DelayMechanism) -> String {
+    match mechanism {
+        Enumeration::Enum(mechanism) => mechanism.to_string(),
+        Enumeration::Unknown(mechanism) if mechanism.into_inner() == 0 => "Auto".to_string(),
+        Enumeration::Unknown(mechanism) => enumeration8(mechanism),
+    }
+}
+
+/// Clock quality fields, named `clockClass` etc. or `gm.ClockClass` etc. with a prefix
+fn clock_quality(prefix: &str, quality: &ClockQuality) -> Vec<(String, String)> {
+    let name = |name: &str| match prefix {
+        "" => name.to_string(),
+        prefix => format!("{}{}{}", prefix, name[..1].to_uppercase(), &name[1..]),
+    };
+    vec![
+        field(
+            &name("clockClass"),
+            quality.clockClass.into_inner().to_string(),
+        ),
+        field(&name("clockAccuracy"), enumeration8(quality.clockAccuracy)),
+        field(
+            &name("offsetScaledLogVariance"),
+            format!("0x{:04x}", quality.offsetScaledLogVariance.into_inner()),
+        ),
+    ]
+}
+
+/// Field names and values of management data, in the order and notation of pmc
+pub fn describe(data: &ManagementData) -> Vec<(String, String)> {
+    let mut fields = Vec::new();
+    match data {
+        ManagementData::Default_Data_Set(ds) => {
+            let flags = ds.flags.into_inner();
+            fields.push(field("twoStepFlag", flag(flags, DEFAULT_TSC)));
+            fields.push(field("slaveOnly", flag(flags, DEFAULT_SO)));
+            fields.push(field(
+                "numberPorts",
+                ds.numberPorts.into_inner().to_string(),
+            ));
+            fields.push(field("priority1", ds.priority1.into_inner().to_string()));
+            fields.extend(clock_quality("", &ds.clockQuality));
+            fields.push(field("priority2", ds.priority2.into_inner().to_string()));
+            fields.push(field(
+                "clockIdentity",
+                format_clock_identity(&ds.clockIdentity),
+            ));
+            fields.push(field(
+                "domainNumber",
+                ds.domainNumber.into_inner().to_string(),
+            ));
+        }
+        ManagementData::Current_Data_Set(ds) => {
+            fields.push(field(
+                "stepsRemoved",
+                ds.stepsRemoved.into_inner().to_string(),
+            ));
+            fields.push(field(
+                "offsetFromMaster",
+                time_interval(ds.offsetFromMaster),
+            ));
+            fields.push(field("meanPathDelay", time_interval(ds.meanPathDelay)));
+        }
+        ManagementData::Parent_Data_Set(ds) => {
+            fields.push(field(
+                "parentPortIdentity",
+                port_identity(&ds.parentPortIdentity),
+            ));
+            fields.push(field("parentStats", flag(ds.flags.into_inner(), PARENT_PS)));
+            fields.push(field(
+                "observedParentOffsetScaledLogVariance",
+                format!(
+                    "0x{:04x}",
+                    ds.observedParentOffsetScaledLogVariance.into_inner()
+                ),
+            ));
+            fields.push(field(
+                "observedParentClockPhaseChangeRate",
+                format!(
+                    "0x{:08x}",
+                    ds.observedParentClockPhaseChangeRate.into_inner()
+                ),
+            ));
+            fields.push(field(
+                "grandmasterPriority1",
+                ds.grandmasterPriority1.into_inner().to_string(),
+            ));
+            fields.extend(clock_quality("gm.", &ds.grandmasterClockQuality));
+            fields.push(field(
+                "grandmasterPriority2",
+                ds.grandmasterPriority2.into_inner().to_string(),
+            ));
+            fields.push(field(
+                "grandmasterIdentity",
+                format_clock_identity(&ds.grandmasterIdentity),
+            ));
+        }
+        ManagementData::Time_Properties_Data_Set(ds) => {
+            let flags = ds.flags.into_inner();
+            fields.push(field(
+                "currentUtcOffset",
+                ds.currentUtcOffset.into_inner().to_string(),
+            ));
+            fields.push(field("leap61", flag(flags, TIME_LI_61)));
+            fields.push(field("leap59", flag(flags, TIME_LI_59)));
+            fields.push(field("currentUtcOffsetValid", flag(flags, TIME_UTCV)));
+            fields.push(field("ptpTimescale", flag(flags, TIME_PTP)));
+            fields.push(field("timeTraceable", flag(flags, TIME_TTRA)));
+            fields.push(field("frequencyTraceable", flag(flags, TIME_FTRA)));
+            fields.push(field("timeSource", enumeration8(ds.timeSource)));
+        }
+        ManagementData::Port_Data_Set(ds) => {
+            fields.push(field("portIdentity", port_identity(&ds.portIdentity)));
+            fields.push(field("portState", port_state(ds.portState)));
+            fields.push(field(
+                "logMinDelayReqInterval",
+                ds.logMinDelayReqInterval.into_inner().to_string(),
+            ));
+            fields.push(field(
+                "peerMeanPathDelay",
+                time_interval(ds.peerMeanPathDelay),
+            ));
+            fields.push(field(
+                "logAnnounceInterval",
+                ds.logAnnounceInterval.into_inner().to_string(),
+            ));
+            fields.push(field(
+                "announceReceiptTimeout",
+                ds.announceReceiptTimeout.into_inner().to_string(),
+            ));
+            fields.push(field(
+                "logSyncInterval",
+                ds.logSyncInterval.into_inner().to_string(),
+            ));
+            fields.push(field("delayMechanism", delay_mechanism(ds.delayMechanism)));
+            fields.push(field(
+                "logMinPdelayReqInterval",
+                ds.logMinPdelayReqInterval.into_inner().to_string(),
+            ));
+            fields.push(field(
+                "versionNumber",
+                ds.versionNumber.into_inner().to_string(),
+            ));
+        }
+        ManagementData::Priority1(value) => {
+            fields.push(field("priority1", value.value.into_inner().to_string()))
+        }
+        ManagementData::Priority2(value) => {
+            fields.push(field("priority2", value.value.into_inner().to_string()))
+        }
+        ManagementData::Domain(value) => {
+            fields.push(field("domainNumber", value.value.into_inner().to_string()))
+        }
+        ManagementData::Slave_Only(value) => {
+            fields.push(field("slaveOnly", flag(value.value.into_inner(), 0x01)))
+        }
+        ManagementData::Log_Announce_Interval(value) => fields.push(field(
+            "logAnnounceInterval",
+            value.value.into_inner().to_string(),
+        )),
+        ManagementData::Announce_Receipt_Timeout(value) => fields.push(field(
+            "announceReceiptTimeout",
+            value.value.into_inner().to_string(),
+        )),
+        ManagementData::Log_Sync_Interval(value) => fields.push(field(
+            "logSyncInterval",
+            value.value.into_inner().to_string(),
+        )),
+        ManagementData::Version_Number(value) => fields.push(field(
+            "versionNumber",
+            (value.value.into_inner() & 0x0F).to_string(),
+        )),
+        ManagementData::Delay_Mechanism(value) => {
+            fields.push(field("delayMechanism", delay_mechanism(value.value.into())))
+        }
+        ManagementData::Log_Min_Pdelay_Req_Interval(value) => fields.push(field(
+            "logMinPdelayReqInterval",
+            value.value.into_inner().to_string(),
+        )),
+        ManagementData::User_Description(data) => {
+            fields.push(field("userDescription", data.userDescription.to_string()))
+        }
+        ManagementData::Fault_Log(log) => {
+            fields.push(field(
+                "numberOfFaultRecords",
+                log.numberOfFaultRecords.into_inner().to_string(),
+            ));
+            for record in &log.faultRecords {
+                let severity = match record.severityCode {
+                    Enumeration::Enum(severity) => severity.to_string(),
+                    Enumeration::Unknown(severity) => severity.to_string(),
+                };
+                fields.push(field(
+                    "faultRecord",
+                    format!(
+                        "{} {} {} {} {}",
+                        record.faultTime,
+                        severity,
+                        record.faultName,
+                        record.faultValue,
+                        record.faultDescription
+                    ),
+                ));
+            }
+        }
+        ManagementData::Raw(data) if !data.is_empty() => fields.push(field(
+            "data",
+            data.iter()
+                .map(|octet| format!("{:02x}", octet.into_inner()))
+                .collect(),
+        )),
+        ManagementData::Raw(_) => (),
+    }
+    fields
+}
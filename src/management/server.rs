@@ -0,0 +1,346 @@
+use crate::clock::Clock;
+use crate::config::LOG_INTERVAL_RANGE;
+use crate::management::management;
+use crate::protocol::parser::parse_ptp_message;
+use crate::protocol::types::datasets::DEFAULT_SO;
+use crate::protocol::types::derived::PortIdentity;
+use crate::protocol::types::enums::values::{self, PortState};
+use crate::protocol::types::enums::Enumeration;
+use crate::protocol::types::message::tlv::{self, ManagementData, Value};
+use crate::protocol::types::message::{Body, Message, Tlv};
+use crate::protocol::types::primitive::int::Int;
+use crate::protocol::types::primitive::Integer8;
+
+use mio::net::UnixDatagram;
+use mio::{Interest, Registry, Token};
+
+use std::io;
+use std::os::unix::fs::FileTypeExt;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+type Outcome = Result<ManagementData, values::ManagementErrorId>;
+
+/// Whether `id` addresses a port rather than the whole clock
+fn is_port_id(id: values::ManagementId) -> bool {
+    use values::ManagementId::*;
+
+    matches!(
+        id,
+        PORT_DATA_SET
+            | LOG_ANNOUNCE_INTERVAL
+            | ANNOUNCE_RECEIPT_TIMEOUT
+            | LOG_SYNC_INTERVAL
+            | VERSION_NUMBER
+            | ENABLE_PORT
+            | DISABLE_PORT
+            | UNICAST_NEGOTIATION_ENABLE
+            | UNICAST_MASTER_TABLE
+            | UNICAST_MASTER_MAX_TABLE_SIZE
+            | ACCEPTABLE_MASTER_TABLE_ENABLED
+            | ALTERNATE_MASTER
+            | TRANSPARENT_CLOCK_PORT_DATA_SET
+            | DELAY_MECHANISM
+            | LOG_MIN_PDELAY_REQ_INTERVAL
+    )
+}
+
+fn clock_get(clock: &Clock, id: values::ManagementId) -> Outcome {
+    use values::ManagementId::*;
+
+    let ds = &clock.default_ds;
+    Ok(match id {
+        NULL_MANAGEMENT => ManagementData::empty(),
+        DEFAULT_DATA_SET => ManagementData::Default_Data_Set(*ds),
+        CURRENT_DATA_SET => ManagementData::Current_Data_Set(clock.current_ds),
+        PARENT_DATA_SET => ManagementData::Parent_Data_Set(clock.parent_ds),
+        TIME_PROPERTIES_DATA_SET => {
+            ManagementData::Time_Properties_Data_Set(clock.time_properties_ds)
+        }
+        PRIORITY1 => ManagementData::Priority1(Value::new(ds.priority1)),
+        PRIORITY2 => ManagementData::Priority2(Value::new(ds.priority2)),
+        DOMAIN => ManagementData::Domain(Value::new(ds.domainNumber)),
+        SLAVE_ONLY => ManagementData::Slave_Only(Value::new(Int::new(
+            (ds.flags.into_inner() & DEFAULT_SO != 0) as u8,
+        ))),
+        USER_DESCRIPTION => ManagementData::User_Description(tlv::User_Description::new(
+            &clock.config.user_description,
+        )),
+        FAULT_LOG => ManagementData::Fault_Log(tlv::Fault_Log::new(Vec::new())),
+        _ => return Err(values::ManagementErrorId::NOT_SUPPORTED),
+    })
+}
+
+fn clock_set(clock: &mut Clock, id: values::ManagementId, data: &ManagementData) -> Outcome {
+    use values::ManagementId::*;
+
+    // The configuration follows so that both report the same values
+    let (ds, config) = (&mut clock.default_ds, &mut clock.config);
+    match (id, data) {
+        (PRIORITY1, ManagementData::Priority1(value)) => {
+            ds.priority1 = value.value;
+            config.priority1 = value.value.into_inner();
+        }
+        (PRIORITY2, ManagementData::Priority2(value)) => {
+            ds.priority2 = value.value;
+            config.priority2 = value.value.into_inner();
+        }
+        (DOMAIN, ManagementData::Domain(value)) => {
+            ds.domainNumber = value.value;
+            config.domain_number = value.value.into_inner();
+        }
+        (USER_DESCRIPTION, ManagementData::User_Description(data)) => {
+            config.user_description = data.userDescription.to_string()
+        }
+        (PRIORITY1, _) | (PRIORITY2, _) | (DOMAIN, _) | (USER_DESCRIPTION, _) => {
+            return Err(values::ManagementErrorId::WRONG_LENGTH)
+        }
+        _ => return Err(values::ManagementErrorId::NOT_SETABLE),
+    }
+    clock_get(clock, id)
+}
+
+fn port_get(clock: &Clock, port: usize, id: values::ManagementId) -> Outcome {
+    use values::ManagementId::*;
+
+    let port = &clock.ports[port];
+    Ok(match id {
+        PORT_DATA_SET => ManagementData::Port_Data_Set(port.dataset()),
+        LOG_ANNOUNCE_INTERVAL => ManagementData::Log_Announce_Interval(Value::new(Int::new(
+            port.config.log_announce_interval,
+        ))),
+        ANNOUNCE_RECEIPT_TIMEOUT => ManagementData::Announce_Receipt_Timeout(Value::new(Int::new(
+            port.config.announce_receipt_timeout,
+        ))),
+        LOG_SYNC_INTERVAL => {
+            ManagementData::Log_Sync_Interval(Value::new(Int::new(port.config.log_sync_interval)))
+        }
+        VERSION_NUMBER => ManagementData::Version_Number(Value::new(Int::new(2))),
+        DELAY_MECHANISM => {
+            ManagementData::Delay_Mechanism(Value::new(port.delay_mechanism().into()))
+        }
+        LOG_MIN_PDELAY_REQ_INTERVAL => ManagementData::Log_Min_Pdelay_Req_Interval(Value::new(
+            Int::new(port.config.log_min_pdelay_req_interval),
+        )),
+        _ => return Err(values::ManagementErrorId::NOT_SUPPORTED),
+    })
+}
+
+/// Intervals out of `LOG_INTERVAL_RANGE` would overflow the port's timers
+fn log_interval(value: &Value<Integer8>) -> Result<i8, values::ManagementErrorId> {
+    match value.value.into_inner() {
+        log if LOG_INTERVAL_RANGE.contains(&log) => Ok(log),
+        _ => Err(values::ManagementErrorId::WRONG_VALUE),
+    }
+}
+
+fn port_set(
+    clock: &mut Clock,
+    port: usize,
+    id: values::ManagementId,
+    data: &ManagementData,
+    now: Instant,
+) -> Outcome {
+    use values::ManagementId::*;
+
+    let config = &mut clock.ports[port].config;
+    match (id, data) {
+        (LOG_ANNOUNCE_INTERVAL, ManagementData::Log_Announce_Interval(value)) => {
+            config.log_announce_interval = log_interval(value)?
+        }
+        (LOG_SYNC_INTERVAL, ManagementData::Log_Sync_Interval(value)) => {
+            config.log_sync_interval = log_interval(value)?
+        }
+        (LOG_MIN_PDELAY_REQ_INTERVAL, ManagementData::Log_Min_Pdelay_Req_Interval(value)) => {
+            config.log_min_pdelay_req_interval = log_interval(value)?
+        }
+        (LOG_ANNOUNCE_INTERVAL, _) | (LOG_SYNC_INTERVAL, _) | (LOG_MIN_PDELAY_REQ_INTERVAL, _) => {
+            return Err(values::ManagementErrorId::WRONG_LENGTH)
+        }
+        _ => return Err(values::ManagementErrorId::NOT_SETABLE),
+    }
+    clock.ports[port].restart_timers(now);
+    port_get(clock, port, id)
+}
+
+fn port_command(clock: &mut Clock, port: usize, id: values::ManagementId, now: Instant) -> Outcome {
+    let port = &mut clock.ports[port];
+    match id {
+        values::ManagementId::ENABLE_PORT => {
+            if port.state == PortState::DISABLED {
+                port.state = PortState::LISTENING;
+                port.reset_announce_receipt_timeout(now);
+            }
+        }
+        values::ManagementId::DISABLE_PORT => {
+            port.state = PortState::DISABLED;
+            port.foreign_masters.clear();
+        }
+        _ => return Err(values::ManagementErrorId::NOT_SUPPORTED),
+    }
+    Ok(ManagementData::empty())
+}
+
+/// Answers a Management message addressed to `clock`, with one response for
+/// clock-wide management IDs and one per targeted port otherwise
+pub fn respond(clock: &mut Clock, request: &Message, now: Instant) -> Vec<Message> {
+    let management_body = match &request.body {
+        Body::Management(management_body) => management_body,
+        _ => return Vec::new(),
+    };
+    let action = match management_body.actionField {
+        Enumeration::Enum(action @ values::ActionField::GET)
+        | Enumeration::Enum(action @ values::ActionField::SET)
+        | Enumeration::Enum(action @ values::ActionField::COMMAND) => action,
+        _ => return Vec::new(),
+    };
+    let target = management_body.targetPortIdentity;
+    if request.header.domainNumber.into_inner() != clock.domain_number()
+        || (target.clockIdentity != [Int::new(0xFF); 8] && target.clockIdentity != clock.identity())
+    {
+        return Vec::new();
+    }
+    let tlv = match management_body.tlvs.iter().find_map(|tlv| match tlv {
+        Tlv::Management(tlv) => Some(tlv),
+        _ => None,
+    }) {
+        Some(tlv) => tlv,
+        None => return Vec::new(),
+    };
+
+    let mut outcomes = Vec::new();
+    match tlv.managementId {
+        Enumeration::Enum(id) if is_port_id(id) => {
+            let ports: Vec<_> = match target.portNumber.into_inner() {
+                0xFFFF => (0..clock.ports.len()).collect(),
+                number => (0..clock.ports.len())
+                    .filter(|&idx| clock.ports[idx].identity.portNumber.into_inner() == number)
+                    .collect(),
+            };
+            for port in ports {
+                let outcome = match action {
+                    values::ActionField::GET => port_get(clock, port, id),
+                    values::ActionField::SET => port_set(clock, port, id, &tlv.dataField, now),
+                    _ => port_command(clock, port, id, now),
+                };
+                outcomes.push((clock.ports[port].identity, outcome));
+            }
+        }
+        Enumeration::Enum(id) => {
+            let outcome = match action {
+                values::ActionField::GET => clock_get(clock, id),
+                values::ActionField::SET => clock_set(clock, id, &tlv.dataField),
+                _ if id == values::ManagementId::NULL_MANAGEMENT => Ok(ManagementData::empty()),
+                _ => Err(values::ManagementErrorId::NOT_SUPPORTED),
+            };
+            outcomes.push((clock_port_identity(clock), outcome));
+        }
+        Enumeration::Unknown(_) => outcomes.push((
+            clock_port_identity(clock),
+            Err(values::ManagementErrorId::NO_SUCH_ID),
+        )),
+    }
+
+    let hops = management_body
+        .startingBoundaryHops
+        .into_inner()
+        .saturating_sub(management_body.boundaryHops.into_inner());
+    let response_action = match action {
+        values::ActionField::COMMAND => values::ActionField::ACKNOWLEDGE,
+        _ => values::ActionField::RESPONSE,
+    };
+    outcomes
+        .into_iter()
+        .map(|(source, outcome)| {
+            let tlv = match outcome {
+                Ok(data) => Tlv::Management(tlv::Management {
+                    tlvType: Enumeration::Enum(values::TlvType::MANAGEMENT),
+                    lengthField: Int::new(2 + data.length()),
+                    managementId: tlv.managementId,
+                    dataField: data,
+                }),
+                Err(error) => Tlv::Management_Error_Status(tlv::Management_Error_Status::new(
+                    error,
+                    tlv.managementId,
+                )),
+            };
+            management(
+                source,
+                request.header.sourcePortIdentity,
+                clock.domain_number(),
+                request.header.sequenceId.into_inner(),
+                hops,
+                response_action,
+                vec![tlv],
+            )
+        })
+        .collect()
+}
+
+fn clock_port_identity(clock: &Clock) -> PortIdentity {
+    PortIdentity {
+        clockIdentity: clock.identity(),
+        portNumber: Int::new(0),
+    }
+}
+
+/// Unix datagram socket accepting Management messages for the local clock
+#[derive(Debug)]
+pub struct UdsServer {
+    socket: UnixDatagram,
+    path: PathBuf,
+}
+
+impl UdsServer {
+    /// Binds to `path`, replacing a socket left behind by a previous instance
+    /// but no other kind of file
+    pub fn bind<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        match std::fs::symlink_metadata(&path) {
+            Ok(metadata) if metadata.file_type().is_socket() => std::fs::remove_file(&path)?,
+            Ok(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("{} exists and isn't a socket", path.display()),
+                ))
+            }
+            Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+            Err(_) => (),
+        }
+        Ok(UdsServer {
+            socket: UnixDatagram::bind(&path)?,
+            path,
+        })
+    }
+
+    pub fn register(&mut self, registry: &Registry, token: Token) -> io::Result<()> {
+        registry.register(&mut self.socket, token, Interest::READABLE)
+    }
+
+    /// Answers every pending request
+    pub fn serve(&self, clock: &mut Clock, now: Instant) -> io::Result<()> {
+        let mut buf = [0; 1500];
+        loop {
+            let (len, addr) = match self.socket.recv_from(&mut buf) {
+                Ok(received) => received,
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+                Err(err) => return Err(err),
+            };
+            let (request, peer) = match (parse_ptp_message(&buf[..len]), addr.as_pathname()) {
+                (Ok((_, request)), Some(peer)) => (request, peer.to_path_buf()),
+                _ => continue,
+            };
+            for response in respond(clock, &request, now) {
+                if let Err(err) = self.socket.send_to(&response.to_bytes(), &peer) {
+                    eprintln!("Couldn't send management response: {}", err);
+                }
+            }
+        }
+    }
+}
+
+impl Drop for UdsServer {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
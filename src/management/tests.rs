@@ -0,0 +1,82 @@
+use super::*;
+use crate::protocol::parser::parse_ptp_message;
+
+#[test]
+pub fn test_request() {
+    let request: Request = "get current_data_set".parse().unwrap();
+    assert_eq!(request.action, values::ActionField::GET);
+    assert_eq!(request.id, values::ManagementId::CURRENT_DATA_SET);
+    assert_eq!(request.data, ManagementData::empty());
+
+    let request: Request = "SET LOG_SYNC_INTERVAL -3".parse().unwrap();
+    assert_eq!(
+        request.data,
+        ManagementData::Log_Sync_Interval(Value::new(Int::new(-3)))
+    );
+    let request: Request = "SET DELAY_MECHANISM P2P".parse().unwrap();
+    assert_eq!(
+        request.data,
+        ManagementData::Delay_Mechanism(Value::new(Int::new(2)))
+    );
+    assert_eq!(
+        "CMD ENABLE_PORT".parse::<Request>().unwrap().action,
+        values::ActionField::COMMAND
+    );
+
+    assert!("SET PRIORITY1 256".parse::<Request>().is_err());
+    assert!("SET PRIORITY1".parse::<Request>().is_err());
+    assert!("SET DEFAULT_DATA_SET 1".parse::<Request>().is_err());
+    assert!("GET PRIORITY1 1".parse::<Request>().is_err());
+    assert!("GET FOO".parse::<Request>().is_err());
+}
+
+#[test]
+pub fn test_management_message() {
+    let source = PortIdentity {
+        clockIdentity: [Int::new(1); 8],
+        portNumber: Int::new(1),
+    };
+    let time_properties = TimePropertiesDS {
+        currentUtcOffset: Int::new(37),
+        flags: Int::new(TIME_PTP | TIME_UTCV),
+        timeSource: Enumeration::Enum(values::TimeSource::GPS),
+    };
+    let response = management(
+        source,
+        all_ports(),
+        0,
+        5,
+        1,
+        values::ActionField::RESPONSE,
+        vec![
+            Tlv::Management(tlv::Management::new(
+                values::ManagementId::TIME_PROPERTIES_DATA_SET,
+                ManagementData::Time_Properties_Data_Set(time_properties),
+            )),
+            Tlv::Management_Error_Status(tlv::Management_Error_Status::new(
+                values::ManagementErrorId::NOT_SETABLE,
+                Enumeration::Enum(values::ManagementId::DOMAIN),
+            )),
+        ],
+    );
+    let bytes = response.clone().to_bytes();
+    assert_eq!(bytes.len(), 48 + 10 + 12);
+    assert_eq!(
+        bytes.len(),
+        response.header.messageLength.into_inner() as usize
+    );
+    let (rest, parsed) = parse_ptp_message(&bytes).unwrap();
+    assert!(rest.is_empty());
+    assert_eq!(parsed, response);
+
+    let fields = describe(&ManagementData::Time_Properties_Data_Set(time_properties));
+    assert_eq!(
+        fields[0],
+        ("currentUtcOffset".to_string(), "37".to_string())
+    );
+    assert_eq!(
+        fields[3],
+        ("currentUtcOffsetValid".to_string(), "1".to_string())
+    );
+    assert_eq!(fields[7], ("timeSource".to_string(), "0x20".to_string()));
+}
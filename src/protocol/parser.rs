@@ -1,10 +1,13 @@
+use crate::protocol::types::datasets::*;
 use crate::protocol::types::derived::*;
 use crate::protocol::types::enums::*;
-use crate::protocol::types::message::{body, Body, Header, Message};
+use crate::protocol::types::message::{body, tlv, Body, Header, Message, Tlv};
 use crate::protocol::types::primitive::int::TryInto;
+use crate::protocol::types::primitive::{Enumeration16, Integer4, Integer48, Nibble, Octet};
 
+use nom::combinator::rest;
 use nom::error::ParseError;
-use nom::number::streaming::{be_i16, be_i64, be_i8, be_u16, be_u32, be_u8};
+use nom::number::streaming::{be_i16, be_i32, be_i64, be_i8, be_u16, be_u32, be_u8};
 use nom::{Err, IResult, Needed};
 
 #[inline]
@@ -22,6 +25,33 @@ pub fn be_u48<'a, E: ParseError<&'a [u8]>>(i: &'a [u8]) -> IResult<&'a [u8], u64
     }
 }
 
+/// 48 bit two's complement integer, sign extended
+#[inline]
+pub fn be_i48<'a, E: ParseError<&'a [u8]>>(i: &'a [u8]) -> IResult<&'a [u8], i64, E> {
+    be_integer48(i).map(|(i, value)| (i, value.into_inner()))
+}
+
+#[inline]
+pub fn be_integer48<'a, E: ParseError<&'a [u8]>>(i: &'a [u8]) -> IResult<&'a [u8], Integer48, E> {
+    be_u48(i).map(|(i, raw)| (i, Integer48::wrapping_new(raw as i64)))
+}
+
+/// Two 4 bit fields sharing an octet, high nibble first
+pub fn parse_nibbles(i: &[u8]) -> IResult<&[u8], (Nibble, Nibble)> {
+    map!(i, be_u8, |octet| (
+        Nibble::wrapping_new(octet >> 4),
+        Nibble::wrapping_new(octet)
+    ))
+}
+
+/// Two signed 4 bit fields sharing an octet, high nibble first, sign extended
+pub fn parse_signed_nibbles(i: &[u8]) -> IResult<&[u8], (Integer4, Integer4)> {
+    map!(i, be_u8, |octet| (
+        Integer4::wrapping_new(octet as i8 >> 4),
+        Integer4::wrapping_new(octet as i8)
+    ))
+}
+
 named!(#[allow(non_snake_case)], pub parse_timestamp<Timestamp>,
     do_parse!(
         secondsField: be_u48 >>
@@ -62,16 +92,298 @@ named!(#[allow(non_snake_case)], pub parse_clock_quality<ClockQuality>,
         )
     )
 );
+named!(#[allow(non_snake_case)], pub parse_default_ds<DefaultDS>,
+    do_parse!(
+        flags: be_u8 >>
+        _reserved1: be_u8 >>
+        numberPorts: be_u16 >>
+        priority1: be_u8 >>
+        clockQuality: parse_clock_quality >>
+        priority2: be_u8 >>
+        clockIdentity: take!(8) >>
+        domainNumber: be_u8 >>
+        _reserved2: be_u8 >>
+        (
+            DefaultDS {
+                flags: flags.try_into().unwrap(),
+                _reserved1: _reserved1.try_into().unwrap(),
+                numberPorts: numberPorts.try_into().unwrap(),
+                priority1: priority1.try_into().unwrap(),
+                clockQuality,
+                priority2: priority2.try_into().unwrap(),
+                clockIdentity: clockIdentity.try_into().unwrap(),
+                domainNumber: domainNumber.try_into().unwrap(),
+                _reserved2: _reserved2.try_into().unwrap()
+            }
+        )
+    )
+);
+
+named!(#[allow(non_snake_case)], pub parse_current_ds<CurrentDS>,
+    do_parse!(
+        stepsRemoved: be_u16 >>
+        offsetFromMaster: be_i64 >>
+        meanPathDelay: be_i64 >>
+        (
+            CurrentDS {
+                stepsRemoved: stepsRemoved.try_into().unwrap(),
+                offsetFromMaster: TimeInterval::new(offsetFromMaster),
+                meanPathDelay: TimeInterval::new(meanPathDelay)
+            }
+        )
+    )
+);
+
+named!(#[allow(non_snake_case)], pub parse_parent_ds<ParentDS>,
+    do_parse!(
+        parentPortIdentity: parse_port_identity >>
+        flags: be_u8 >>
+        _reserved: be_u8 >>
+        observedParentOffsetScaledLogVariance: be_u16 >>
+        observedParentClockPhaseChangeRate: be_i32 >>
+        grandmasterPriority1: be_u8 >>
+        grandmasterClockQuality: parse_clock_quality >>
+        grandmasterPriority2: be_u8 >>
+        grandmasterIdentity: take!(8) >>
+        (
+            ParentDS {
+                parentPortIdentity,
+                flags: flags.try_into().unwrap(),
+                _reserved: _reserved.try_into().unwrap(),
+                observedParentOffsetScaledLogVariance:
+                    observedParentOffsetScaledLogVariance.try_into().unwrap(),
+                observedParentClockPhaseChangeRate:
+                    observedParentClockPhaseChangeRate.try_into().unwrap(),
+                grandmasterPriority1: grandmasterPriority1.try_into().unwrap(),
+                grandmasterClockQuality,
+                grandmasterPriority2: grandmasterPriority2.try_into().unwrap(),
+                grandmasterIdentity: grandmasterIdentity.try_into().unwrap()
+            }
+        )
+    )
+);
+
+named!(#[allow(non_snake_case)], pub parse_time_properties_ds<TimePropertiesDS>,
+    do_parse!(
+        currentUtcOffset: be_i16 >>
+        flags: be_u8 >>
+        timeSource: be_u8 >>
+        (
+            TimePropertiesDS {
+                currentUtcOffset: currentUtcOffset.try_into().unwrap(),
+                flags: flags.try_into().unwrap(),
+                timeSource: timeSource.into()
+            }
+        )
+    )
+);
+
+named!(#[allow(non_snake_case)], pub parse_port_ds<PortDS>,
+    do_parse!(
+        portIdentity: parse_port_identity >>
+        portState: be_u8 >>
+        logMinDelayReqInterval: be_i8 >>
+        peerMeanPathDelay: be_i64 >>
+        logAnnounceInterval: be_i8 >>
+        announceReceiptTimeout: be_u8 >>
+        logSyncInterval: be_i8 >>
+        delayMechanism: be_u8 >>
+        logMinPdelayReqInterval: be_i8 >>
+        b0: parse_nibbles >>
+        (
+            PortDS {
+                portIdentity,
+                portState: portState.into(),
+                logMinDelayReqInterval: logMinDelayReqInterval.try_into().unwrap(),
+                peerMeanPathDelay: TimeInterval::new(peerMeanPathDelay),
+                logAnnounceInterval: logAnnounceInterval.try_into().unwrap(),
+                announceReceiptTimeout: announceReceiptTimeout.try_into().unwrap(),
+                logSyncInterval: logSyncInterval.try_into().unwrap(),
+                delayMechanism: delayMechanism.into(),
+                logMinPdelayReqInterval: logMinPdelayReqInterval.try_into().unwrap(),
+                _reserved: b0.0,
+                versionNumber: b0.1
+            }
+        )
+    )
+);
+
+named!(#[allow(non_snake_case)], pub parse_port_address<PortAddress>,
+    do_parse!(
+        networkProtocol: be_u16 >>
+        addressLength: be_u16 >>
+        addressField: take!(addressLength) >>
+        (
+            PortAddress {
+                networkProtocol: Enumeration16::new(networkProtocol).into(),
+                addressLength: addressLength.into(),
+                addressField: addressField.iter().map(|&octet| octet.into()).collect()
+            }
+        )
+    )
+);
+
+named!(#[allow(non_snake_case)], pub parse_ptp_text<PTPText>,
+    do_parse!(
+        lengthField: be_u8 >>
+        textField: take!(lengthField) >>
+        (
+            PTPText {
+                lengthField: lengthField.into(),
+                textField: textField.iter().map(|&octet| octet.into()).collect()
+            }
+        )
+    )
+);
+
+// Parsed within faultRecordLength, so octets of a longer record are skipped
+named!(#[allow(non_snake_case)], pub parse_fault_record<FaultRecord>,
+    do_parse!(
+        faultRecordLength: be_u16 >>
+        record: flat_map!(take!(faultRecordLength), do_parse!(
+            faultTime: parse_timestamp >>
+            severityCode: be_u8 >>
+            faultName: parse_ptp_text >>
+            faultValue: parse_ptp_text >>
+            faultDescription: parse_ptp_text >>
+            (
+                FaultRecord {
+                    faultRecordLength: faultRecordLength.into(),
+                    faultTime,
+                    severityCode: severityCode.into(),
+                    faultName,
+                    faultValue,
+                    faultDescription
+                }
+            )
+        )) >>
+        (record)
+    )
+);
+
+named!(#[allow(non_snake_case)], parse_user_description<tlv::User_Description>,
+    do_parse!(
+        userDescription: parse_ptp_text >>
+        _pad: rest >>
+        (
+            tlv::User_Description {
+                userDescription,
+                _pad: _pad.iter().map(|&octet| octet.into()).collect()
+            }
+        )
+    )
+);
+
+/// Optional displayData of MANAGEMENT_ERROR_STATUS and the padding after it
+fn parse_display_data(i: &[u8]) -> IResult<&[u8], (Option<PTPText>, Vec<Octet>)> {
+    if i.is_empty() {
+        return Ok((i, (None, Vec::new())));
+    }
+    let (i, text) = parse_ptp_text(i)?;
+    let (i, pad) = rest(i)?;
+    Ok((
+        i,
+        (Some(text), pad.iter().map(|&octet| octet.into()).collect()),
+    ))
+}
+
+named!(#[allow(non_snake_case)], parse_fault_log<tlv::Fault_Log>,
+    do_parse!(
+        numberOfFaultRecords: be_u16 >>
+        faultRecords: count!(parse_fault_record, numberOfFaultRecords as usize) >>
+        _pad: rest >>
+        (
+            tlv::Fault_Log {
+                numberOfFaultRecords: numberOfFaultRecords.into(),
+                faultRecords,
+                _pad: _pad.iter().map(|&octet| octet.into()).collect()
+            }
+        )
+    )
+);
+
+macro_rules! parse_value (
+    ($i:expr, $parser:ident, $variant:ident) => (
+        do_parse!($i,
+            value: $parser >>
+            _reserved: be_u8 >>
+            (
+                tlv::ManagementData::$variant(tlv::Value {
+                    value: value.try_into().unwrap(),
+                    _reserved: _reserved.try_into().unwrap(),
+                })
+            )
+        )
+    );
+);
+
+/// Parses the dataField of a MANAGEMENT TLV, keeping it raw when it's empty
+/// or the managementId isn't supported
+pub fn parse_management_data(
+    i: &[u8],
+    management_id: ManagementId,
+) -> IResult<&[u8], tlv::ManagementData> {
+    use values::ManagementId::*;
+
+    if i.is_empty() {
+        return Ok((i, tlv::ManagementData::empty()));
+    }
+    let data = match management_id {
+        Enumeration::Enum(DEFAULT_DATA_SET) => {
+            map!(i, parse_default_ds, tlv::ManagementData::Default_Data_Set)
+        }
+        Enumeration::Enum(CURRENT_DATA_SET) => {
+            map!(i, parse_current_ds, tlv::ManagementData::Current_Data_Set)
+        }
+        Enumeration::Enum(PARENT_DATA_SET) => {
+            map!(i, parse_parent_ds, tlv::ManagementData::Parent_Data_Set)
+        }
+        Enumeration::Enum(TIME_PROPERTIES_DATA_SET) => map!(
+            i,
+            parse_time_properties_ds,
+            tlv::ManagementData::Time_Properties_Data_Set
+        ),
+        Enumeration::Enum(PORT_DATA_SET) => {
+            map!(i, parse_port_ds, tlv::ManagementData::Port_Data_Set)
+        }
+        Enumeration::Enum(PRIORITY1) => parse_value!(i, be_u8, Priority1),
+        Enumeration::Enum(PRIORITY2) => parse_value!(i, be_u8, Priority2),
+        Enumeration::Enum(DOMAIN) => parse_value!(i, be_u8, Domain),
+        Enumeration::Enum(SLAVE_ONLY) => parse_value!(i, be_u8, Slave_Only),
+        Enumeration::Enum(LOG_ANNOUNCE_INTERVAL) => parse_value!(i, be_i8, Log_Announce_Interval),
+        Enumeration::Enum(ANNOUNCE_RECEIPT_TIMEOUT) => {
+            parse_value!(i, be_u8, Announce_Receipt_Timeout)
+        }
+        Enumeration::Enum(LOG_SYNC_INTERVAL) => parse_value!(i, be_i8, Log_Sync_Interval),
+        Enumeration::Enum(VERSION_NUMBER) => parse_value!(i, be_u8, Version_Number),
+        Enumeration::Enum(DELAY_MECHANISM) => parse_value!(i, be_u8, Delay_Mechanism),
+        Enumeration::Enum(LOG_MIN_PDELAY_REQ_INTERVAL) => {
+            parse_value!(i, be_i8, Log_Min_Pdelay_Req_Interval)
+        }
+        Enumeration::Enum(USER_DESCRIPTION) => map!(
+            i,
+            parse_user_description,
+            tlv::ManagementData::User_Description
+        ),
+        Enumeration::Enum(FAULT_LOG) => map!(i, parse_fault_log, tlv::ManagementData::Fault_Log),
+        _ => Err(Err::Error(error_position!(
+            i,
+            nom::error::ErrorKind::Switch
+        ))),
+    };
+    data.or_else(|_| {
+        Ok((
+            &i[i.len()..],
+            tlv::ManagementData::Raw(i.iter().map(|&octet| octet.into()).collect()),
+        ))
+    })
+}
+
 #[allow(non_snake_case)]
-pub fn parse_ptp_header<'a>(i: &'a [u8]) -> IResult<&'a [u8], Header> {
+pub fn parse_ptp_header(i: &[u8]) -> IResult<&[u8], Header> {
     do_parse!(
         i,
-        b0: bits!(tuple!(
-            take_bits!(4u8),
-            take_bits!(4u8),
-            take_bits!(4u8),
-            take_bits!(4u8)
-        )) >> messageLength: be_u16
+        b0: parse_nibbles >> b1: parse_nibbles >> messageLength: be_u16
             >> domainNumber: be_u8
             >> _reserved2: be_u8
             >> flagField: take!(2)
@@ -83,28 +395,167 @@ pub fn parse_ptp_header<'a>(i: &'a [u8]) -> IResult<&'a [u8], Header> {
             >> logMessageInterval: be_i8
             >> (Header {
                 transportSpecific: b0.0,
-                messageType: b0.1,
-                _reserved1: b0.2,
-                versionPTP: b0.3,
+                messageType: b0.1.into(),
+                _reserved1: b1.0,
+                versionPTP: b1.1,
                 messageLength: messageLength.try_into().unwrap(),
                 domainNumber: domainNumber.try_into().unwrap(),
                 _reserved2: _reserved2.try_into().unwrap(),
                 flagField: flagField.try_into().unwrap(),
-                correctionField: correctionField.into(),
+                correctionField: TimeInterval::new(correctionField),
                 _reserved3: _reserved3.try_into().unwrap(),
                 sourcePortIdentity,
                 sequenceId: sequenceId.try_into().unwrap(),
-                controlField: controlField.try_into().unwrap(),
+                controlField: controlField.into(),
                 logMessageInterval: logMessageInterval.try_into().unwrap(),
             })
     )
 }
 
+#[allow(non_snake_case)]
+macro_rules! parse_tlv_value (
+    ($i:expr, $tlv_type:expr, $length_field:expr) => (
+        {
+            match $tlv_type {
+                Enumeration::Enum(values::TlvType::REQUEST_UNICAST_TRANSMISSION) => {
+                    do_parse!($i,
+                        b0: parse_nibbles >>
+                        logInterMessagePeriod: be_i8 >>
+                        durationField: be_u32 >>
+                        (
+                            Tlv::Request_Unicast_Transmission(tlv::Request_Unicast_Transmission {
+                                tlvType: $tlv_type,
+                                lengthField: $length_field,
+                                messageType: b0.0.into(),
+                                _reserved: b0.1,
+                                logInterMessagePeriod: logInterMessagePeriod.try_into().unwrap(),
+                                durationField: durationField.try_into().unwrap(),
+                            })
+                        )
+                    )
+                },
+                Enumeration::Enum(values::TlvType::GRANT_UNICAST_TRANSMISSION) => {
+                    do_parse!($i,
+                        b0: parse_nibbles >>
+                        logInterMessagePeriod: be_i8 >>
+                        durationField: be_u32 >>
+                        _reserved2: be_u8 >>
+                        flags: be_u8 >>
+                        (
+                            Tlv::Grant_Unicast_Transmission(tlv::Grant_Unicast_Transmission {
+                                tlvType: $tlv_type,
+                                lengthField: $length_field,
+                                messageType: b0.0.into(),
+                                _reserved1: b0.1,
+                                logInterMessagePeriod: logInterMessagePeriod.try_into().unwrap(),
+                                durationField: durationField.try_into().unwrap(),
+                                _reserved2: _reserved2.try_into().unwrap(),
+                                flags: flags.try_into().unwrap(),
+                            })
+                        )
+                    )
+                },
+                Enumeration::Enum(values::TlvType::CANCEL_UNICAST_TRANSMISSION) => {
+                    do_parse!($i,
+                        b0: parse_nibbles >>
+                        _reserved2: be_u8 >>
+                        (
+                            Tlv::Cancel_Unicast_Transmission(tlv::Cancel_Unicast_Transmission {
+                                tlvType: $tlv_type,
+                                lengthField: $length_field,
+                                messageType: b0.0.into(),
+                                _reserved1: b0.1,
+                                _reserved2: _reserved2.try_into().unwrap(),
+                            })
+                        )
+                    )
+                },
+                Enumeration::Enum(values::TlvType::ACKNOWLEDGE_CANCEL_UNICAST_TRANSMISSION) => {
+                    do_parse!($i,
+                        b0: parse_nibbles >>
+                        _reserved2: be_u8 >>
+                        (
+                            Tlv::Acknowledge_Cancel_Unicast_Transmission(
+                                tlv::Acknowledge_Cancel_Unicast_Transmission {
+                                    tlvType: $tlv_type,
+                                    lengthField: $length_field,
+                                    messageType: b0.0.into(),
+                                    _reserved1: b0.1,
+                                    _reserved2: _reserved2.try_into().unwrap(),
+                                }
+                            )
+                        )
+                    )
+                },
+                Enumeration::Enum(values::TlvType::MANAGEMENT) => {
+                    do_parse!($i,
+                        managementId: be_u16 >>
+                        dataField: call!(parse_management_data, managementId.into()) >>
+                        (
+                            Tlv::Management(tlv::Management {
+                                tlvType: $tlv_type,
+                                lengthField: $length_field,
+                                managementId: managementId.into(),
+                                dataField,
+                            })
+                        )
+                    )
+                },
+                Enumeration::Enum(values::TlvType::MANAGEMENT_ERROR_STATUS) => {
+                    do_parse!($i,
+                        managementErrorId: be_u16 >>
+                        managementId: be_u16 >>
+                        _reserved: take!(4) >>
+                        displayData: parse_display_data >>
+                        (
+                            Tlv::Management_Error_Status(tlv::Management_Error_Status {
+                                tlvType: $tlv_type,
+                                lengthField: $length_field,
+                                managementErrorId: managementErrorId.into(),
+                                managementId: managementId.into(),
+                                _reserved: _reserved.try_into().unwrap(),
+                                displayData: displayData.0,
+                                _pad: displayData.1,
+                            })
+                        )
+                    )
+                },
+                _ => {
+                    do_parse!($i,
+                        valueField: rest >>
+                        (
+                            Tlv::Unknown(TLV {
+                                tlvType: $tlv_type,
+                                lengthField: $length_field,
+                                valueField: valueField.iter().map(|&octet| octet.into()).collect(),
+                            })
+                        )
+                    )
+                }
+            }
+        }
+    );
+);
+
+#[allow(non_snake_case)]
+pub fn parse_tlv(i: &[u8]) -> IResult<&[u8], Tlv> {
+    do_parse!(
+        i,
+        tlvType: be_u16
+            >> lengthField: be_u16
+            >> tlv: flat_map!(
+                take!(lengthField),
+                parse_tlv_value!(TlvType::from(tlvType), lengthField.try_into().unwrap())
+            )
+            >> (tlv)
+    )
+}
+
 #[allow(non_snake_case)]
 macro_rules! parse_ptp_body (
-    ($i:expr, $message_type:expr) => (
+    ($i:expr, $header:expr) => (
         {
-            match $message_type {
+            match $header.messageType {
                 Enumeration::Enum(values::MessageType::Sync) => {
                     do_parse!($i,
                         originTimestamp: parse_timestamp >>
@@ -173,8 +624,47 @@ macro_rules! parse_ptp_body (
                         )
                     )
                 },
+                Enumeration::Enum(values::MessageType::Signaling) => {
+                    do_parse!($i,
+                        targetPortIdentity: parse_port_identity >>
+                        tlvs: flat_map!(
+                            take!(($header.messageLength.into_inner() as usize).saturating_sub(44)),
+                            many0!(complete!(parse_tlv))
+                        ) >>
+                        (
+                            Body::Signaling(body::Signaling {
+                                targetPortIdentity,
+                                tlvs
+                            })
+                        )
+                    )
+                },
+                Enumeration::Enum(values::MessageType::Management) => {
+                    do_parse!($i,
+                        targetPortIdentity: parse_port_identity >>
+                        startingBoundaryHops: be_u8 >>
+                        boundaryHops: be_u8 >>
+                        b0: parse_nibbles >>
+                        _reserved2: be_u8 >>
+                        tlvs: flat_map!(
+                            take!(($header.messageLength.into_inner() as usize).saturating_sub(48)),
+                            many0!(complete!(parse_tlv))
+                        ) >>
+                        (
+                            Body::Management(body::Management {
+                                targetPortIdentity,
+                                startingBoundaryHops: startingBoundaryHops.try_into().unwrap(),
+                                boundaryHops: boundaryHops.try_into().unwrap(),
+                                _reserved1: b0.0,
+                                actionField: b0.1.into(),
+                                _reserved2: _reserved2.try_into().unwrap(),
+                                tlvs
+                            })
+                        )
+                    )
+                },
                 _ => {
-                    eprintln!("Unknown message type: {:?}, couldn't parse!", $message_type);
+                    eprintln!("Unknown message type: {:?}, couldn't parse!", $header.messageType);
                     Ok(($i, Body::Empty))
                 }
             }
@@ -185,7 +675,7 @@ macro_rules! parse_ptp_body (
 named!(#[allow(non_snake_case)], pub parse_ptp_message<Message>,
     do_parse!(
         header: parse_ptp_header >>
-        body: parse_ptp_body!(header.messageType) >>
+        body: parse_ptp_body!(header) >>
         (
             Message {
                 header,
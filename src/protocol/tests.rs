@@ -25,9 +25,11 @@ pub fn test_bit_serialize() {
 #[test]
 pub fn test_bit_serialize2() {
     use crate::protocol::parser::parse_ptp_message;
-    use crate::protocol::types::derived::{ClockQuality, PortIdentity, Timestamp};
-    use crate::protocol::types::enums::values::{MessageType, TimeSource};
-    use crate::protocol::types::enums::Enumeration::{Enum, Unknown};
+    use crate::protocol::types::derived::{ClockQuality, PortIdentity, TimeInterval, Timestamp};
+    use crate::protocol::types::enums::values::{
+        ClockAcurracy, ControlField, MessageType, TimeSource,
+    };
+    use crate::protocol::types::enums::Enumeration::Enum;
     use crate::protocol::types::message::{body, Body, Header, Message};
     use crate::protocol::types::primitive::int::{BitSerialize, Int};
 
@@ -42,7 +44,7 @@ pub fn test_bit_serialize2() {
             domainNumber: Int::new(0),
             _reserved2: Int::new(0),
             flagField: [Int::new(0), Int::new(0)],
-            correctionField: Int::new(0),
+            correctionField: TimeInterval::new(0),
             _reserved3: [Int::new(0), Int::new(0), Int::new(0), Int::new(0)],
             sourcePortIdentity: PortIdentity {
                 clockIdentity: [
@@ -58,7 +60,7 @@ pub fn test_bit_serialize2() {
                 portNumber: Int::new(1),
             },
             sequenceId: Int::new(13123),
-            controlField: Int::new(5),
+            controlField: Enum(ControlField::ALL_OTHERS),
             logMessageInterval: Int::new(1),
         },
         body: Body::Announce(body::Announce {
@@ -71,7 +73,7 @@ pub fn test_bit_serialize2() {
             grandmasterPriority1: Int::new(128),
             grandmasterClockQuality: ClockQuality {
                 clockClass: Int::new(248),
-                clockAccuracy: Unknown(Int::new(254)),
+                clockAccuracy: Enum(ClockAcurracy::UNKNOWN),
                 offsetScaledLogVariance: Int::new(65535),
             },
             grandmasterPriority2: Int::new(128),
@@ -90,9 +92,720 @@ pub fn test_bit_serialize2() {
         }),
     };
     let mut writer = BitWriter::endian(Vec::new(), BigEndian);
-    msg.bit_serialize(&mut writer).unwrap();
+    msg.clone().bit_serialize(&mut writer).unwrap();
     let wwriter = writer.into_writer();
     let msg2 = parse_ptp_message(&wwriter).unwrap();
     assert_eq!(msg2.0.len(), 0);
     assert_eq!(msg, msg2.1);
 }
+
+#[test]
+pub fn test_signaling() {
+    use crate::protocol::parser::parse_ptp_message;
+    use crate::protocol::types::derived::PortIdentity;
+    use crate::protocol::types::enums::values::MessageType;
+    use crate::protocol::types::enums::Enumeration::Enum;
+    use crate::protocol::types::message::{tlv, Tlv};
+    use crate::protocol::types::primitive::int::{BitSerialize, Int};
+    use crate::unicast::signaling;
+
+    use bitstream_io::{BigEndian, BitWriter};
+    let pi = PortIdentity {
+        clockIdentity: [Int::new(15); 8],
+        portNumber: Int::new(1),
+    };
+    let msg = signaling(
+        pi,
+        PortIdentity {
+            clockIdentity: [Int::new(0xFF); 8],
+            portNumber: Int::new(0xFFFF),
+        },
+        0,
+        7,
+        vec![
+            Tlv::Request_Unicast_Transmission(tlv::Request_Unicast_Transmission::new(
+                Enum(MessageType::Sync),
+                -3,
+                300,
+            )),
+            Tlv::Grant_Unicast_Transmission(tlv::Grant_Unicast_Transmission::new(
+                Enum(MessageType::Announce),
+                1,
+                60,
+                true,
+            )),
+            Tlv::Cancel_Unicast_Transmission(tlv::Cancel_Unicast_Transmission::new(Enum(
+                MessageType::Delay_Resp,
+            ))),
+        ],
+    );
+    assert_eq!(msg.header.messageLength, Int::new(44 + 10 + 12 + 6));
+    let mut writer = BitWriter::endian(Vec::new(), BigEndian);
+    msg.clone().bit_serialize(&mut writer).unwrap();
+    let mut wwriter = writer.into_writer();
+    assert_eq!(wwriter.len(), 72);
+    // Trailing bytes past messageLength are not parsed as TLVs
+    wwriter.extend_from_slice(&[0; 8]);
+    let msg2 = parse_ptp_message(&wwriter).unwrap();
+    assert_eq!(msg, msg2.1);
+}
+
+#[test]
+pub fn test_clock_identity() {
+    use crate::protocol::types::derived::{
+        clock_identity_from_mac, format_clock_identity, parse_clock_identity,
+    };
+
+    let identity = clock_identity_from_mac([0xb8, 0x27, 0xeb, 0x92, 0xb1, 0xa6]);
+    assert_eq!(format_clock_identity(&identity), "b827eb.fffe.92b1a6");
+    assert_eq!(parse_clock_identity("b827eb.fffe.92b1a6"), Some(identity));
+    assert_eq!(parse_clock_identity("B827EB.FFFE.92B1A6"), Some(identity));
+    assert_eq!(parse_clock_identity("b827eb.fffe.92b1a"), None);
+    assert_eq!(parse_clock_identity("b827ebfffe92b1a6"), None);
+    assert_eq!(parse_clock_identity("b827eb.fffe.92b1ag"), None);
+    assert_eq!(parse_clock_identity("b827eb.+ffe.92b1a6"), None);
+}
+
+#[test]
+pub fn test_json() {
+    use crate::protocol::types::derived::{
+        clock_identity_from_mac, PortIdentity, TimeInterval, Timestamp,
+    };
+    use crate::protocol::types::enums::{values, Enumeration};
+    use crate::protocol::types::message::{body, Body, Header, Message, Received};
+    use crate::protocol::types::primitive::int::Int;
+
+    let source = PortIdentity {
+        clockIdentity: clock_identity_from_mac([0xb8, 0x27, 0xeb, 0x92, 0xb1, 0xa6]),
+        portNumber: Int::new(1),
+    };
+    let mut message = Message {
+        header: Header::new(values::MessageType::Sync, 44, 0, source, 7, -3),
+        body: Body::Sync(body::Sync {
+            originTimestamp: Timestamp {
+                secondsField: Int::new(1_700_000_000),
+                nanosecondsField: Int::new(5),
+            },
+        }),
+    };
+    let received = Received {
+        source: "192.168.1.10:319".to_string(),
+        timestamp: std::time::Duration::new(1_700_000_000, 42),
+        message: &message,
+    };
+    let json: serde_json::Value = serde_json::from_str(&received.to_json()).unwrap();
+    assert_eq!(json["source"], "192.168.1.10:319");
+    assert_eq!(json["timestamp"]["nanos"], 42);
+    let header = &json["message"]["header"];
+    assert_eq!(header["messageType"], "Sync");
+    assert_eq!(header["logMessageInterval"], -3);
+    assert_eq!(
+        header["sourcePortIdentity"]["clockIdentity"],
+        "b827eb.fffe.92b1a6"
+    );
+    assert!(header.get("_reserved1").is_none());
+    assert_eq!(
+        json["message"]["body"]["Sync"]["originTimestamp"]["secondsField"],
+        1_700_000_000u64
+    );
+
+    message.header.messageType = Enumeration::Unknown(Int::new(0x7));
+    message.header.correctionField = TimeInterval::from_nanoseconds(2.5);
+    let json = serde_json::to_value(&message).unwrap();
+    assert_eq!(json["header"]["messageType"], 7);
+    assert_eq!(json["header"]["correctionField"], 0x28000);
+}
+
+#[test]
+pub fn test_display() {
+    use crate::protocol::types::derived::{
+        clock_identity_from_mac, PortIdentity, TimeInterval, Timestamp,
+    };
+    use crate::protocol::types::enums::{values, ClockAcurracy};
+    use crate::protocol::types::message::{body, tlv, Body, Header, Message, Tlv};
+    use crate::protocol::types::primitive::int::Int;
+
+    let timestamp = Timestamp {
+        secondsField: Int::new(1_700_000_000),
+        nanosecondsField: Int::new(5),
+    };
+    assert_eq!(timestamp.to_string(), "1700000000.000000005");
+    assert_eq!(format!("{:#}", timestamp), "2023-11-14T22:13:20.000000005Z");
+    let leap_day = Timestamp {
+        secondsField: Int::new(951_782_400),
+        nanosecondsField: Int::new(0),
+    };
+    assert_eq!(leap_day.rfc3339(), "2000-02-29T00:00:00.000000000Z");
+
+    let source = PortIdentity {
+        clockIdentity: clock_identity_from_mac([0xb8, 0x27, 0xeb, 0x92, 0xb1, 0xa6]),
+        portNumber: Int::new(1),
+    };
+    assert_eq!(source.to_string(), "b827eb.fffe.92b1a6-1");
+    let accuracy: ClockAcurracy = 0x1Fu8.into();
+    assert_eq!(accuracy.to_string(), "WITHIN_10_NS");
+    let accuracy: ClockAcurracy = 0x80u8.into();
+    assert_eq!(accuracy.to_string(), "Unknown(0x80)");
+
+    let mut sync = Message {
+        header: Header::new(values::MessageType::Sync, 44, 0, source, 7, 0),
+        body: Body::Sync(body::Sync {
+            originTimestamp: timestamp,
+        }),
+    };
+    sync.header.correctionField = TimeInterval::from_nanoseconds(1.5);
+    assert_eq!(
+        sync.to_string(),
+        "Sync seq 7 domain 0 from b827eb.fffe.92b1a6-1 correction 1.5 ns, \
+         origin 1700000000.000000005"
+    );
+    let management = crate::management::management(
+        source,
+        crate::management::all_ports(),
+        0,
+        1,
+        0,
+        values::ActionField::GET,
+        vec![Tlv::Management(tlv::Management::new(
+            values::ManagementId::PRIORITY1,
+            tlv::ManagementData::empty(),
+        ))],
+    );
+    assert_eq!(
+        management.to_string(),
+        "Management seq 1 domain 0 from b827eb.fffe.92b1a6-1, GET target \
+         ffffff.ffff.ffffff-65535 [MANAGEMENT PRIORITY1]"
+    );
+}
+
+#[test]
+pub fn test_clock_accuracy() {
+    use crate::protocol::types::enums::values::ClockAcurracy;
+    use crate::protocol::types::enums::{ClockAcurracy as Accuracy, Enumeration};
+    use crate::protocol::types::primitive::Enumeration8;
+
+    assert_eq!(ClockAcurracy::WITHIN_25_NS.nanoseconds(), Some(25.0));
+    assert_eq!(ClockAcurracy::WITHIN_2_5_PS.nanoseconds(), Some(0.0025));
+    assert_eq!(
+        ClockAcurracy::GREATER_10_S.nanoseconds(),
+        Some(f64::INFINITY)
+    );
+    assert_eq!(ClockAcurracy::UNKNOWN.nanoseconds(), None);
+    assert_eq!(
+        ClockAcurracy::from_nanoseconds(25.0),
+        ClockAcurracy::WITHIN_25_NS
+    );
+    assert_eq!(
+        ClockAcurracy::from_nanoseconds(-60.0),
+        ClockAcurracy::WITHIN_100_NS
+    );
+    assert_eq!(
+        ClockAcurracy::from_nanoseconds(11e9),
+        ClockAcurracy::GREATER_10_S
+    );
+
+    // Lower values are better in the BMCA, unknown being the worst known value
+    assert!(ClockAcurracy::WITHIN_1_PS < ClockAcurracy::WITHIN_25_NS);
+    assert!(ClockAcurracy::GREATER_10_S < ClockAcurracy::UNKNOWN);
+    let profile: Accuracy = 0x80u8.into();
+    let unknown: Accuracy = 0xFEu8.into();
+    assert_eq!(unknown, Enumeration::Enum(ClockAcurracy::UNKNOWN));
+    assert!(Enumeration8::from(profile) < Enumeration8::from(unknown));
+    assert!(profile < unknown);
+}
+
+#[test]
+pub fn test_enumerations() {
+    use crate::protocol::types::enums::{self, values, Enumeration};
+    use crate::protocol::types::primitive::Enumeration16;
+
+    let state: enums::PortState = 9u8.into();
+    assert_eq!(state, Enumeration::Enum(values::PortState::SLAVE));
+    let mechanism: enums::DelayMechanism = 0xFEu8.into();
+    assert_eq!(mechanism.to_string(), "DISABLED");
+    let mechanism: enums::DelayMechanism = 3u8.into();
+    assert_eq!(
+        mechanism,
+        Enumeration::Enum(values::DelayMechanism::COMMON_P2P)
+    );
+    let protocol: enums::NetworkProtocol = Enumeration16::new(3).into();
+    assert_eq!(
+        protocol,
+        Enumeration::Enum(values::NetworkProtocol::IEEE_802_3)
+    );
+    let severity: enums::FaultSeverity = 8u8.into();
+    assert_eq!(severity.to_string(), "Unknown(0x8)");
+
+    // Ordered by raw value whether known or not
+    let mut states: Vec<enums::PortState> = vec![0xFEu8.into(), 9u8.into(), 1u8.into()];
+    states.sort();
+    assert_eq!(states, vec![1u8.into(), 9u8.into(), 0xFEu8.into()]);
+    assert!(enums::PortState::from(9u8) > Enumeration::Enum(values::PortState::MASTER));
+    assert_eq!(
+        enums::MessageType::default(),
+        Enumeration::Enum(values::MessageType::Sync)
+    );
+    assert_eq!(enums::FaultSeverity::default().to_string(), "EMERGENCY");
+    assert_eq!(
+        "PATH_TRACE_LIST".parse(),
+        Ok(values::ManagementId::PATH_TRACE_LIST)
+    );
+    assert_eq!(
+        values::ControlField::from(values::MessageType::Announce),
+        values::ControlField::ALL_OTHERS
+    );
+}
+
+#[test]
+pub fn test_time_interval() {
+    use crate::protocol::types::derived::TimeInterval;
+    use std::convert::TryFrom;
+    use std::time::Duration;
+
+    let interval = TimeInterval::from_nanoseconds(2.5);
+    assert_eq!(interval.scaled(), 0x28000);
+    assert_eq!(interval.to_string(), "2.5");
+    assert_eq!(
+        TimeInterval::from(Duration::from_micros(3)),
+        TimeInterval::from_nanoseconds(3000.0)
+    );
+    assert_eq!(
+        Duration::try_from(interval + TimeInterval::from_nanoseconds(1.0)),
+        Ok(Duration::from_nanos(3))
+    );
+    assert!(Duration::try_from(-interval).is_err());
+    assert_eq!((interval - interval).nanoseconds(), 0.0);
+
+    // Saturates rather than wrapping around
+    let max = TimeInterval::new(i64::MAX);
+    assert_eq!(max + interval, max);
+    assert_eq!(
+        TimeInterval::new(i64::MIN) - interval,
+        TimeInterval::new(i64::MIN)
+    );
+    assert_eq!(TimeInterval::from(Duration::from_secs(1 << 40)), max);
+}
+
+#[test]
+pub fn test_timestamp() {
+    use crate::protocol::types::derived::{TimeInterval, Timestamp};
+    use crate::protocol::types::primitive::int::Int;
+    use nix::sys::time::{TimeSpec, TimeVal, TimeValLike};
+    use std::time::{Duration, SystemTime};
+
+    const MAX_SECONDS: u64 = (1 << 48) - 1;
+    let ns = TimeInterval::from_nanoseconds;
+
+    // Normalization carries whole seconds out of the nanoseconds
+    assert_eq!(Timestamp::new(1, 1_000_000_000), Timestamp::new(2, 0));
+    assert_eq!(
+        Timestamp::new(1, 2_999_999_999),
+        Timestamp::new(3, 999_999_999)
+    );
+    assert_eq!(
+        Timestamp::new(MAX_SECONDS, 1_000_000_000),
+        Timestamp::new(0, 0)
+    );
+    assert_eq!(Timestamp::new(1 << 48, 5), Timestamp::new(0, 5));
+    let mut received = Timestamp::new(10, 0);
+    received.nanosecondsField = Int::new(1_500_000_000);
+    assert_eq!(received.normalized(), Timestamp::new(11, 500_000_000));
+
+    // Second boundaries
+    let before = Timestamp::new(10, 999_999_999);
+    let after = Timestamp::new(11, 0);
+    assert_eq!(after - before, ns(1.0));
+    assert_eq!(before - after, ns(-1.0));
+    assert_eq!(before + ns(1.0), after);
+    assert_eq!(after - ns(1.0), before);
+    assert_eq!(after + ns(-1_000_000_001.0), Timestamp::new(9, 999_999_999));
+    assert_eq!(before + ns(0.75), before);
+    assert_eq!(before + ns(1.75), after);
+    assert!(before < after);
+    assert!(Timestamp::new(10, 1) > Timestamp::new(9, 999_999_999));
+
+    // 48 bit wraparound takes the shorter way
+    let last = Timestamp::new(MAX_SECONDS, 999_999_999);
+    let first = Timestamp::new(0, 0);
+    assert_eq!(last + ns(1.0), first);
+    assert_eq!(first - ns(1.0), last);
+    assert_eq!(first - last, ns(1.0));
+    assert_eq!(last - first, ns(-1.0));
+    assert_eq!(
+        Timestamp::new(2, 0) - Timestamp::new(MAX_SECONDS - 1, 0),
+        ns(4e9)
+    );
+    // Differences beyond the range of TimeInterval saturate
+    assert_eq!(
+        Timestamp::new(1 << 40, 0) - first,
+        TimeInterval::new(i64::MAX)
+    );
+    assert_eq!(
+        first - Timestamp::new(1 << 40, 0),
+        TimeInterval::new(i64::MIN)
+    );
+
+    // Conversions
+    let duration = Duration::new(1_700_000_000, 123_456_789);
+    let timestamp = Timestamp::from(duration);
+    assert_eq!(timestamp, Timestamp::new(1_700_000_000, 123_456_789));
+    assert_eq!(Duration::from(timestamp), duration);
+    assert_eq!(
+        Timestamp::from(SystemTime::UNIX_EPOCH + duration),
+        timestamp
+    );
+    assert_eq!(
+        Timestamp::from(SystemTime::UNIX_EPOCH - Duration::from_secs(1)),
+        first
+    );
+    assert_eq!(
+        Timestamp::from(TimeSpec::nanoseconds(1_700_000_000_123_456_789)),
+        timestamp
+    );
+    assert_eq!(
+        Timestamp::from(TimeVal::microseconds(1_700_000_000_123_456)),
+        Timestamp::new(1_700_000_000, 123_456_000)
+    );
+    assert_eq!(Timestamp::from(TimeSpec::nanoseconds(-1)), first);
+    assert_eq!(Timestamp::from(TimeVal::microseconds(-1)), first);
+
+    // PTP is ahead of UTC by currentUtcOffset
+    let utc = timestamp.to_utc(37);
+    assert_eq!(utc, Timestamp::new(1_699_999_963, 123_456_789));
+    assert_eq!(Timestamp::from_utc(utc, 37), timestamp);
+    assert_eq!(
+        Timestamp::new(10, 0).to_utc(37),
+        Timestamp::new(MAX_SECONDS - 26, 0)
+    );
+}
+
+#[test]
+pub fn test_variable_length_types() {
+    use crate::protocol::parser::{
+        parse_management_data, parse_port_address, parse_ptp_text, parse_tlv,
+    };
+    use crate::protocol::types::derived::{FaultRecord, PTPText, PortAddress, Timestamp};
+    use crate::protocol::types::enums::{values, Enumeration};
+    use crate::protocol::types::message::tlv::{
+        Fault_Log, ManagementData, Management_Error_Status, User_Description,
+    };
+    use crate::protocol::types::message::Tlv;
+    use crate::protocol::types::primitive::int::BitSerialize;
+    use bitstream_io::{BigEndian, BitWriter};
+
+    let address = PortAddress::from("192.0.2.1".parse::<std::net::IpAddr>().unwrap());
+    assert_eq!(address.length(), 8);
+    assert_eq!(address.to_string(), "192.0.2.1");
+    let mut writer = BitWriter::endian(Vec::new(), BigEndian);
+    address.clone().bit_serialize(&mut writer).unwrap();
+    let bytes = writer.into_writer();
+    assert_eq!(bytes, [0, 1, 0, 4, 192, 0, 2, 1]);
+    assert_eq!(parse_port_address(&bytes).unwrap(), (&[][..], address));
+
+    // Truncated on a character boundary
+    let text = PTPText::new(&"é".repeat(200));
+    assert_eq!(text.length(), 255);
+    assert_eq!(text.text(), "é".repeat(127));
+    assert_eq!(
+        parse_ptp_text(&[2, b'h', b'i', 0]).unwrap().1.to_string(),
+        "hi"
+    );
+
+    let description = ManagementData::User_Description(User_Description::new("gm;lab"));
+    assert_eq!(description.length(), 8);
+    let record = FaultRecord::new(
+        Timestamp::new(1, 2),
+        values::FaultSeverity::WARNING,
+        "offset",
+        "1000",
+        "too large",
+    );
+    assert_eq!(record.length(), 2 + 10 + 1 + 7 + 5 + 10);
+
+    // Octets beyond the fields of a record are skipped
+    let mut writer = BitWriter::endian(Vec::new(), BigEndian);
+    record.clone().bit_serialize(&mut writer).unwrap();
+    let short = writer.into_writer();
+    let mut long = short.clone();
+    long[1] += 2;
+    long.extend_from_slice(&[0xAA, 0xBB]);
+    let bytes = [&[0, 2][..], &long, &short].concat();
+    match parse_management_data(&bytes, Enumeration::Enum(values::ManagementId::FAULT_LOG)) {
+        Ok((_, ManagementData::Fault_Log(log))) => {
+            assert_eq!(log.faultRecords[0].faultRecordLength.into_inner(), 33 + 2);
+            assert_eq!(
+                log.faultRecords[0].faultDescription.to_string(),
+                "too large"
+            );
+            assert_eq!(log.faultRecords[1], record);
+        }
+        parsed => panic!("Unexpected {:?}", parsed),
+    }
+
+    let log = ManagementData::Fault_Log(Fault_Log::new(vec![record]));
+    assert_eq!(log.length(), 38);
+    for (id, data) in [
+        (values::ManagementId::USER_DESCRIPTION, description),
+        (values::ManagementId::FAULT_LOG, log),
+    ] {
+        let mut writer = BitWriter::endian(Vec::new(), BigEndian);
+        data.clone().bit_serialize(&mut writer).unwrap();
+        let bytes = writer.into_writer();
+        assert_eq!(bytes.len(), data.length() as usize);
+        let parsed = parse_management_data(&bytes, Enumeration::Enum(id)).unwrap();
+        assert_eq!(parsed, (&[][..], data));
+    }
+
+    // displayData keeps its padding so that a forwarded error keeps its length
+    let error = Tlv::Management_Error_Status(
+        Management_Error_Status::new(
+            values::ManagementErrorId::NOT_SUPPORTED,
+            Enumeration::Enum(values::ManagementId::PRIORITY1),
+        )
+        .with_display_data("busy"),
+    );
+    let mut writer = BitWriter::endian(Vec::new(), BigEndian);
+    error.clone().bit_serialize(&mut writer).unwrap();
+    let bytes = writer.into_writer();
+    assert_eq!(bytes.len(), 4 + 8 + 5 + 1);
+    assert_eq!(parse_tlv(&bytes).unwrap(), (&[][..], error));
+    // Text running past the end of the TLV is an error
+    let mut bad = bytes.clone();
+    bad[12] = 6;
+    assert!(parse_tlv(&bad).is_err());
+}
+
+#[test]
+pub fn test_derive_bit_serialize() {
+    use crate::protocol::types::derived::PTPText;
+    use crate::protocol::types::primitive::int::{BitSerialize, Int};
+    use crate::protocol::types::primitive::{Octet, UInteger16, UInteger8};
+    use bitstream_io::{BigEndian, BitWriter};
+
+    #[derive(BitSerialize)]
+    struct Tuple(UInteger16, [Octet; 3]);
+
+    #[derive(BitSerialize)]
+    #[allow(non_snake_case)]
+    struct Fields {
+        count: UInteger8,
+        #[bit_serialize(len = "count")]
+        values: Vec<UInteger16>,
+        #[bit_serialize(skip)]
+        _cache: u64,
+        text: Option<PTPText>,
+    }
+
+    fn serialize<T: BitSerialize<Vec<u8>, BigEndian>>(value: T) -> std::io::Result<Vec<u8>> {
+        let mut writer = BitWriter::endian(Vec::new(), BigEndian);
+        value.bit_serialize(&mut writer)?;
+        Ok(writer.into_writer())
+    }
+
+    assert_eq!(
+        serialize(Tuple(Int::new(0x0102), [Int::new(3); 3])).unwrap(),
+        [1, 2, 3, 3, 3]
+    );
+    let octets: [Octet; 2] = [Int::new(1), Int::new(2)];
+    assert_eq!(serialize(&octets[..]).unwrap(), [1, 2]);
+    let fields = Fields {
+        count: Int::new(2),
+        values: vec![Int::new(1), Int::new(2)],
+        _cache: 7,
+        text: Some(PTPText::new("a")),
+    };
+    assert_eq!(serialize(fields).unwrap(), [2, 0, 1, 0, 2, 1, b'a']);
+    let fields = Fields {
+        count: Int::new(3),
+        values: vec![Int::new(1)],
+        _cache: 0,
+        text: None,
+    };
+    assert_eq!(
+        serialize(fields).unwrap_err().kind(),
+        std::io::ErrorKind::InvalidData
+    );
+}
+
+#[test]
+pub fn test_bit_size() {
+    use crate::management::{self, all_ports, Request};
+    use crate::protocol::types::datasets::{DefaultDS, PortDS};
+    use crate::protocol::types::derived::PortIdentity;
+    use crate::protocol::types::message::{body, Header};
+    use crate::protocol::types::primitive::int::{BitSize, FixedBitSize, Int};
+
+    assert_eq!(Header::BIT_SIZE, 34 * 8);
+    assert_eq!(body::Announce::BIT_SIZE, 30 * 8);
+    assert_eq!(DefaultDS::BIT_SIZE, 20 * 8);
+    assert_eq!(PortDS::BIT_SIZE, 26 * 8);
+
+    let source = PortIdentity {
+        clockIdentity: [Int::new(1); 8],
+        portNumber: Int::new(1),
+    };
+    let request: Request = "SET USER_DESCRIPTION lab".parse().unwrap();
+    let mut message = management::management(
+        source,
+        all_ports(),
+        0,
+        1,
+        1,
+        request.action,
+        vec![request.tlv()],
+    );
+    assert_eq!(message.header.messageLength.into_inner(), 48 + 4 + 2 + 4);
+    assert_eq!(message.bit_size(), message.clone().to_bytes().len() * 8);
+
+    message.header.messageLength = Int::new(0);
+    let message = message.finalize();
+    assert_eq!(message.header.messageLength.into_inner(), 58);
+}
+
+#[test]
+pub fn test_checked_int() {
+    use crate::protocol::parser::parse_ptp_header;
+    use crate::protocol::types::enums::{values, Enumeration};
+    use crate::protocol::types::primitive::{Nibble, UInteger16, UInteger48};
+
+    assert_eq!(Nibble::checked_new(15).map(Nibble::into_inner), Some(15));
+    assert_eq!(Nibble::checked_new(16), None);
+    assert_eq!(Nibble::wrapping_new(0x1F).into_inner(), 0xF);
+    assert_eq!(
+        UInteger48::saturating_new(u64::MAX).into_inner(),
+        (1 << 48) - 1
+    );
+    assert_eq!(UInteger48::wrapping_new(1 << 48).into_inner(), 0);
+
+    let max = UInteger16::from(u16::MAX);
+    assert_eq!(max.checked_add(UInteger16::from(1u8)), None);
+    assert_eq!(max.wrapping_add(UInteger16::from(1u8)).into_inner(), 0);
+    assert_eq!(
+        UInteger16::from(0u8).wrapping_sub(UInteger16::from(1u8)),
+        max
+    );
+    assert_eq!(Nibble::new(15).wrapping_add(Nibble::new(2)).into_inner(), 1);
+    assert_eq!(Nibble::new(0).checked_sub(Nibble::new(1)), None);
+
+    // transportSpecific 0xA, Delay_Req, reserved 0xF, version 2
+    let mut header = vec![0xA1, 0xF2, 0, 44];
+    header.resize(34, 0);
+    let (_, header) = parse_ptp_header(&header).unwrap();
+    assert_eq!(header.transportSpecific.into_inner(), 0xA);
+    assert_eq!(
+        header.messageType,
+        Enumeration::Enum(values::MessageType::Delay_Req)
+    );
+    assert_eq!(header._reserved1.into_inner(), 0xF);
+    assert_eq!(header.versionPTP.into_inner(), 2);
+}
+
+#[test]
+pub fn test_int_ops() {
+    use crate::protocol::types::derived::PortIdentity;
+    use crate::protocol::types::primitive::int::Int;
+    use crate::protocol::types::primitive::{Integer8, Nibble, Octet, UInteger16};
+    use std::collections::HashMap;
+
+    let a = UInteger16::from(300u16);
+    let b = UInteger16::from(20u8);
+    assert_eq!((a - b).into_inner(), 280);
+    assert_eq!((a * b).into_inner(), 6000);
+    assert_eq!(a.max(b), a);
+    assert_eq!(UInteger16::default().into_inner(), 0);
+    assert_eq!((-Integer8::from(5i8)).into_inner(), -5);
+    assert_eq!(Integer8::from(i8::MIN).checked_neg(), None);
+    assert_eq!((Octet::from(0b0110u8) << 2).into_inner(), 0b1_1000);
+    assert_eq!((Octet::from(0b0110u8) >> 1u8).into_inner(), 0b11);
+    assert_eq!((Nibble::new(0b0101) << 1).into_inner(), 0b1010);
+    assert!(std::panic::catch_unwind(|| Nibble::new(0b1000) << 1).is_err());
+    assert_eq!(
+        (Octet::from(0b1100u8) & Octet::from(0b1010u8)).into_inner(),
+        0b1000
+    );
+    assert_eq!(
+        (Octet::from(0b1100u8) | Octet::from(0b1010u8)).into_inner(),
+        0b1110
+    );
+
+    let mut masters = HashMap::new();
+    let identity = |port: u16| PortIdentity {
+        clockIdentity: [Int::new(1); 8],
+        portNumber: Int::new(port),
+    };
+    masters.insert(identity(1), "first");
+    masters.insert(identity(2), "second");
+    masters.insert(identity(1), "again");
+    assert_eq!(masters.len(), 2);
+    assert_eq!(masters[&identity(1)], "again");
+    assert!(identity(1) < identity(2));
+}
+
+#[test]
+pub fn test_signed_narrow_int() {
+    use crate::protocol::parser::{be_i48, be_integer48, parse_signed_nibbles};
+    use crate::protocol::types::primitive::int::constrain::{i4, i48, Constrain, Signed, Unsigned};
+    use crate::protocol::types::primitive::int::{BitDeserialize, BitSerialize, FixedBitSize, Int};
+    use crate::protocol::types::primitive::{Integer4, Integer48};
+    use bitstream_io::{BigEndian, BitReader, BitWriter};
+
+    assert_eq!((i4::max(), i4::min()), (7, -8));
+    assert_eq!(i48::min(), -(1 << 47));
+    assert_eq!(Integer4::checked_new(8), None);
+    assert_eq!(Integer4::wrapping_new(0xF).into_inner(), -1);
+    assert_eq!(
+        Integer4::new(7).wrapping_add(Integer4::new(1)).into_inner(),
+        -8
+    );
+    assert_eq!((Unsigned::<12>::max(), Signed::<12>::min()), (4095, -2048));
+    assert_eq!(Int::<Signed<12>>::BIT_SIZE, 12);
+
+    let mut writer = BitWriter::endian(Vec::new(), BigEndian);
+    Integer4::new(-2).bit_serialize(&mut writer).unwrap();
+    Integer4::new(3).bit_serialize(&mut writer).unwrap();
+    Integer48::new(-2).bit_serialize(&mut writer).unwrap();
+    Int::<Signed<12>>::new(-1)
+        .bit_serialize(&mut writer)
+        .unwrap();
+    Int::<Unsigned<4>>::new(5)
+        .bit_serialize(&mut writer)
+        .unwrap();
+    let bytes = writer.into_writer();
+    assert_eq!(
+        bytes,
+        [0xE3, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFE, 0xFF, 0xF5]
+    );
+
+    let (_, value) = be_i48::<()>(&bytes[1..7]).unwrap();
+    assert_eq!(value, -2);
+    let (_, value) = be_i48::<()>(&[0x7F, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]).unwrap();
+    assert_eq!(value, i48::max());
+    let (_, value) = be_integer48::<()>(&[0x80, 0, 0, 0, 0, 0]).unwrap();
+    assert_eq!(value, Integer48::new(i48::min()));
+    let (_, nibbles) = parse_signed_nibbles(&bytes).unwrap();
+    assert_eq!(nibbles, (Integer4::new(-2), Integer4::new(3)));
+
+    // Reading the fields back sign extends them
+    let mut reader = BitReader::endian(&bytes[..], BigEndian);
+    assert_eq!(
+        Integer4::bit_deserialize(&mut reader).unwrap(),
+        Integer4::new(-2)
+    );
+    assert_eq!(
+        Integer4::bit_deserialize(&mut reader).unwrap(),
+        Integer4::new(3)
+    );
+    assert_eq!(
+        Integer48::bit_deserialize(&mut reader).unwrap(),
+        Integer48::new(-2)
+    );
+    assert_eq!(
+        Int::<Signed<12>>::bit_deserialize(&mut reader).unwrap(),
+        Int::new(-1)
+    );
+    assert_eq!(
+        Int::<Unsigned<4>>::bit_deserialize(&mut reader).unwrap(),
+        Int::new(5)
+    );
+    assert!(Integer4::bit_deserialize(&mut reader).is_err());
+}
@@ -0,0 +1,82 @@
+//! Data sets of IEEE 1588-2008 clause 8, laid out as in the management TLVs
+//! of clause 15.5.3 so they can be serialized directly into responses
+
+use crate::protocol::types::derived::{ClockIdentity, ClockQuality, PortIdentity, TimeInterval};
+use crate::protocol::types::enums::{DelayMechanism, PortState, TimeSource};
+use crate::protocol::types::primitive::int::{BitSerialize, FixedBitSize};
+use crate::protocol::types::primitive::*;
+
+/// defaultDS flags: twoStepFlag
+pub const DEFAULT_TSC: u8 = 0x01;
+/// defaultDS flags: slaveOnly
+pub const DEFAULT_SO: u8 = 0x02;
+/// parentDS flags: parentStats
+pub const PARENT_PS: u8 = 0x01;
+/// timePropertiesDS flags, same bits as the second octet of flagField
+pub const TIME_LI_61: u8 = 0x01;
+pub const TIME_LI_59: u8 = 0x02;
+pub const TIME_UTCV: u8 = 0x04;
+pub const TIME_PTP: u8 = 0x08;
+pub const TIME_TTRA: u8 = 0x10;
+pub const TIME_FTRA: u8 = 0x20;
+
+#[derive(Debug, PartialEq, Clone, Copy, BitSerialize, FixedBitSize, Serialize)]
+pub struct DefaultDS {
+    pub flags: Octet,
+    #[serde(skip)]
+    pub _reserved1: Octet,
+    pub numberPorts: UInteger16,
+    pub priority1: UInteger8,
+    pub clockQuality: ClockQuality,
+    pub priority2: UInteger8,
+    #[serde(serialize_with = "crate::protocol::types::derived::serialize_clock_identity")]
+    pub clockIdentity: ClockIdentity,
+    pub domainNumber: UInteger8,
+    #[serde(skip)]
+    pub _reserved2: Octet,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, BitSerialize, FixedBitSize, Serialize)]
+pub struct CurrentDS {
+    pub stepsRemoved: UInteger16,
+    pub offsetFromMaster: TimeInterval,
+    pub meanPathDelay: TimeInterval,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, BitSerialize, FixedBitSize, Serialize)]
+pub struct ParentDS {
+    pub parentPortIdentity: PortIdentity,
+    pub flags: Octet,
+    #[serde(skip)]
+    pub _reserved: Octet,
+    pub observedParentOffsetScaledLogVariance: UInteger16,
+    pub observedParentClockPhaseChangeRate: Integer32,
+    pub grandmasterPriority1: UInteger8,
+    pub grandmasterClockQuality: ClockQuality,
+    pub grandmasterPriority2: UInteger8,
+    #[serde(serialize_with = "crate::protocol::types::derived::serialize_clock_identity")]
+    pub grandmasterIdentity: ClockIdentity,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, BitSerialize, FixedBitSize, Serialize)]
+pub struct TimePropertiesDS {
+    pub currentUtcOffset: Integer16,
+    pub flags: Octet,
+    pub timeSource: TimeSource,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, BitSerialize, FixedBitSize, Serialize)]
+pub struct PortDS {
+    pub portIdentity: PortIdentity,
+    pub portState: PortState,
+    pub logMinDelayReqInterval: Integer8,
+    pub peerMeanPathDelay: TimeInterval,
+    pub logAnnounceInterval: Integer8,
+    pub announceReceiptTimeout: UInteger8,
+    pub logSyncInterval: Integer8,
+    pub delayMechanism: DelayMechanism,
+    pub logMinPdelayReqInterval: Integer8,
+    #[serde(skip)]
+    pub _reserved: Nibble,
+    pub versionNumber: UInteger4,
+}
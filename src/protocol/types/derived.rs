@@ -1,39 +1,549 @@
-use crate::protocol::types::enums::ClockAcurracy;
-use crate::protocol::types::primitive::int::BitSerialize;
+use crate::protocol::types::enums::{
+    values, ClockAcurracy, Enumeration, FaultSeverity, NetworkProtocol, TlvType,
+};
+use crate::protocol::types::primitive::int::{BitSerialize, BitSize, FixedBitSize, Int};
 use crate::protocol::types::primitive::*;
 
-#[allow(dead_code)]
-pub struct TimeInterval; //TODO
+use nix::sys::time::{TimeSpec, TimeVal};
 
-#[derive(Debug, PartialEq, Clone, Copy, BitSerialize)]
+use std::convert::TryFrom;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::ops::{Add, AddAssign, Neg, Sub, SubAssign};
+use std::time::{Duration, SystemTime};
+
+/// Time interval in 2^-16 ns units, so that 2.5 ns is 0x28000
+#[derive(Debug, PartialEq, PartialOrd, Clone, Copy, BitSerialize, FixedBitSize)]
+pub struct TimeInterval {
+    pub scaledNanoseconds: Integer64,
+}
+
+impl TimeInterval {
+    /// Interval of `scaled` 2^-16 ns units
+    pub fn new(scaled: i64) -> Self {
+        TimeInterval {
+            scaledNanoseconds: Int::new(scaled),
+        }
+    }
+
+    pub fn scaled(self) -> i64 {
+        self.scaledNanoseconds.into_inner()
+    }
+
+    /// Saturates at the bounds of the 64 bit representation
+    pub fn from_nanoseconds(nanoseconds: f64) -> Self {
+        TimeInterval::new((nanoseconds * 65536.0) as i64)
+    }
+
+    pub fn nanoseconds(self) -> f64 {
+        self.scaled() as f64 / 65536.0
+    }
+
+    pub fn saturating_add(self, other: TimeInterval) -> Self {
+        TimeInterval::new(self.scaled().saturating_add(other.scaled()))
+    }
+
+    pub fn saturating_sub(self, other: TimeInterval) -> Self {
+        TimeInterval::new(self.scaled().saturating_sub(other.scaled()))
+    }
+}
+
+/// Saturates at about 39 hours, the longest interval the representation holds
+impl From<Duration> for TimeInterval {
+    fn from(duration: Duration) -> Self {
+        TimeInterval::new(
+            i64::try_from(duration.as_nanos())
+                .unwrap_or(i64::MAX)
+                .saturating_mul(1 << 16),
+        )
+    }
+}
+
+/// Truncated to whole nanoseconds, negative intervals can't be converted
+impl TryFrom<TimeInterval> for Duration {
+    type Error = &'static str;
+
+    fn try_from(interval: TimeInterval) -> Result<Self, Self::Error> {
+        u64::try_from(interval.scaled() >> 16)
+            .map(Duration::from_nanos)
+            .map_err(|_| "Negative time interval")
+    }
+}
+
+impl Add for TimeInterval {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        self.saturating_add(other)
+    }
+}
+
+impl Sub for TimeInterval {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        self.saturating_sub(other)
+    }
+}
+
+impl AddAssign for TimeInterval {
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other;
+    }
+}
+
+impl SubAssign for TimeInterval {
+    fn sub_assign(&mut self, other: Self) {
+        *self = *self - other;
+    }
+}
+
+impl Neg for TimeInterval {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        TimeInterval::new(self.scaled().saturating_neg())
+    }
+}
+
+/// Nanoseconds including the fraction, e.g. `1.5`
+impl fmt::Display for TimeInterval {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.nanoseconds().fmt(f)
+    }
+}
+
+/// Serialized as the plain scaled value, like the other integer fields
+impl serde::Serialize for TimeInterval {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_i64(self.scaled())
+    }
+}
+
+/// Ordered by seconds then nanoseconds, which matches time order once normalized
+#[derive(Debug, PartialEq, PartialOrd, Clone, Copy, BitSerialize, FixedBitSize, Serialize)]
 pub struct Timestamp {
     pub secondsField: UInteger48,
     pub nanosecondsField: UInteger32,
 }
 
+const NANOS_PER_SEC: i128 = 1_000_000_000;
+/// Nanoseconds after which secondsField wraps around
+const TIMESTAMP_PERIOD: i128 = (1 << 48) * NANOS_PER_SEC;
+
+/// Interval from `other` to `self`, taking the shorter way around the 48 bit
+/// wraparound, saturated as a TimeInterval
+impl Sub for Timestamp {
+    type Output = TimeInterval;
+
+    fn sub(self, other: Timestamp) -> TimeInterval {
+        let diff = (self.nanos() - other.nanos()).rem_euclid(TIMESTAMP_PERIOD);
+        let diff = if diff >= TIMESTAMP_PERIOD / 2 {
+            diff - TIMESTAMP_PERIOD
+        } else {
+            diff
+        };
+        TimeInterval::new(i64::try_from(diff << 16).unwrap_or(if diff < 0 {
+            i64::MIN
+        } else {
+            i64::MAX
+        }))
+    }
+}
+
+/// Fractions of nanoseconds are rounded down
+impl Add<TimeInterval> for Timestamp {
+    type Output = Timestamp;
+
+    fn add(self, interval: TimeInterval) -> Timestamp {
+        Timestamp::from_nanos(self.nanos() + i128::from(interval.scaled() >> 16))
+    }
+}
+
+impl Sub<TimeInterval> for Timestamp {
+    type Output = Timestamp;
+
+    fn sub(self, interval: TimeInterval) -> Timestamp {
+        self + -interval
+    }
+}
+
+/// Time since the Unix epoch, which is also the PTP epoch
+impl From<Duration> for Timestamp {
+    fn from(duration: Duration) -> Self {
+        Timestamp::from_nanos(duration.as_nanos() as i128)
+    }
+}
+
+impl From<Timestamp> for Duration {
+    fn from(timestamp: Timestamp) -> Self {
+        Duration::new(
+            timestamp.secondsField.into_inner(),
+            timestamp.nanosecondsField.into_inner(),
+        )
+    }
+}
+
+/// Times before the epoch are clamped to it, as in the other conversions
+/// from system times
+impl From<SystemTime> for Timestamp {
+    fn from(time: SystemTime) -> Self {
+        time.duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .into()
+    }
+}
+
+/// Times before the epoch are clamped to it
+impl From<TimeSpec> for Timestamp {
+    fn from(time: TimeSpec) -> Self {
+        Timestamp::from_nanos(
+            (i128::from(time.tv_sec()) * NANOS_PER_SEC + i128::from(time.tv_nsec())).max(0),
+        )
+    }
+}
+
+/// Times before the epoch are clamped to it
+impl From<TimeVal> for Timestamp {
+    fn from(time: TimeVal) -> Self {
+        Timestamp::from_nanos(
+            (i128::from(time.tv_sec()) * NANOS_PER_SEC + i128::from(time.tv_usec()) * 1000).max(0),
+        )
+    }
+}
+
+/// `seconds.nanoseconds`, or RFC 3339 with the alternate flag `{:#}`
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if f.alternate() {
+            f.write_str(&self.rfc3339())
+        } else {
+            write!(f, "{}.{:09}", self.secondsField, self.nanosecondsField)
+        }
+    }
+}
+
+impl Timestamp {
+    /// Carries nanoseconds beyond a second into the seconds, which wrap around at 2^48
+    pub fn new(seconds: u64, nanoseconds: u64) -> Self {
+        Timestamp::from_nanos(i128::from(seconds) * NANOS_PER_SEC + i128::from(nanoseconds))
+    }
+
+    /// Timestamp `nanos` after the epoch, modulo the 48 bit seconds range
+    fn from_nanos(nanos: i128) -> Self {
+        let nanos = nanos.rem_euclid(TIMESTAMP_PERIOD);
+        Timestamp {
+            secondsField: Int::new((nanos / NANOS_PER_SEC) as u64),
+            nanosecondsField: Int::new((nanos % NANOS_PER_SEC) as u32),
+        }
+    }
+
+    fn nanos(self) -> i128 {
+        i128::from(self.secondsField.into_inner()) * NANOS_PER_SEC
+            + i128::from(self.nanosecondsField.into_inner())
+    }
+
+    /// Carries a nanosecondsField of a second or more, as received from a
+    /// misbehaving peer, into secondsField
+    pub fn normalized(self) -> Self {
+        Timestamp::from_nanos(self.nanos())
+    }
+
+    /// Converts a PTP (TAI) timestamp to UTC given currentUtcOffset in seconds
+    pub fn to_utc(self, current_utc_offset: i16) -> Self {
+        Timestamp::from_nanos(self.nanos() - i128::from(current_utc_offset) * NANOS_PER_SEC)
+    }
+
+    /// Converts a UTC timestamp to the PTP (TAI) timescale given currentUtcOffset in seconds
+    pub fn from_utc(utc: Timestamp, current_utc_offset: i16) -> Self {
+        Timestamp::from_nanos(utc.nanos() + i128::from(current_utc_offset) * NANOS_PER_SEC)
+    }
+
+    /// Renders the timestamp as e.g. `2023-11-14T22:13:20.000000005Z`. The PTP
+    /// timescale is TAI, `currentUtcOffset` has to be subtracted beforehand for UTC.
+    pub fn rfc3339(&self) -> String {
+        let seconds = self.secondsField.into_inner();
+        let (days, time) = (seconds / 86400, seconds % 86400);
+        // Civil date from days since 1970-01-01, after Howard Hinnant's algorithm
+        let z = days + 719_468;
+        let (era, doe) = (z / 146_097, z % 146_097);
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + u64::from(month <= 2);
+        format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:09}Z",
+            year,
+            month,
+            day,
+            time / 3600,
+            time / 60 % 60,
+            time % 60,
+            self.nanosecondsField
+        )
+    }
+}
+
 pub type ClockIdentity = [Octet; 8];
 
-#[derive(Debug, PartialEq, Clone, Copy, BitSerialize)]
+/// Builds an EUI-64 clock identity from an EUI-48 MAC address by inserting
+/// FF:FE between the OUI and the extension identifier
+pub fn clock_identity_from_mac(mac: [u8; 6]) -> ClockIdentity {
+    let [a, b, c, d, e, f] = mac;
+    [a, b, c, 0xFF, 0xFE, d, e, f].map(Int::new)
+}
+
+/// Formats a clock identity as e.g. `b827eb.fffe.92b1a6`
+pub fn format_clock_identity(identity: &ClockIdentity) -> String {
+    let hex: Vec<_> = identity
+        .iter()
+        .map(|octet| format!("{:02x}", octet.into_inner()))
+        .collect();
+    format!(
+        "{}.{}.{}",
+        hex[..3].concat(),
+        hex[3..5].concat(),
+        hex[5..].concat()
+    )
+}
+
+/// Serializes a clock identity as a string in the notation of `format_clock_identity`
+pub fn serialize_clock_identity<S: serde::Serializer>(
+    identity: &ClockIdentity,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&format_clock_identity(identity))
+}
+
+/// Parses a clock identity in the notation of `format_clock_identity`
+pub fn parse_clock_identity(s: &str) -> Option<ClockIdentity> {
+    let groups: Vec<_> = s.split('.').collect();
+    if groups
+        .iter()
+        .map(|group| group.len())
+        .ne([6, 4, 6].iter().copied())
+    {
+        return None;
+    }
+    let hex = groups.concat();
+    let mut identity = [Int::new(0); 8];
+    for (idx, octet) in identity.iter_mut().enumerate() {
+        let digits = hex.get(idx * 2..idx * 2 + 2)?;
+        if !digits.bytes().all(|digit| digit.is_ascii_hexdigit()) {
+            return None;
+        }
+        *octet = Int::new(u8::from_str_radix(digits, 16).ok()?);
+    }
+    Some(identity)
+}
+
+#[derive(
+    Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Clone, Copy, BitSerialize, FixedBitSize, Serialize,
+)]
 pub struct PortIdentity {
+    #[serde(serialize_with = "crate::protocol::types::derived::serialize_clock_identity")]
     pub clockIdentity: ClockIdentity,
     pub portNumber: UInteger16,
 }
 
-#[allow(dead_code)]
-pub struct PortAddress; //TODO
+/// `clockIdentity-portNumber`, e.g. `b827eb.fffe.92b1a6-1`
+impl fmt::Display for PortIdentity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}-{}",
+            format_clock_identity(&self.clockIdentity),
+            self.portNumber
+        )
+    }
+}
+
+/// Protocol address of a port (IEEE 1588-2008, 5.3.6)
+#[derive(Debug, PartialEq, Clone, BitSerialize, BitSize, Serialize)]
+pub struct PortAddress {
+    pub networkProtocol: NetworkProtocol,
+    pub addressLength: UInteger16,
+    #[bit_serialize(len = "addressLength")]
+    pub addressField: Vec<Octet>,
+}
+
+impl PortAddress {
+    pub fn new(networkProtocol: values::NetworkProtocol, address: &[u8]) -> Self {
+        PortAddress {
+            networkProtocol: Enumeration::Enum(networkProtocol),
+            addressLength: Int::new(address.len() as u16),
+            addressField: address.iter().map(|&octet| Int::new(octet)).collect(),
+        }
+    }
+
+    /// Length on the wire
+    pub fn length(&self) -> u16 {
+        4 + self.addressLength.into_inner()
+    }
+}
+
+impl From<IpAddr> for PortAddress {
+    fn from(address: IpAddr) -> Self {
+        match address {
+            IpAddr::V4(address) => {
+                PortAddress::new(values::NetworkProtocol::UDP_IPV4, &address.octets())
+            }
+            IpAddr::V6(address) => {
+                PortAddress::new(values::NetworkProtocol::UDP_IPV6, &address.octets())
+            }
+        }
+    }
+}
 
-#[derive(Debug, PartialEq, Clone, Copy, BitSerialize)]
+/// IP addresses in their usual notation, other addresses as colon separated octets
+impl fmt::Display for PortAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let octets: Vec<u8> = self
+            .addressField
+            .iter()
+            .map(|octet| octet.into_inner())
+            .collect();
+        match (self.networkProtocol, octets.len()) {
+            (Enumeration::Enum(values::NetworkProtocol::UDP_IPV4), 4) => {
+                Ipv4Addr::from([octets[0], octets[1], octets[2], octets[3]]).fmt(f)
+            }
+            (Enumeration::Enum(values::NetworkProtocol::UDP_IPV6), 16) => {
+                let mut address = [0; 16];
+                address.copy_from_slice(&octets);
+                Ipv6Addr::from(address).fmt(f)
+            }
+            _ => {
+                let hex: Vec<_> = octets
+                    .iter()
+                    .map(|octet| format!("{:02x}", octet))
+                    .collect();
+                f.write_str(&hex.join(":"))
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, BitSerialize, FixedBitSize, Serialize)]
 pub struct ClockQuality {
     pub clockClass: UInteger8,
     pub clockAccuracy: ClockAcurracy,
     pub offsetScaledLogVariance: UInteger16,
 }
 
-#[allow(dead_code)]
-pub struct TLV; //TODO
+impl fmt::Display for ClockQuality {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "class {} accuracy {} variance {:#06x}",
+            self.clockClass, self.clockAccuracy, self.offsetScaledLogVariance
+        )
+    }
+}
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, PartialEq, Clone, BitSerialize, BitSize, Serialize)]
+pub struct TLV {
+    pub tlvType: TlvType,
+    pub lengthField: UInteger16,
+    #[bit_serialize(len = "lengthField")]
+    pub valueField: Vec<Octet>,
+}
+
+/// UTF-8 text of up to 255 octets (IEEE 1588-2008, 5.3.9)
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, PartialEq, Clone, BitSerialize, BitSize)]
+pub struct PTPText {
+    pub lengthField: UInteger8,
+    #[bit_serialize(len = "lengthField")]
+    pub textField: Vec<Octet>,
+}
+
+impl PTPText {
+    /// Truncates `text` to the last whole character within 255 octets
+    pub fn new(text: &str) -> Self {
+        let mut end = text.len().min(255);
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        PTPText {
+            lengthField: Int::new(end as u8),
+            textField: text.as_bytes()[..end]
+                .iter()
+                .map(|&octet| Int::new(octet))
+                .collect(),
+        }
+    }
+
+    /// Length on the wire
+    pub fn length(&self) -> u16 {
+        1 + u16::from(self.lengthField.into_inner())
+    }
+
+    /// The text, with invalid UTF-8 replaced
+    pub fn text(&self) -> String {
+        let octets: Vec<u8> = self
+            .textField
+            .iter()
+            .map(|octet| octet.into_inner())
+            .collect();
+        String::from_utf8_lossy(&octets).into_owned()
+    }
+}
+
+impl fmt::Display for PTPText {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.text())
+    }
+}
+
+/// Serialized as a string
+impl serde::Serialize for PTPText {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.text())
+    }
+}
 
-#[allow(dead_code)]
-pub struct PTPText; //TODO
+/// Entry of the fault log (IEEE 1588-2008, 15.5.3.4.7)
+#[derive(Debug, PartialEq, Clone, BitSerialize, BitSize, Serialize)]
+pub struct FaultRecord {
+    /// Octets following this field
+    pub faultRecordLength: UInteger16,
+    pub faultTime: Timestamp,
+    pub severityCode: FaultSeverity,
+    pub faultName: PTPText,
+    pub faultValue: PTPText,
+    pub faultDescription: PTPText,
+}
+
+impl FaultRecord {
+    pub fn new(
+        faultTime: Timestamp,
+        severityCode: values::FaultSeverity,
+        faultName: &str,
+        faultValue: &str,
+        faultDescription: &str,
+    ) -> Self {
+        let (faultName, faultValue, faultDescription) = (
+            PTPText::new(faultName),
+            PTPText::new(faultValue),
+            PTPText::new(faultDescription),
+        );
+        FaultRecord {
+            faultRecordLength: Int::new(
+                11 + faultName.length() + faultValue.length() + faultDescription.length(),
+            ),
+            faultTime,
+            severityCode: Enumeration::Enum(severityCode),
+            faultName,
+            faultValue,
+            faultDescription,
+        }
+    }
 
-#[allow(dead_code)]
-pub struct FaultRecord; //TODO
+    /// Length on the wire
+    pub fn length(&self) -> u16 {
+        2 + self.faultRecordLength.into_inner()
+    }
+}
@@ -1,13 +1,17 @@
-use crate::protocol::types::primitive::int::BitSerialize;
+use crate::protocol::types::primitive::int::constrain::Constrain;
+use crate::protocol::types::primitive::int::{BitSerialize, FixedBitSize, Int};
 use crate::protocol::types::primitive::{Enumeration16, Enumeration4, Enumeration8};
 use bitstream_io::{BitWriter, Endianness};
 
+use std::cmp::Ordering;
 use std::convert::TryFrom;
+use std::fmt;
 
 #[allow(non_camel_case_types)]
+#[allow(clippy::upper_case_acronyms)]
 pub mod values;
 
-#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+#[derive(Debug, Eq, PartialEq, Hash, Clone, Copy)]
 pub enum Enumeration<E, T> {
     Enum(T),
     Unknown(E),
@@ -26,7 +30,39 @@ where
     }
 }
 
-impl<U: Into<E> + PartialOrd + Copy, E, T: TryFrom<E>> From<U> for Enumeration<E, T> {
+impl<E: FixedBitSize, T> FixedBitSize for Enumeration<E, T> {
+    const BIT_SIZE: usize = E::BIT_SIZE;
+}
+
+/// Variant name, or e.g. `Unknown(0x1f)` for values without a variant
+impl<E: fmt::LowerHex, T: fmt::Display> fmt::Display for Enumeration<E, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Enumeration::Enum(t) => t.fmt(f),
+            Enumeration::Unknown(e) => write!(f, "Unknown({:#x})", e),
+        }
+    }
+}
+
+/// Serialized as the variant name, or the raw value when unknown
+impl<E: serde::Serialize, T: serde::Serialize> serde::Serialize for Enumeration<E, T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Enumeration::Enum(t) => t.serialize(serializer),
+            Enumeration::Unknown(e) => e.serialize(serializer),
+        }
+    }
+}
+
+/// Raw values an Enumeration is built from. Enumeration doesn't implement it,
+/// which keeps `From<U>` apart from `From<T> for T`.
+pub trait Raw {}
+
+impl Raw for u8 {}
+impl Raw for u16 {}
+impl<C: Constrain> Raw for Int<C> {}
+
+impl<U: Into<E> + Raw + Copy, E, T: TryFrom<E>> From<U> for Enumeration<E, T> {
     fn from(val: U) -> Enumeration<E, T> {
         match T::try_from(val.into()) {
             Ok(en) => Enumeration::Enum(en),
@@ -35,6 +71,29 @@ impl<U: Into<E> + PartialOrd + Copy, E, T: TryFrom<E>> From<U> for Enumeration<E
     }
 }
 
+/// The raw value 0, even if it has a variant
+impl<E: Default + Raw + Copy, T: TryFrom<E>> Default for Enumeration<E, T> {
+    fn default() -> Self {
+        Self::from(E::default())
+    }
+}
+
+/// Ordered by raw value, a known variant before an unknown one of the same value
+impl<E: Ord + Copy + From<Self>, T: Eq + Copy> Ord for Enumeration<E, T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let unknown = |val: &Self| matches!(val, Enumeration::Unknown(_));
+        E::from(*self)
+            .cmp(&E::from(*other))
+            .then_with(|| unknown(self).cmp(&unknown(other)))
+    }
+}
+
+impl<E: Ord + Copy + From<Self>, T: Eq + Copy> PartialOrd for Enumeration<E, T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 macro_rules! impl_from_enumeration {
     ($E:ty) => {
         impl<T: Into<$E>> From<Enumeration<$E, T>> for $E {
@@ -53,11 +112,7 @@ impl_from_enumeration!(Enumeration8);
 impl_from_enumeration!(Enumeration16);
 
 impl<
-        E: Copy
-            + PartialOrd
-            + std::ops::Add
-            + From<<E as std::ops::Add>::Output>
-            + From<Enumeration<E, T>>,
+        E: Copy + Raw + std::ops::Add + From<<E as std::ops::Add>::Output> + From<Enumeration<E, T>>,
         T: TryFrom<E> + Into<E> + Copy,
     > std::ops::AddAssign for Enumeration<E, T>
 {
@@ -68,7 +123,7 @@ impl<
 
 impl<
         E: Copy
-            + PartialOrd
+            + Raw
             + std::ops::Shl<U>
             + From<<E as std::ops::Shl<U>>::Output>
             + From<Enumeration<E, T>>,
@@ -85,7 +140,7 @@ impl<
 
 impl<
         E: Copy
-            + PartialOrd
+            + Raw
             + std::ops::Shr<U>
             + From<<E as std::ops::Shr<U>>::Output>
             + From<Enumeration<E, T>>,
@@ -103,3 +158,12 @@ impl<
 pub type MessageType = Enumeration<Enumeration4, values::MessageType>;
 pub type ClockAcurracy = Enumeration<Enumeration8, values::ClockAcurracy>;
 pub type TimeSource = Enumeration<Enumeration8, values::TimeSource>;
+pub type TlvType = Enumeration<Enumeration16, values::TlvType>;
+pub type ActionField = Enumeration<Enumeration4, values::ActionField>;
+pub type ManagementId = Enumeration<Enumeration16, values::ManagementId>;
+pub type ManagementErrorId = Enumeration<Enumeration16, values::ManagementErrorId>;
+pub type PortState = Enumeration<Enumeration8, values::PortState>;
+pub type DelayMechanism = Enumeration<Enumeration8, values::DelayMechanism>;
+pub type NetworkProtocol = Enumeration<Enumeration16, values::NetworkProtocol>;
+pub type FaultSeverity = Enumeration<Enumeration8, values::FaultSeverity>;
+pub type ControlField = Enumeration<Enumeration8, values::ControlField>;
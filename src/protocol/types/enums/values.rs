@@ -1,5 +1,5 @@
 use crate::protocol::types::primitive::int::constrain::{u4, Constrain};
-use crate::protocol::types::primitive::int::{BitSerialize, BitSerializeProto, Int};
+use crate::protocol::types::primitive::int::{BitSerialize, BitSerializeProto, FixedBitSize, Int};
 use bitstream_io::{BitWriter, Endianness};
 use std::convert::TryFrom;
 use std::io;
@@ -17,6 +17,13 @@ enum_Int!(MessageType<u4> {
     Management = 0xD
 });
 
+impl MessageType {
+    /// Event messages are timestamped and sent to the event port
+    pub fn is_event(self) -> bool {
+        (self as u8) < 0x8
+    }
+}
+
 enum_Int!(TimeSource<u8> {
     ATOMIC_CLOCK = 0x10,
     GPS = 0x20,
@@ -29,5 +36,245 @@ enum_Int!(TimeSource<u8> {
 });
 
 enum_Int!(ClockAcurracy<u8> {
-    Unknown = 0xFF
+    WITHIN_1_PS = 0x17,
+    WITHIN_2_5_PS = 0x18,
+    WITHIN_10_PS = 0x19,
+    WITHIN_25_PS = 0x1A,
+    WITHIN_100_PS = 0x1B,
+    WITHIN_250_PS = 0x1C,
+    WITHIN_1_NS = 0x1D,
+    WITHIN_2_5_NS = 0x1E,
+    WITHIN_10_NS = 0x1F,
+    WITHIN_25_NS = 0x20,
+    WITHIN_100_NS = 0x21,
+    WITHIN_250_NS = 0x22,
+    WITHIN_1_US = 0x23,
+    WITHIN_2_5_US = 0x24,
+    WITHIN_10_US = 0x25,
+    WITHIN_25_US = 0x26,
+    WITHIN_100_US = 0x27,
+    WITHIN_250_US = 0x28,
+    WITHIN_1_MS = 0x29,
+    WITHIN_2_5_MS = 0x2A,
+    WITHIN_10_MS = 0x2B,
+    WITHIN_25_MS = 0x2C,
+    WITHIN_100_MS = 0x2D,
+    WITHIN_250_MS = 0x2E,
+    WITHIN_1_S = 0x2F,
+    WITHIN_10_S = 0x30,
+    GREATER_10_S = 0x31,
+    UNKNOWN = 0xFE
 });
+
+/// Upper bounds in nanoseconds of the accuracies (IEEE 1588-2019, Table 5)
+const CLOCK_ACCURACY_BOUNDS: [(ClockAcurracy, f64); 26] = [
+    (ClockAcurracy::WITHIN_1_PS, 0.001),
+    (ClockAcurracy::WITHIN_2_5_PS, 0.0025),
+    (ClockAcurracy::WITHIN_10_PS, 0.01),
+    (ClockAcurracy::WITHIN_25_PS, 0.025),
+    (ClockAcurracy::WITHIN_100_PS, 0.1),
+    (ClockAcurracy::WITHIN_250_PS, 0.25),
+    (ClockAcurracy::WITHIN_1_NS, 1.0),
+    (ClockAcurracy::WITHIN_2_5_NS, 2.5),
+    (ClockAcurracy::WITHIN_10_NS, 10.0),
+    (ClockAcurracy::WITHIN_25_NS, 25.0),
+    (ClockAcurracy::WITHIN_100_NS, 100.0),
+    (ClockAcurracy::WITHIN_250_NS, 250.0),
+    (ClockAcurracy::WITHIN_1_US, 1e3),
+    (ClockAcurracy::WITHIN_2_5_US, 2.5e3),
+    (ClockAcurracy::WITHIN_10_US, 10e3),
+    (ClockAcurracy::WITHIN_25_US, 25e3),
+    (ClockAcurracy::WITHIN_100_US, 100e3),
+    (ClockAcurracy::WITHIN_250_US, 250e3),
+    (ClockAcurracy::WITHIN_1_MS, 1e6),
+    (ClockAcurracy::WITHIN_2_5_MS, 2.5e6),
+    (ClockAcurracy::WITHIN_10_MS, 10e6),
+    (ClockAcurracy::WITHIN_25_MS, 25e6),
+    (ClockAcurracy::WITHIN_100_MS, 100e6),
+    (ClockAcurracy::WITHIN_250_MS, 250e6),
+    (ClockAcurracy::WITHIN_1_S, 1e9),
+    (ClockAcurracy::WITHIN_10_S, 10e9),
+];
+
+impl ClockAcurracy {
+    /// Upper bound of the time error in nanoseconds, infinite beyond 10 s and
+    /// `None` when unknown
+    pub fn nanoseconds(self) -> Option<f64> {
+        match self {
+            ClockAcurracy::GREATER_10_S => Some(f64::INFINITY),
+            ClockAcurracy::UNKNOWN => None,
+            accuracy => CLOCK_ACCURACY_BOUNDS
+                .iter()
+                .find(|(bound_accuracy, _)| *bound_accuracy == accuracy)
+                .map(|&(_, bound)| bound),
+        }
+    }
+
+    /// Best accuracy whose bound includes a time error of `nanoseconds`
+    pub fn from_nanoseconds(nanoseconds: f64) -> Self {
+        CLOCK_ACCURACY_BOUNDS
+            .iter()
+            .find(|&&(_, bound)| nanoseconds.abs() <= bound)
+            .map_or(ClockAcurracy::GREATER_10_S, |&(accuracy, _)| accuracy)
+    }
+}
+
+/// Ordered by value, lower values being better in the BMCA (IEEE 1588-2008, 9.3.4)
+impl PartialOrd for ClockAcurracy {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ClockAcurracy {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (*self as u8).cmp(&(*other as u8))
+    }
+}
+
+enum_Int!(TlvType<u16> {
+    MANAGEMENT = 0x0001,
+    MANAGEMENT_ERROR_STATUS = 0x0002,
+    ORGANIZATION_EXTENSION = 0x0003,
+    REQUEST_UNICAST_TRANSMISSION = 0x0004,
+    GRANT_UNICAST_TRANSMISSION = 0x0005,
+    CANCEL_UNICAST_TRANSMISSION = 0x0006,
+    ACKNOWLEDGE_CANCEL_UNICAST_TRANSMISSION = 0x0007,
+    PATH_TRACE = 0x0008,
+    ALTERNATE_TIME_OFFSET_INDICATOR = 0x0009,
+    AUTHENTICATION = 0x2000,
+    AUTHENTICATION_CHALLENGE = 0x2001,
+    SECURITY_ASSOCIATION_UPDATE = 0x2002,
+    CUM_FREQ_SCALE_FACTOR_OFFSET = 0x2003
+});
+
+enum_Int!(ActionField<u4> {
+    GET = 0x0,
+    SET = 0x1,
+    RESPONSE = 0x2,
+    COMMAND = 0x3,
+    ACKNOWLEDGE = 0x4
+});
+
+enum_Int!(ManagementId<u16> {
+    NULL_MANAGEMENT = 0x0000,
+    CLOCK_DESCRIPTION = 0x0001,
+    USER_DESCRIPTION = 0x0002,
+    SAVE_IN_NON_VOLATILE_STORAGE = 0x0003,
+    RESET_NON_VOLATILE_STORAGE = 0x0004,
+    INITIALIZE = 0x0005,
+    FAULT_LOG = 0x0006,
+    FAULT_LOG_RESET = 0x0007,
+    DEFAULT_DATA_SET = 0x2000,
+    CURRENT_DATA_SET = 0x2001,
+    PARENT_DATA_SET = 0x2002,
+    TIME_PROPERTIES_DATA_SET = 0x2003,
+    PORT_DATA_SET = 0x2004,
+    PRIORITY1 = 0x2005,
+    PRIORITY2 = 0x2006,
+    DOMAIN = 0x2007,
+    SLAVE_ONLY = 0x2008,
+    LOG_ANNOUNCE_INTERVAL = 0x2009,
+    ANNOUNCE_RECEIPT_TIMEOUT = 0x200A,
+    LOG_SYNC_INTERVAL = 0x200B,
+    VERSION_NUMBER = 0x200C,
+    ENABLE_PORT = 0x200D,
+    DISABLE_PORT = 0x200E,
+    TIME = 0x200F,
+    CLOCK_ACCURACY = 0x2010,
+    UTC_PROPERTIES = 0x2011,
+    TRACEABILITY_PROPERTIES = 0x2012,
+    TIMESCALE_PROPERTIES = 0x2013,
+    UNICAST_NEGOTIATION_ENABLE = 0x2014,
+    PATH_TRACE_LIST = 0x2015,
+    PATH_TRACE_ENABLE = 0x2016,
+    GRANDMASTER_CLUSTER_TABLE = 0x2017,
+    UNICAST_MASTER_TABLE = 0x2018,
+    UNICAST_MASTER_MAX_TABLE_SIZE = 0x2019,
+    ACCEPTABLE_MASTER_TABLE = 0x201A,
+    ACCEPTABLE_MASTER_TABLE_ENABLED = 0x201B,
+    ACCEPTABLE_MASTER_MAX_TABLE_SIZE = 0x201C,
+    ALTERNATE_MASTER = 0x201D,
+    ALTERNATE_TIME_OFFSET_ENABLE = 0x201E,
+    ALTERNATE_TIME_OFFSET_NAME = 0x201F,
+    ALTERNATE_TIME_OFFSET_MAX_KEY = 0x2020,
+    ALTERNATE_TIME_OFFSET_PROPERTIES = 0x2021,
+    TRANSPARENT_CLOCK_DEFAULT_DATA_SET = 0x4000,
+    TRANSPARENT_CLOCK_PORT_DATA_SET = 0x4001,
+    PRIMARY_DOMAIN = 0x4002,
+    DELAY_MECHANISM = 0x6000,
+    LOG_MIN_PDELAY_REQ_INTERVAL = 0x6001
+});
+
+enum_Int!(ManagementErrorId<u16> {
+    RESPONSE_TOO_BIG = 0x0001,
+    NO_SUCH_ID = 0x0002,
+    WRONG_LENGTH = 0x0003,
+    WRONG_VALUE = 0x0004,
+    NOT_SETABLE = 0x0005,
+    NOT_SUPPORTED = 0x0006,
+    GENERAL_ERROR = 0xFFFE
+});
+
+enum_Int!(PortState<u8> {
+    INITIALIZING = 0x01,
+    FAULTY = 0x02,
+    DISABLED = 0x03,
+    LISTENING = 0x04,
+    PRE_MASTER = 0x05,
+    MASTER = 0x06,
+    PASSIVE = 0x07,
+    UNCALIBRATED = 0x08,
+    SLAVE = 0x09
+});
+
+enum_Int!(DelayMechanism<u8> {
+    E2E = 0x01,
+    P2P = 0x02,
+    COMMON_P2P = 0x03,
+    SPECIAL = 0x04,
+    DISABLED = 0xFE
+});
+
+enum_Int!(NetworkProtocol<u16> {
+    UDP_IPV4 = 0x0001,
+    UDP_IPV6 = 0x0002,
+    IEEE_802_3 = 0x0003,
+    DEVICENET = 0x0004,
+    CONTROLNET = 0x0005,
+    PROFINET = 0x0006
+});
+
+enum_Int!(FaultSeverity<u8> {
+    EMERGENCY = 0x00,
+    ALERT = 0x01,
+    CRITICAL = 0x02,
+    ERROR = 0x03,
+    WARNING = 0x04,
+    NOTICE = 0x05,
+    INFORMATIONAL = 0x06,
+    DEBUG = 0x07
+});
+
+enum_Int!(ControlField<u8> {
+    SYNC = 0x00,
+    DELAY_REQ = 0x01,
+    FOLLOW_UP = 0x02,
+    DELAY_RESP = 0x03,
+    MANAGEMENT = 0x04,
+    ALL_OTHERS = 0x05
+});
+
+impl From<MessageType> for ControlField {
+    /// controlField of a message type, kept for compatibility with PTPv1 hardware
+    fn from(message_type: MessageType) -> ControlField {
+        match message_type {
+            MessageType::Sync => ControlField::SYNC,
+            MessageType::Delay_Req => ControlField::DELAY_REQ,
+            MessageType::Follow_Up => ControlField::FOLLOW_UP,
+            MessageType::Delay_Resp => ControlField::DELAY_RESP,
+            MessageType::Management => ControlField::MANAGEMENT,
+            _ => ControlField::ALL_OTHERS,
+        }
+    }
+}
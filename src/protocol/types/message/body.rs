@@ -1,38 +1,60 @@
 use crate::protocol::types::derived::*;
-use crate::protocol::types::enums::TimeSource;
-use crate::protocol::types::primitive::int::BitSerialize;
+use crate::protocol::types::enums::{ActionField, TimeSource};
+use crate::protocol::types::message::Tlv;
+use crate::protocol::types::primitive::int::{BitSerialize, BitSize, FixedBitSize};
 use crate::protocol::types::primitive::*;
 
-#[derive(Debug, PartialEq, BitSerialize, Clone, Copy)]
+#[derive(Debug, PartialEq, BitSerialize, FixedBitSize, Clone, Copy, Serialize)]
 pub struct Announce {
     pub originTimestamp: Timestamp,
     pub currentUtcOffset: Integer16,
+    #[serde(skip)]
     pub _reserved: Octet,
     pub grandmasterPriority1: UInteger8,
     pub grandmasterClockQuality: ClockQuality,
     pub grandmasterPriority2: UInteger8,
+    #[serde(serialize_with = "crate::protocol::types::derived::serialize_clock_identity")]
     pub grandmasterIdentity: ClockIdentity,
     pub stepsRemoved: UInteger16,
     pub timeSource: TimeSource,
 }
 
-#[derive(Debug, PartialEq, BitSerialize, Clone, Copy)]
+#[derive(Debug, PartialEq, BitSerialize, FixedBitSize, Clone, Copy, Serialize)]
 pub struct Sync {
     pub originTimestamp: Timestamp,
 }
 
-#[derive(Debug, PartialEq, BitSerialize, Clone, Copy)]
+#[derive(Debug, PartialEq, BitSerialize, FixedBitSize, Clone, Copy, Serialize)]
 pub struct Delay_Req {
     pub originTimestamp: Timestamp,
 }
 
-#[derive(Debug, PartialEq, BitSerialize, Clone, Copy)]
+#[derive(Debug, PartialEq, BitSerialize, FixedBitSize, Clone, Copy, Serialize)]
 pub struct Follow_Up {
     pub preciseOriginTimestamp: Timestamp,
 }
 
-#[derive(Debug, PartialEq, BitSerialize, Clone, Copy)]
+#[derive(Debug, PartialEq, BitSerialize, FixedBitSize, Clone, Copy, Serialize)]
 pub struct Delay_Resp {
     pub receiveTimestamp: Timestamp,
     pub requestingPortIdentity: PortIdentity,
 }
+
+#[derive(Debug, PartialEq, BitSerialize, BitSize, Clone, Serialize)]
+pub struct Signaling {
+    pub targetPortIdentity: PortIdentity,
+    pub tlvs: Vec<Tlv>,
+}
+
+#[derive(Debug, PartialEq, BitSerialize, BitSize, Clone, Serialize)]
+pub struct Management {
+    pub targetPortIdentity: PortIdentity,
+    pub startingBoundaryHops: UInteger8,
+    pub boundaryHops: UInteger8,
+    #[serde(skip)]
+    pub _reserved1: Nibble,
+    pub actionField: ActionField,
+    #[serde(skip)]
+    pub _reserved2: Octet,
+    pub tlvs: Vec<Tlv>,
+}
@@ -1,45 +1,235 @@
 use crate::protocol::types::derived::*;
-use crate::protocol::types::enums::MessageType;
-use crate::protocol::types::primitive::int::BitSerialize;
+use crate::protocol::types::enums::{values, ControlField, Enumeration, MessageType};
+use crate::protocol::types::primitive::int::{BitSerialize, BitSize, FixedBitSize, Int};
 use crate::protocol::types::primitive::*;
+use bitstream_io::{BigEndian, BitWriter};
+
+use std::fmt;
 
 #[allow(non_camel_case_types)]
 #[allow(non_snake_case)]
 pub mod body;
+#[allow(non_camel_case_types)]
+#[allow(non_snake_case)]
+pub mod tlv;
 
 #[allow(non_snake_case)]
-#[derive(Debug, PartialEq, BitSerialize, Clone, Copy)]
+#[derive(Debug, PartialEq, BitSerialize, FixedBitSize, Clone, Copy, Serialize)]
 pub struct Header {
     pub transportSpecific: Nibble,
     pub messageType: MessageType,
+    #[serde(skip)]
     pub _reserved1: Nibble,
     pub versionPTP: UInteger4,
     pub messageLength: UInteger16,
     pub domainNumber: UInteger8,
+    #[serde(skip)]
     pub _reserved2: Octet,
     pub flagField: [Octet; 2],
-    pub correctionField: Integer64,
+    pub correctionField: TimeInterval,
+    #[serde(skip)]
     pub _reserved3: [Octet; 4],
     pub sourcePortIdentity: PortIdentity,
     pub sequenceId: UInteger16,
-    pub controlField: UInteger8,
+    pub controlField: ControlField,
     pub logMessageInterval: Integer8,
 }
 
+#[allow(non_snake_case)]
+impl Header {
+    pub fn new(
+        messageType: values::MessageType,
+        messageLength: u16,
+        domainNumber: u8,
+        sourcePortIdentity: PortIdentity,
+        sequenceId: u16,
+        logMessageInterval: i8,
+    ) -> Header {
+        Header {
+            transportSpecific: Int::new(0),
+            messageType: Enumeration::Enum(messageType),
+            _reserved1: Int::new(0),
+            versionPTP: Int::new(2),
+            messageLength: Int::new(messageLength),
+            domainNumber: Int::new(domainNumber),
+            _reserved2: Int::new(0),
+            flagField: [Int::new(0), Int::new(0)],
+            correctionField: TimeInterval::new(0),
+            _reserved3: [Int::new(0); 4],
+            sourcePortIdentity,
+            sequenceId: Int::new(sequenceId),
+            controlField: Enumeration::Enum(messageType.into()),
+            logMessageInterval: Int::new(logMessageInterval),
+        }
+    }
+}
+
 #[allow(non_camel_case_types)]
-#[derive(Debug, PartialEq, BitSerialize, Clone, Copy)]
+#[derive(Debug, PartialEq, BitSerialize, BitSize, Clone, Serialize)]
 pub enum Body {
     Announce(body::Announce),
     Sync(body::Sync),
     Delay_Req(body::Delay_Req),
     Follow_Up(body::Follow_Up),
     Delay_Resp(body::Delay_Resp),
+    Signaling(body::Signaling),
+    Management(body::Management),
     Empty,
 }
 
+#[allow(non_camel_case_types)]
+#[derive(Debug, PartialEq, BitSerialize, BitSize, Clone, Serialize)]
+pub enum Tlv {
+    Request_Unicast_Transmission(tlv::Request_Unicast_Transmission),
+    Grant_Unicast_Transmission(tlv::Grant_Unicast_Transmission),
+    Cancel_Unicast_Transmission(tlv::Cancel_Unicast_Transmission),
+    Acknowledge_Cancel_Unicast_Transmission(tlv::Acknowledge_Cancel_Unicast_Transmission),
+    Management(tlv::Management),
+    Management_Error_Status(tlv::Management_Error_Status),
+    Unknown(TLV),
+}
+
+impl Tlv {
+    /// Length of the whole TLV on the wire, including type and length fields
+    pub fn length(&self) -> u16 {
+        4 + match self {
+            Tlv::Request_Unicast_Transmission(tlv) => tlv.lengthField,
+            Tlv::Grant_Unicast_Transmission(tlv) => tlv.lengthField,
+            Tlv::Cancel_Unicast_Transmission(tlv) => tlv.lengthField,
+            Tlv::Acknowledge_Cancel_Unicast_Transmission(tlv) => tlv.lengthField,
+            Tlv::Management(tlv) => tlv.lengthField,
+            Tlv::Management_Error_Status(tlv) => tlv.lengthField,
+            Tlv::Unknown(tlv) => tlv.lengthField,
+        }
+        .into_inner()
+    }
+}
+
+/// TLV type followed by the message type or management ID it refers to
+impl fmt::Display for Tlv {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Tlv::Request_Unicast_Transmission(tlv) => {
+                write!(f, "{} {}", tlv.tlvType, tlv.messageType)
+            }
+            Tlv::Grant_Unicast_Transmission(tlv) => {
+                write!(f, "{} {}", tlv.tlvType, tlv.messageType)
+            }
+            Tlv::Cancel_Unicast_Transmission(tlv) => {
+                write!(f, "{} {}", tlv.tlvType, tlv.messageType)
+            }
+            Tlv::Acknowledge_Cancel_Unicast_Transmission(tlv) => {
+                write!(f, "{} {}", tlv.tlvType, tlv.messageType)
+            }
+            Tlv::Management(tlv) => write!(f, "{} {}", tlv.tlvType, tlv.managementId),
+            Tlv::Management_Error_Status(tlv) => write!(
+                f,
+                "{} {} {}",
+                tlv.tlvType, tlv.managementErrorId, tlv.managementId
+            ),
+            Tlv::Unknown(tlv) => write!(f, "{}", tlv.tlvType),
+        }
+    }
+}
+
 #[allow(non_snake_case)]
-#[derive(Debug, PartialEq, BitSerialize, Clone, Copy)]
+#[derive(Debug, PartialEq, BitSerialize, BitSize, Clone, Serialize)]
 pub struct Message {
     pub header: Header,
     pub body: Body,
 }
+
+impl Message {
+    /// Length in octets on the wire
+    pub fn length(&self) -> usize {
+        self.bit_size() / 8
+    }
+
+    /// Sets `messageLength` to the length the message will be serialized with
+    pub fn finalize(mut self) -> Self {
+        self.header.messageLength = Int::new(self.length() as u16);
+        self
+    }
+
+    /// Serializes the message into its network representation
+    pub fn to_bytes(self) -> Vec<u8> {
+        let mut writer = BitWriter::endian(Vec::new(), BigEndian);
+        self.bit_serialize(&mut writer)
+            .expect("Writing to a Vec can't fail");
+        writer.into_writer()
+    }
+}
+
+/// One line summary in the spirit of tcpdump, e.g.
+/// `Sync seq 7 domain 0 from b827eb.fffe.92b1a6-1, origin 1700000000.000000005`
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let header = &self.header;
+        write!(
+            f,
+            "{} seq {} domain {} from {}",
+            header.messageType, header.sequenceId, header.domainNumber, header.sourcePortIdentity
+        )?;
+        if header.correctionField != TimeInterval::new(0) {
+            write!(f, " correction {} ns", header.correctionField)?;
+        }
+        let tlvs = |tlvs: &[Tlv]| {
+            tlvs.iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        match &self.body {
+            Body::Announce(announce) => write!(
+                f,
+                ", gm {} priority1 {} {} priority2 {} stepsRemoved {} timeSource {}",
+                format_clock_identity(&announce.grandmasterIdentity),
+                announce.grandmasterPriority1,
+                announce.grandmasterClockQuality,
+                announce.grandmasterPriority2,
+                announce.stepsRemoved,
+                announce.timeSource
+            ),
+            Body::Sync(sync) => write!(f, ", origin {}", sync.originTimestamp),
+            Body::Delay_Req(delay_req) => write!(f, ", origin {}", delay_req.originTimestamp),
+            Body::Follow_Up(follow_up) => {
+                write!(f, ", precise origin {}", follow_up.preciseOriginTimestamp)
+            }
+            Body::Delay_Resp(delay_resp) => write!(
+                f,
+                ", receive {} for {}",
+                delay_resp.receiveTimestamp, delay_resp.requestingPortIdentity
+            ),
+            Body::Signaling(signaling) => write!(
+                f,
+                ", target {} [{}]",
+                signaling.targetPortIdentity,
+                tlvs(&signaling.tlvs)
+            ),
+            Body::Management(management) => write!(
+                f,
+                ", {} target {} [{}]",
+                management.actionField,
+                management.targetPortIdentity,
+                tlvs(&management.tlvs)
+            ),
+            Body::Empty => Ok(()),
+        }
+    }
+}
+
+/// Received message with its source address and receive timestamp, serialized
+/// as one JSON object per message for log ingestion
+#[derive(Debug, Serialize)]
+pub struct Received<'a> {
+    pub source: String,
+    /// Receive timestamp since the Unix epoch
+    pub timestamp: std::time::Duration,
+    pub message: &'a Message,
+}
+
+impl Received<'_> {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("Message serialization can't fail")
+    }
+}
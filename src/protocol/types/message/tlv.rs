@@ -0,0 +1,274 @@
+use crate::protocol::types::datasets::*;
+use crate::protocol::types::derived::{FaultRecord, PTPText};
+use crate::protocol::types::enums::{
+    values, Enumeration, ManagementErrorId, ManagementId, MessageType, TlvType,
+};
+use crate::protocol::types::primitive::int::{BitSerialize, BitSize, FixedBitSize, Int};
+use crate::protocol::types::primitive::*;
+
+pub const GRANT_RENEWAL_INVITED: u8 = 0x01;
+
+#[derive(Debug, PartialEq, BitSerialize, FixedBitSize, Clone, Copy, Serialize)]
+pub struct Request_Unicast_Transmission {
+    pub tlvType: TlvType,
+    pub lengthField: UInteger16,
+    pub messageType: MessageType,
+    #[serde(skip)]
+    pub _reserved: Nibble,
+    pub logInterMessagePeriod: Integer8,
+    pub durationField: UInteger32,
+}
+
+impl Request_Unicast_Transmission {
+    pub fn new(messageType: MessageType, logInterMessagePeriod: i8, durationField: u32) -> Self {
+        Request_Unicast_Transmission {
+            tlvType: Enumeration::Enum(values::TlvType::REQUEST_UNICAST_TRANSMISSION),
+            lengthField: Int::new(6),
+            messageType,
+            _reserved: Int::new(0),
+            logInterMessagePeriod: Int::new(logInterMessagePeriod),
+            durationField: Int::new(durationField),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, BitSerialize, FixedBitSize, Clone, Copy, Serialize)]
+pub struct Grant_Unicast_Transmission {
+    pub tlvType: TlvType,
+    pub lengthField: UInteger16,
+    pub messageType: MessageType,
+    #[serde(skip)]
+    pub _reserved1: Nibble,
+    pub logInterMessagePeriod: Integer8,
+    pub durationField: UInteger32,
+    #[serde(skip)]
+    pub _reserved2: Octet,
+    pub flags: Octet,
+}
+
+impl Grant_Unicast_Transmission {
+    pub fn new(
+        messageType: MessageType,
+        logInterMessagePeriod: i8,
+        durationField: u32,
+        renewalInvited: bool,
+    ) -> Self {
+        Grant_Unicast_Transmission {
+            tlvType: Enumeration::Enum(values::TlvType::GRANT_UNICAST_TRANSMISSION),
+            lengthField: Int::new(8),
+            messageType,
+            _reserved1: Int::new(0),
+            logInterMessagePeriod: Int::new(logInterMessagePeriod),
+            durationField: Int::new(durationField),
+            _reserved2: Int::new(0),
+            flags: Int::new(if renewalInvited {
+                GRANT_RENEWAL_INVITED
+            } else {
+                0
+            }),
+        }
+    }
+
+    pub fn renewal_invited(&self) -> bool {
+        self.flags.into_inner() & GRANT_RENEWAL_INVITED != 0
+    }
+}
+
+#[derive(Debug, PartialEq, BitSerialize, FixedBitSize, Clone, Copy, Serialize)]
+pub struct Cancel_Unicast_Transmission {
+    pub tlvType: TlvType,
+    pub lengthField: UInteger16,
+    pub messageType: MessageType,
+    #[serde(skip)]
+    pub _reserved1: Nibble,
+    #[serde(skip)]
+    pub _reserved2: Octet,
+}
+
+impl Cancel_Unicast_Transmission {
+    pub fn new(messageType: MessageType) -> Self {
+        Cancel_Unicast_Transmission {
+            tlvType: Enumeration::Enum(values::TlvType::CANCEL_UNICAST_TRANSMISSION),
+            lengthField: Int::new(2),
+            messageType,
+            _reserved1: Int::new(0),
+            _reserved2: Int::new(0),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, BitSerialize, FixedBitSize, Clone, Copy, Serialize)]
+pub struct Acknowledge_Cancel_Unicast_Transmission {
+    pub tlvType: TlvType,
+    pub lengthField: UInteger16,
+    pub messageType: MessageType,
+    #[serde(skip)]
+    pub _reserved1: Nibble,
+    #[serde(skip)]
+    pub _reserved2: Octet,
+}
+
+impl Acknowledge_Cancel_Unicast_Transmission {
+    pub fn new(messageType: MessageType) -> Self {
+        Acknowledge_Cancel_Unicast_Transmission {
+            tlvType: Enumeration::Enum(values::TlvType::ACKNOWLEDGE_CANCEL_UNICAST_TRANSMISSION),
+            lengthField: Int::new(2),
+            messageType,
+            _reserved1: Int::new(0),
+            _reserved2: Int::new(0),
+        }
+    }
+}
+
+/// Management data consisting of a single field padded to two octets, e.g. PRIORITY1
+#[derive(Debug, PartialEq, BitSerialize, FixedBitSize, Clone, Copy, Serialize)]
+pub struct Value<T> {
+    pub value: T,
+    #[serde(skip)]
+    pub _reserved: Octet,
+}
+
+impl<T> Value<T> {
+    pub fn new(value: T) -> Self {
+        Value {
+            value,
+            _reserved: Int::new(0),
+        }
+    }
+}
+
+/// Padding to the even length of a dataField
+fn padding(length: u16) -> Vec<Octet> {
+    vec![Int::new(0); (length % 2) as usize]
+}
+
+/// USER_DESCRIPTION management data
+#[derive(Debug, PartialEq, BitSerialize, BitSize, Clone, Serialize)]
+pub struct User_Description {
+    pub userDescription: PTPText,
+    #[serde(skip)]
+    pub _pad: Vec<Octet>,
+}
+
+impl User_Description {
+    pub fn new(userDescription: &str) -> Self {
+        let userDescription = PTPText::new(userDescription);
+        User_Description {
+            _pad: padding(userDescription.length()),
+            userDescription,
+        }
+    }
+}
+
+/// FAULT_LOG management data
+#[derive(Debug, PartialEq, BitSerialize, BitSize, Clone, Serialize)]
+pub struct Fault_Log {
+    pub numberOfFaultRecords: UInteger16,
+    #[bit_serialize(len = "numberOfFaultRecords")]
+    pub faultRecords: Vec<FaultRecord>,
+    #[serde(skip)]
+    pub _pad: Vec<Octet>,
+}
+
+impl Fault_Log {
+    pub fn new(faultRecords: Vec<FaultRecord>) -> Self {
+        let length: u16 = faultRecords.iter().map(FaultRecord::length).sum();
+        Fault_Log {
+            numberOfFaultRecords: Int::new(faultRecords.len() as u16),
+            faultRecords,
+            _pad: padding(length),
+        }
+    }
+}
+
+/// dataField of a MANAGEMENT TLV, selected by its managementId
+#[derive(Debug, PartialEq, BitSerialize, BitSize, Clone, Serialize)]
+pub enum ManagementData {
+    Default_Data_Set(DefaultDS),
+    Current_Data_Set(CurrentDS),
+    Parent_Data_Set(ParentDS),
+    Time_Properties_Data_Set(TimePropertiesDS),
+    Port_Data_Set(PortDS),
+    Priority1(Value<UInteger8>),
+    Priority2(Value<UInteger8>),
+    Domain(Value<UInteger8>),
+    /// Flags octet with slaveOnly as the least significant bit
+    Slave_Only(Value<Octet>),
+    Log_Announce_Interval(Value<Integer8>),
+    Announce_Receipt_Timeout(Value<UInteger8>),
+    Log_Sync_Interval(Value<Integer8>),
+    /// versionNumber in the lower nibble
+    Version_Number(Value<Octet>),
+    Delay_Mechanism(Value<Enumeration8>),
+    Log_Min_Pdelay_Req_Interval(Value<Integer8>),
+    User_Description(User_Description),
+    Fault_Log(Fault_Log),
+    /// Empty dataField of GET and COMMAND requests, or data of an unsupported managementId
+    Raw(Vec<Octet>),
+}
+
+impl ManagementData {
+    pub fn empty() -> Self {
+        ManagementData::Raw(Vec::new())
+    }
+
+    /// Length in octets on the wire
+    pub fn length(&self) -> u16 {
+        (self.bit_size() / 8) as u16
+    }
+}
+
+#[derive(Debug, PartialEq, BitSerialize, BitSize, Clone, Serialize)]
+pub struct Management {
+    pub tlvType: TlvType,
+    pub lengthField: UInteger16,
+    pub managementId: ManagementId,
+    pub dataField: ManagementData,
+}
+
+impl Management {
+    pub fn new(managementId: values::ManagementId, dataField: ManagementData) -> Self {
+        Management {
+            tlvType: Enumeration::Enum(values::TlvType::MANAGEMENT),
+            lengthField: Int::new(2 + dataField.length()),
+            managementId: Enumeration::Enum(managementId),
+            dataField,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, BitSerialize, BitSize, Clone, Serialize)]
+pub struct Management_Error_Status {
+    pub tlvType: TlvType,
+    pub lengthField: UInteger16,
+    pub managementErrorId: ManagementErrorId,
+    pub managementId: ManagementId,
+    #[serde(skip)]
+    pub _reserved: [Octet; 4],
+    pub displayData: Option<PTPText>,
+    #[serde(skip)]
+    pub _pad: Vec<Octet>,
+}
+
+impl Management_Error_Status {
+    pub fn new(managementErrorId: values::ManagementErrorId, managementId: ManagementId) -> Self {
+        Management_Error_Status {
+            tlvType: Enumeration::Enum(values::TlvType::MANAGEMENT_ERROR_STATUS),
+            lengthField: Int::new(8),
+            managementErrorId: Enumeration::Enum(managementErrorId),
+            managementId,
+            _reserved: [Int::new(0); 4],
+            displayData: None,
+            _pad: Vec::new(),
+        }
+    }
+
+    /// Adds a human readable description of the error
+    pub fn with_display_data(mut self, displayData: &str) -> Self {
+        let displayData = PTPText::new(displayData);
+        self._pad = padding(displayData.length());
+        self.lengthField = Int::new(8 + displayData.length() + self._pad.len() as u16);
+        self.displayData = Some(displayData);
+        self
+    }
+}
@@ -1,6 +1,8 @@
 #[macro_use]
 pub mod primitive;
 #[allow(non_snake_case)]
+pub mod datasets;
+#[allow(non_snake_case)]
 pub mod derived;
 pub mod enums;
 pub mod message;
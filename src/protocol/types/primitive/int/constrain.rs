@@ -3,6 +3,14 @@ pub trait Constrain {
     const BITS: u32;
     fn max() -> Self::Type;
     fn min() -> Self::Type;
+    /// Reduces `value` modulo 2^BITS into the range of the type
+    fn wrap(value: Self::Type) -> Self::Type;
+    fn checked_add(a: Self::Type, b: Self::Type) -> Option<Self::Type>;
+    fn checked_sub(a: Self::Type, b: Self::Type) -> Option<Self::Type>;
+    fn checked_mul(a: Self::Type, b: Self::Type) -> Option<Self::Type>;
+    fn checked_neg(a: Self::Type) -> Option<Self::Type>;
+    fn wrapping_add(a: Self::Type, b: Self::Type) -> Self::Type;
+    fn wrapping_sub(a: Self::Type, b: Self::Type) -> Self::Type;
 }
 
 macro_rules! impl_constrain {
@@ -14,7 +22,6 @@ macro_rules! impl_constrain {
     ($name:ident, $sign:ident, $bits:expr, $type:ty) => {
         impl_constrain!(@struct $name);
         impl_constrain!(@impl $name, $sign, $bits, $type);
-
     };
     (@struct $name:ident) => {
         #[derive(Debug, PartialEq, PartialOrd, Clone, Copy)]
@@ -22,9 +29,51 @@ macro_rules! impl_constrain {
     };
     (@impl $name:ty, $sign:ident, $bits:expr, $type:ty) => {
         impl Constrain for $name {
-            type Type = $type;
-            impl_constrain!(@maxmin $sign, $type);
-            const BITS: u32 = $bits;
+            impl_constrain!(@body $sign, $bits, $type);
+        }
+    };
+    (@body $sign:ident, $bits:expr, $type:ty) => {
+        type Type = $type;
+        impl_constrain!(@maxmin $sign, $type);
+        impl_constrain!(@wrap $sign, $type);
+        const BITS: u32 = $bits;
+        fn checked_add(a: $type, b: $type) -> Option<$type> {
+            a.checked_add(b).filter(|&sum| {
+                sum <= <Self as Constrain>::max() && sum >= <Self as Constrain>::min()
+            })
+        }
+        fn checked_sub(a: $type, b: $type) -> Option<$type> {
+            a.checked_sub(b).filter(|&difference| {
+                difference <= <Self as Constrain>::max() && difference >= <Self as Constrain>::min()
+            })
+        }
+        fn checked_mul(a: $type, b: $type) -> Option<$type> {
+            a.checked_mul(b).filter(|&product| {
+                product <= <Self as Constrain>::max() && product >= <Self as Constrain>::min()
+            })
+        }
+        fn checked_neg(a: $type) -> Option<$type> {
+            a.checked_neg().filter(|&negated| {
+                negated <= <Self as Constrain>::max() && negated >= <Self as Constrain>::min()
+            })
+        }
+        fn wrapping_add(a: $type, b: $type) -> $type {
+            <Self as Constrain>::wrap(a.wrapping_add(b))
+        }
+        fn wrapping_sub(a: $type, b: $type) -> $type {
+            <Self as Constrain>::wrap(a.wrapping_sub(b))
+        }
+    };
+    (@wrap i, $type:ty) => {
+        fn wrap(value: $type) -> $type {
+            // Sign extension from the highest bit of the type
+            let shift = <$type>::BITS - <Self as Constrain>::BITS;
+            (value << shift) >> shift
+        }
+    };
+    (@wrap u, $type:ty) => {
+        fn wrap(value: $type) -> $type {
+            value & <Self as Constrain>::max()
         }
     };
     (@maxmin i, $type:ty) => {
@@ -47,6 +96,24 @@ macro_rules! impl_constrain {
 impl_constrain!(u1, u, 1, u8);
 impl_constrain!(u4, u, 4, u8);
 impl_constrain!(u48, u, 48, u64);
+impl_constrain!(i4, i, 4, i8);
+impl_constrain!(i48, i, 48, i64);
 
 impl_constrain!(u: u8, u16, u32, u64);
 impl_constrain!(i: i8, i16, i32, i64);
+
+/// Unsigned integer of any width from 1 to 64 bits
+#[derive(Debug, PartialEq, PartialOrd, Clone, Copy)]
+pub struct Unsigned<const BITS: u32>;
+
+/// Signed integer of any width from 1 to 64 bits, in two's complement
+#[derive(Debug, PartialEq, PartialOrd, Clone, Copy)]
+pub struct Signed<const BITS: u32>;
+
+impl<const N: u32> Constrain for Unsigned<N> {
+    impl_constrain!(@body u, { assert!(N >= 1 && N <= 64, "width out of range"); N }, u64);
+}
+
+impl<const N: u32> Constrain for Signed<N> {
+    impl_constrain!(@body i, { assert!(N >= 1 && N <= 64, "width out of range"); N }, i64);
+}
@@ -1,16 +1,22 @@
-use bitstream_io::{BitWriter, Endianness, Numeric};
+use bitstream_io::{BitReader, BitWriter, Endianness, Numeric};
+use serde::{Serialize, Serializer};
+use std::cmp::Ordering;
 use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::io;
-use std::ops::{Add, AddAssign, Shl, Shr};
+use std::ops::{
+    Add, AddAssign, BitAnd, BitAndAssign, BitOr, BitOrAssign, Mul, Neg, Shl, Shr, Sub, SubAssign,
+};
 
 #[allow(non_camel_case_types)]
 pub mod constrain;
 use constrain::Constrain;
 
-#[derive(PartialEq, PartialOrd, Clone, Copy)]
+#[derive(Clone, Copy)]
 pub struct Int<C: Constrain>(C::Type);
 
 impl<C: Constrain> Int<C> {
+    /// Panics when `c` is out of range, see `checked_new` for untrusted values
     pub fn new(c: C::Type) -> Self
     where
         C::Type: PartialOrd,
@@ -20,11 +26,105 @@ impl<C: Constrain> Int<C> {
         } else if c < C::min() {
             panic!("Value underflows!")
         } else {
-            return Int(c);
+            Int(c)
+        }
+    }
+    /// `None` when `c` is out of range
+    pub fn checked_new(c: C::Type) -> Option<Self>
+    where
+        C::Type: PartialOrd,
+    {
+        if c > C::max() || c < C::min() {
+            None
+        } else {
+            Some(Int(c))
+        }
+    }
+    /// Keeps the lowest `BITS` bits of `c`, sign extended for signed types
+    pub fn wrapping_new(c: C::Type) -> Self {
+        Int(C::wrap(c))
+    }
+    /// Clamps `c` to the range of the type
+    pub fn saturating_new(c: C::Type) -> Self
+    where
+        C::Type: PartialOrd,
+    {
+        if c > C::max() {
+            Int(C::max())
+        } else if c < C::min() {
+            Int(C::min())
+        } else {
+            Int(c)
         }
     }
     pub fn into_inner(self) -> C::Type {
-        return self.0;
+        self.0
+    }
+    pub fn checked_add(self, other: Self) -> Option<Self> {
+        C::checked_add(self.0, other.0).map(Int)
+    }
+    pub fn checked_sub(self, other: Self) -> Option<Self> {
+        C::checked_sub(self.0, other.0).map(Int)
+    }
+    pub fn checked_mul(self, other: Self) -> Option<Self> {
+        C::checked_mul(self.0, other.0).map(Int)
+    }
+    pub fn checked_neg(self) -> Option<Self> {
+        C::checked_neg(self.0).map(Int)
+    }
+    pub fn wrapping_add(self, other: Self) -> Self {
+        Int(C::wrapping_add(self.0, other.0))
+    }
+    pub fn wrapping_sub(self, other: Self) -> Self {
+        Int(C::wrapping_sub(self.0, other.0))
+    }
+}
+
+impl<C: Constrain> PartialEq for Int<C>
+where
+    C::Type: PartialEq,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl<C: Constrain> Eq for Int<C> where C::Type: Eq {}
+
+impl<C: Constrain> PartialOrd for Int<C>
+where
+    C::Type: PartialOrd,
+{
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.0.partial_cmp(&other.0)
+    }
+}
+
+impl<C: Constrain> Ord for Int<C>
+where
+    C::Type: Ord,
+{
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.cmp(&other.0)
+    }
+}
+
+impl<C: Constrain> Hash for Int<C>
+where
+    C::Type: Hash,
+{
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state)
+    }
+}
+
+/// Zero, which is in the range of every type
+impl<C: Constrain> Default for Int<C>
+where
+    C::Type: Default,
+{
+    fn default() -> Self {
+        Int(C::Type::default())
     }
 }
 
@@ -37,11 +137,39 @@ where
             formatter,
             "{:?}{}",
             self.0,
-            std::any::type_name::<C>().rsplitn(2, "::").next().unwrap()
+            std::any::type_name::<C>().rsplit("::").next().unwrap()
         )
     }
 }
 
+impl<C: Constrain> fmt::Display for Int<C>
+where
+    C::Type: fmt::Display,
+{
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(formatter)
+    }
+}
+
+impl<C: Constrain> fmt::LowerHex for Int<C>
+where
+    C::Type: fmt::LowerHex,
+{
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(formatter)
+    }
+}
+
+/// Serialized as the plain number
+impl<C: Constrain> Serialize for Int<C>
+where
+    C::Type: Serialize,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(serializer)
+    }
+}
+
 impl<C: Constrain> Constrain for Int<C> {
     type Type = C::Type;
     fn max() -> Self::Type {
@@ -50,25 +178,54 @@ impl<C: Constrain> Constrain for Int<C> {
     fn min() -> Self::Type {
         C::min()
     }
+    fn wrap(value: Self::Type) -> Self::Type {
+        C::wrap(value)
+    }
+    fn checked_add(a: Self::Type, b: Self::Type) -> Option<Self::Type> {
+        C::checked_add(a, b)
+    }
+    fn checked_sub(a: Self::Type, b: Self::Type) -> Option<Self::Type> {
+        C::checked_sub(a, b)
+    }
+    fn checked_mul(a: Self::Type, b: Self::Type) -> Option<Self::Type> {
+        C::checked_mul(a, b)
+    }
+    fn checked_neg(a: Self::Type) -> Option<Self::Type> {
+        C::checked_neg(a)
+    }
+    fn wrapping_add(a: Self::Type, b: Self::Type) -> Self::Type {
+        C::wrapping_add(a, b)
+    }
+    fn wrapping_sub(a: Self::Type, b: Self::Type) -> Self::Type {
+        C::wrapping_sub(a, b)
+    }
     const BITS: u32 = C::BITS;
 }
 
-// Eq required only so it doesn't conflict
-impl<C: Constrain, U: Eq> From<U> for Int<C>
-where
-    C::Type: From<U> + fmt::Debug + PartialOrd,
-{
-    fn from(other: U) -> Int<C> {
-        let oth = other.into();
-        debug_assert!(
-            oth <= C::max() && oth >= C::min(),
-            "Conversion from {:?} resulted in overflow!",
-            oth
-        );
-        Int(oth)
-    }
+/// Conversions from the primitive types that always fit, others go through `TryFrom`
+macro_rules! impl_from_lossless {
+    ($constrain:ty: $($type:ty),*) => {
+        $(
+            impl From<$type> for Int<$constrain> {
+                fn from(other: $type) -> Int<$constrain> {
+                    Int(other.into())
+                }
+            }
+        )*
+    };
 }
 
+impl_from_lossless!(u8: u8);
+impl_from_lossless!(u16: u8, u16);
+impl_from_lossless!(u32: u8, u16, u32);
+impl_from_lossless!(constrain::u48: u8, u16, u32);
+impl_from_lossless!(u64: u8, u16, u32, u64);
+impl_from_lossless!(i8: i8);
+impl_from_lossless!(i16: u8, i8, i16);
+impl_from_lossless!(i32: u8, u16, i8, i16, i32);
+impl_from_lossless!(constrain::i48: u8, u16, u32, i8, i16, i32);
+impl_from_lossless!(i64: u8, u16, u32, i8, i16, i32, i64);
+
 pub trait BitSerializeProto<W: io::Write, E: Endianness>
 where
     Self: Numeric,
@@ -114,24 +271,145 @@ where
     }
 }
 
-macro_rules! impl_bit_serialize {
-    ([_; $L:literal]) => {
-        impl<W: io::Write, E: Endianness, U: BitSerialize<W, E> + Copy> BitSerialize<W, E>
-            for [U; $L]
-        {
-            fn bit_serialize(self, bw: &mut BitWriter<W, E>) -> Result<(), io::Error> {
-                for idx in 0..$L {
-                    self[idx].bit_serialize(bw)?
+pub trait BitDeserializeProto<R: io::Read, E: Endianness>
+where
+    Self: Numeric,
+{
+    fn bit_deserialize_proto(bits: u32, br: &mut BitReader<R, E>) -> Result<Self, io::Error>;
+}
+pub trait BitDeserialize<R: io::Read, E: Endianness>: Sized {
+    fn bit_deserialize(br: &mut BitReader<R, E>) -> Result<Self, io::Error>;
+}
+
+macro_rules! impl_bit_deserialize_proto {
+    ($i:ident: $($type:ty),*) => {
+        $(
+            impl<R: io::Read, E: Endianness> BitDeserializeProto<R, E> for $type {
+                fn bit_deserialize_proto(bits: u32, br: &mut BitReader<R, E>) -> Result<Self, io::Error> {
+                    impl_bit_deserialize_proto!(@read $i, br, bits)
                 }
-                Ok(())
             }
+        )*
+    };
+    (@read u, $br:ident, $b:ident) => {
+        $br.read($b)
+    };
+    // Sign extended from the highest of the `bits` read
+    (@read i, $br:ident, $b:ident) => {
+        $br.read_signed($b)
+    };
+}
+
+impl_bit_deserialize_proto!(i: i8, i16, i32, i64);
+impl_bit_deserialize_proto!(u: u8, u16, u32, u64);
+
+impl<R: io::Read, E: Endianness, C: Constrain> BitDeserialize<R, E> for Int<C>
+where
+    C::Type: BitDeserializeProto<R, E>,
+{
+    fn bit_deserialize(br: &mut BitReader<R, E>) -> Result<Self, io::Error> {
+        C::Type::bit_deserialize_proto(C::BITS, br).map(Int)
+    }
+}
+
+impl<W: io::Write, E: Endianness, U: BitSerialize<W, E>, const N: usize> BitSerialize<W, E>
+    for [U; N]
+{
+    fn bit_serialize(self, bw: &mut BitWriter<W, E>) -> Result<(), io::Error> {
+        for el in self {
+            el.bit_serialize(bw)?
         }
+        Ok(())
+    }
+}
+
+impl<W: io::Write, E: Endianness, U: BitSerialize<W, E> + Copy> BitSerialize<W, E> for &[U] {
+    fn bit_serialize(self, bw: &mut BitWriter<W, E>) -> Result<(), io::Error> {
+        for &el in self {
+            el.bit_serialize(bw)?
+        }
+        Ok(())
+    }
+}
+
+impl<W: io::Write, E: Endianness, U: BitSerialize<W, E>> BitSerialize<W, E> for Vec<U> {
+    fn bit_serialize(self, bw: &mut BitWriter<W, E>) -> Result<(), io::Error> {
+        for el in self {
+            el.bit_serialize(bw)?
+        }
+        Ok(())
+    }
+}
+
+/// Optional trailing fields, nothing is written for `None`
+impl<W: io::Write, E: Endianness, U: BitSerialize<W, E>> BitSerialize<W, E> for Option<U> {
+    fn bit_serialize(self, bw: &mut BitWriter<W, E>) -> Result<(), io::Error> {
+        match self {
+            Some(el) => el.bit_serialize(bw),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Number of bits a value takes when serialized
+pub trait BitSize {
+    fn bit_size(&self) -> usize;
+}
+
+/// Types of which every value takes the same number of bits
+pub trait FixedBitSize {
+    const BIT_SIZE: usize;
+}
+
+impl<T: FixedBitSize> BitSize for T {
+    fn bit_size(&self) -> usize {
+        T::BIT_SIZE
+    }
+}
+
+macro_rules! impl_fixed_bit_size {
+    ($($type:ty),*) => {
+        $(
+            impl FixedBitSize for $type {
+                const BIT_SIZE: usize = <$type>::BITS as usize;
+            }
+        )*
     };
 }
 
-impl_bit_serialize!([_; 2]);
-impl_bit_serialize!([_; 4]);
-impl_bit_serialize!([_; 8]);
+impl_fixed_bit_size!(i8, i16, i32, i64, u8, u16, u32, u64);
+
+impl<C: Constrain> FixedBitSize for Int<C> {
+    const BIT_SIZE: usize = C::BITS as usize;
+}
+
+impl<U: FixedBitSize, const N: usize> FixedBitSize for [U; N] {
+    const BIT_SIZE: usize = N * U::BIT_SIZE;
+}
+
+impl<U: BitSize> BitSize for [U] {
+    fn bit_size(&self) -> usize {
+        self.iter().map(BitSize::bit_size).sum()
+    }
+}
+
+impl<U: BitSize> BitSize for &[U] {
+    fn bit_size(&self) -> usize {
+        self.iter().map(BitSize::bit_size).sum()
+    }
+}
+
+impl<U: BitSize> BitSize for Vec<U> {
+    fn bit_size(&self) -> usize {
+        self.iter().map(BitSize::bit_size).sum()
+    }
+}
+
+impl<U: BitSize> BitSize for Option<U> {
+    fn bit_size(&self) -> usize {
+        self.as_ref().map_or(0, BitSize::bit_size)
+    }
+}
 
 pub trait TryFrom<T> {
     type Error;
@@ -147,7 +425,7 @@ pub trait TryInto<T> {
 
 impl<T: TryFrom<U>, U> TryInto<T> for U {
     type Error = <T as TryFrom<U>>::Error;
-    fn try_into(self: Self) -> Result<T, Self::Error> {
+    fn try_into(self) -> Result<T, Self::Error> {
         T::try_from(self)
     }
 }
@@ -197,71 +475,135 @@ impl_try_from!([_; 2]);
 impl_try_from!([_; 4]);
 impl_try_from!([_; 8]);
 
-impl<C: Constrain> Add for Int<C>
-where
-    C::Type: Add + From<<C::Type as Add>::Output> + fmt::Debug + PartialOrd,
-{
+/// Panics on overflow, also in release builds where overflows used to go
+/// unnoticed. Values received from the network go through `checked_add`, or
+/// `wrapping_add` for counters such as sequenceId.
+impl<C: Constrain> Add for Int<C> {
     type Output = Self;
     fn add(self, other: Self) -> Self::Output {
-        let sum = (self.0 + other.0).into();
-        debug_assert!(
-            sum <= C::max() && sum >= C::min(),
-            "Sum {:?} resulted in overflow!",
-            sum
-        );
-        Int(sum)
+        self.checked_add(other).expect("Sum resulted in overflow!")
     }
 }
 
 impl<C: Constrain> AddAssign for Int<C>
 where
-    C::Type: AddAssign + fmt::Debug + PartialOrd,
+    C::Type: Copy,
 {
     fn add_assign(&mut self, other: Self) {
-        self.0 += other.0;
-        debug_assert!(
-            self.0 <= C::max() && self.0 >= C::min(),
-            "Sum {:?} resulted in overflow!",
-            self.0
-        );
+        self.0 = C::checked_add(self.0, other.0).expect("Sum resulted in overflow!");
+    }
+}
+
+/// Panics on overflow, see `checked_sub` and `wrapping_sub`
+impl<C: Constrain> Sub for Int<C> {
+    type Output = Self;
+    fn sub(self, other: Self) -> Self::Output {
+        self.checked_sub(other)
+            .expect("Difference resulted in overflow!")
+    }
+}
+
+impl<C: Constrain> SubAssign for Int<C>
+where
+    C::Type: Copy,
+{
+    fn sub_assign(&mut self, other: Self) {
+        self.0 = C::checked_sub(self.0, other.0).expect("Difference resulted in overflow!");
+    }
+}
+
+/// Panics on overflow, see `checked_mul`
+impl<C: Constrain> Mul for Int<C> {
+    type Output = Self;
+    fn mul(self, other: Self) -> Self::Output {
+        self.checked_mul(other)
+            .expect("Product resulted in overflow!")
+    }
+}
+
+/// Only for signed types, panics when negating the minimum
+impl<C: Constrain> Neg for Int<C>
+where
+    C::Type: Neg,
+{
+    type Output = Self;
+    fn neg(self) -> Self::Output {
+        self.checked_neg().expect("Negation resulted in overflow!")
     }
 }
 
+/// Panics when the result is out of range, e.g. bits shifted past a `Nibble`
 impl<C: Constrain, U> Shl<U> for Int<C>
 where
-    C::Type: Shl<U> + From<<C::Type as Shl<U>>::Output> + fmt::Debug + PartialOrd,
+    C::Type: Shl<U> + From<<C::Type as Shl<U>>::Output> + PartialOrd,
 {
     type Output = Self;
     fn shl(self, other: U) -> Self::Output {
-        let out = (self.0 << other).into();
-        debug_assert!(out <= C::max() && out >= C::min());
-        Int(out)
+        Self::checked_new((self.0 << other).into()).expect("Shift resulted in overflow!")
     }
 }
 
+/// Panics when the result is out of range
 impl<C: Constrain, U> Shr<U> for Int<C>
 where
-    C::Type: Shr<U> + From<<C::Type as Shr<U>>::Output> + fmt::Debug + PartialOrd,
+    C::Type: Shr<U> + From<<C::Type as Shr<U>>::Output> + PartialOrd,
 {
     type Output = Self;
     fn shr(self, other: U) -> Self::Output {
-        let out = (self.0 >> other).into();
-        debug_assert!(out <= C::max() && out >= C::min());
-        Int(out)
+        Self::checked_new((self.0 >> other).into()).expect("Shift resulted in overflow!")
+    }
+}
+
+// Masking two values in range stays in range, also for sign extended narrow types
+impl<C: Constrain> BitAnd for Int<C>
+where
+    C::Type: BitAnd<Output = C::Type>,
+{
+    type Output = Self;
+    fn bitand(self, other: Self) -> Self::Output {
+        Int(self.0 & other.0)
+    }
+}
+
+impl<C: Constrain> BitAndAssign for Int<C>
+where
+    C::Type: BitAndAssign,
+{
+    fn bitand_assign(&mut self, other: Self) {
+        self.0 &= other.0
+    }
+}
+
+impl<C: Constrain> BitOr for Int<C>
+where
+    C::Type: BitOr<Output = C::Type>,
+{
+    type Output = Self;
+    fn bitor(self, other: Self) -> Self::Output {
+        Int(self.0 | other.0)
+    }
+}
+
+impl<C: Constrain> BitOrAssign for Int<C>
+where
+    C::Type: BitOrAssign,
+{
+    fn bitor_assign(&mut self, other: Self) {
+        self.0 |= other.0
     }
 }
 
 macro_rules! enum_Int {
     ($name:ident<$type:ty> {$($field:ident = $value:expr),*}) => {
-        #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+        #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
         pub enum $name {
             $(
                 $field = $value
             ),*
         }
-        impl Into<Int<$type>> for $name {
-            fn into(self) -> Int<$type> {
-                Int::new(match self {
+        impl From<$name> for Int<$type> {
+            fn from(other: $name) -> Int<$type> {
+                Int::new(match other {
                     $(
                         $name::$field => $value
                     ),*
@@ -280,7 +622,41 @@ macro_rules! enum_Int {
                 }
             }
         }
-       impl<W: io::Write, E: Endianness> BitSerialize<W, E> for $name {
+        impl $name {
+            pub fn name(self) -> &'static str {
+                match self {
+                    $(
+                        $name::$field => stringify!($field)
+                    ),*
+                }
+            }
+        }
+        impl std::fmt::Display for $name {
+            fn fmt(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str(self.name())
+            }
+        }
+        impl serde::Serialize for $name {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_str(self.name())
+            }
+        }
+        impl std::str::FromStr for $name {
+            type Err = ();
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                match s {
+                    $(
+                        stringify!($field) => Ok($name::$field),
+                    )*
+                    _ => Err(()),
+                }
+            }
+        }
+        impl FixedBitSize for $name {
+            const BIT_SIZE: usize = <$type as Constrain>::BITS as usize;
+        }
+        impl<W: io::Write, E: Endianness> BitSerialize<W, E> for $name {
             fn bit_serialize(self, bw: &mut BitWriter<W, E>) -> Result<(), io::Error> {
                 let val: <$type as Constrain>::Type = match self {
                     $(
@@ -0,0 +1,319 @@
+use super::{Counters, ServoSummary, MESSAGE_TYPES};
+use crate::clock::Clock;
+use crate::protocol::types::derived::format_clock_identity;
+use crate::protocol::types::enums::values;
+use crate::protocol::types::primitive::Enumeration4;
+
+use mio::net::{TcpListener, TcpStream};
+use mio::{Interest, Registry, Token};
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt::Write as _;
+use std::io::{self, Read, Write};
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+/// Time a client gets to send its request and read the response
+const CLIENT_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Appends the HELP and TYPE lines of a metric
+fn describe(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn write_counts(out: &mut String, name: &str, help: &str, counts: &[u64; MESSAGE_TYPES]) {
+    describe(out, name, "counter", help);
+    for (idx, count) in counts.iter().enumerate() {
+        if let Ok(message_type) = values::MessageType::try_from(Enumeration4::new(idx as u8)) {
+            let _ = writeln!(out, "{}{{type=\"{}\"}} {}", name, message_type, count);
+        }
+    }
+}
+
+/// Renders the clock's state, the message counters and the last servo summary
+/// in the Prometheus text exposition format
+pub fn render(clock: &Clock, counters: &Counters, summary: Option<&ServoSummary>) -> String {
+    let mut out = String::new();
+
+    describe(
+        &mut out,
+        "ptp_port_state",
+        "gauge",
+        "portState enumeration value (IEEE 1588-2008, Table 8)",
+    );
+    for port in &clock.ports {
+        let _ = writeln!(
+            out,
+            "ptp_port_state{{port=\"{}\",state=\"{}\"}} {}",
+            port.identity.portNumber, port.state, port.state as u8
+        );
+    }
+
+    describe(
+        &mut out,
+        "ptp_offset_from_master_nanoseconds",
+        "gauge",
+        "currentDS.offsetFromMaster",
+    );
+    let _ = writeln!(
+        out,
+        "ptp_offset_from_master_nanoseconds {}",
+        clock.current_ds.offsetFromMaster
+    );
+    describe(
+        &mut out,
+        "ptp_mean_path_delay_nanoseconds",
+        "gauge",
+        "currentDS.meanPathDelay",
+    );
+    let _ = writeln!(
+        out,
+        "ptp_mean_path_delay_nanoseconds {}",
+        clock.current_ds.meanPathDelay
+    );
+    describe(
+        &mut out,
+        "ptp_steps_removed",
+        "gauge",
+        "currentDS.stepsRemoved",
+    );
+    let _ = writeln!(out, "ptp_steps_removed {}", clock.current_ds.stepsRemoved);
+    describe(
+        &mut out,
+        "ptp_grandmaster_info",
+        "gauge",
+        "Identity of the grandmaster in parentDS",
+    );
+    let _ = writeln!(
+        out,
+        "ptp_grandmaster_info{{clock_identity=\"{}\"}} 1",
+        format_clock_identity(&clock.parent_ds.grandmasterIdentity)
+    );
+
+    if let Some(summary) = summary {
+        describe(
+            &mut out,
+            "ptp_offset_rms_nanoseconds",
+            "gauge",
+            "RMS of the offsets of the last summary interval",
+        );
+        let _ = writeln!(out, "ptp_offset_rms_nanoseconds {}", summary.offset.rms);
+        describe(
+            &mut out,
+            "ptp_offset_max_nanoseconds",
+            "gauge",
+            "Largest absolute offset of the last summary interval",
+        );
+        let _ = writeln!(out, "ptp_offset_max_nanoseconds {}", summary.offset.max);
+        if let Some(freq) = summary.freq {
+            describe(
+                &mut out,
+                "ptp_frequency_adjustment_ppb",
+                "gauge",
+                "Mean frequency adjustment of the last summary interval",
+            );
+            let _ = writeln!(out, "ptp_frequency_adjustment_ppb {}", freq.mean);
+        }
+    }
+
+    write_counts(
+        &mut out,
+        "ptp_messages_received_total",
+        "Messages received and accepted",
+        &counters.received,
+    );
+    write_counts(
+        &mut out,
+        "ptp_messages_sent_total",
+        "Messages sent",
+        &counters.sent,
+    );
+    write_counts(
+        &mut out,
+        "ptp_messages_dropped_total",
+        "Messages received for another domain or from the clock itself",
+        &counters.dropped,
+    );
+    describe(
+        &mut out,
+        "ptp_messages_unparseable_total",
+        "counter",
+        "Datagrams that couldn't be parsed",
+    );
+    let _ = writeln!(
+        out,
+        "ptp_messages_unparseable_total {}",
+        counters.unparseable
+    );
+    out
+}
+
+/// Largest request accepted, the rest of a longer request is ignored
+const MAX_REQUEST: usize = 8192;
+/// Connections served at the same time, further clients are refused
+const MAX_CLIENTS: usize = 16;
+
+/// Connection in progress, read and written without blocking
+#[derive(Debug)]
+struct Client {
+    stream: TcpStream,
+    request: Vec<u8>,
+    response: Vec<u8>,
+    written: usize,
+    deadline: Instant,
+}
+
+impl Client {
+    /// Reads and writes what the socket allows, returns whether the connection is done
+    fn progress<F: FnMut() -> String>(&mut self, metrics: &mut F) -> io::Result<bool> {
+        let mut buf = [0; 1024];
+        while self.response.is_empty() {
+            match self.stream.read(&mut buf) {
+                Ok(0) => self.response = response(&self.request, metrics),
+                Ok(len) => {
+                    self.request.extend_from_slice(&buf[..len]);
+                    if self.request.windows(4).any(|window| window == b"\r\n\r\n")
+                        || self.request.len() >= MAX_REQUEST
+                    {
+                        self.response = response(&self.request, metrics);
+                    }
+                }
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => return Ok(false),
+                Err(err) => return Err(err),
+            }
+        }
+        while self.written < self.response.len() {
+            match self.stream.write(&self.response[self.written..]) {
+                Ok(len) => self.written += len,
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => return Ok(false),
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(true)
+    }
+}
+
+/// Minimal HTTP listener answering `GET /metrics` with the text of `render`.
+/// Clients get tokens counting down from the listener's token.
+#[derive(Debug)]
+pub struct MetricsServer {
+    listener: TcpListener,
+    token: Token,
+    clients: HashMap<Token, Client>,
+}
+
+impl MetricsServer {
+    pub fn bind(addr: SocketAddr) -> io::Result<Self> {
+        // Bound through std, mio 0.7.0's own bind doesn't handle current SocketAddr layouts
+        let listener = std::net::TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(MetricsServer {
+            listener: TcpListener::from_std(listener),
+            token: Token(usize::MAX),
+            clients: HashMap::new(),
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    pub fn register(&mut self, registry: &Registry, token: Token) -> io::Result<()> {
+        self.token = token;
+        registry.register(&mut self.listener, token, Interest::READABLE)
+    }
+
+    /// Whether `token` is the listener's or one of its clients'
+    pub fn owns(&self, token: Token) -> bool {
+        token == self.token || self.clients.contains_key(&token)
+    }
+
+    /// Handles an event of `token`, accepting pending connections or serving a
+    /// client, calling `metrics` once per request
+    pub fn serve<F: FnMut() -> String>(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        now: Instant,
+        mut metrics: F,
+    ) -> io::Result<()> {
+        if token != self.token {
+            let done = match self.clients.get_mut(&token) {
+                Some(client) => client.progress(&mut metrics).unwrap_or_else(|err| {
+                    eprintln!("Couldn't answer metrics request: {}", err);
+                    true
+                }),
+                None => return Ok(()),
+            };
+            if done {
+                self.close(registry, token);
+            }
+            return Ok(());
+        }
+        loop {
+            let mut stream = match self.listener.accept() {
+                Ok((stream, _)) => stream,
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+                Err(err) => return Err(err),
+            };
+            let token = match (1..=MAX_CLIENTS)
+                .map(|idx| Token(self.token.0 - idx))
+                .find(|token| !self.clients.contains_key(token))
+            {
+                Some(token) => token,
+                // Dropping the stream closes the connection
+                None => continue,
+            };
+            registry.register(&mut stream, token, Interest::READABLE | Interest::WRITABLE)?;
+            self.clients.insert(
+                token,
+                Client {
+                    stream,
+                    request: Vec::new(),
+                    response: Vec::new(),
+                    written: 0,
+                    deadline: now + CLIENT_TIMEOUT,
+                },
+            );
+        }
+    }
+
+    /// Closes the connections of clients that took too long
+    pub fn expire(&mut self, registry: &Registry, now: Instant) {
+        let expired: Vec<_> = self
+            .clients
+            .iter()
+            .filter(|(_, client)| now >= client.deadline)
+            .map(|(&token, _)| token)
+            .collect();
+        for token in expired {
+            self.close(registry, token);
+        }
+    }
+
+    fn close(&mut self, registry: &Registry, token: Token) {
+        if let Some(mut client) = self.clients.remove(&token) {
+            let _ = registry.deregister(&mut client.stream);
+        }
+    }
+}
+
+/// Response to a request, `metrics` being called only for requests of the metrics
+fn response<F: FnMut() -> String>(request: &[u8], metrics: &mut F) -> Vec<u8> {
+    let request_line = String::from_utf8_lossy(request);
+    let mut words = request_line.split_whitespace();
+    let (status, body) = match (words.next(), words.next()) {
+        (Some("GET"), Some("/metrics")) | (Some("GET"), Some("/")) => ("200 OK", metrics()),
+        (Some("GET"), _) => ("404 Not Found", String::new()),
+        _ => ("405 Method Not Allowed", String::new()),
+    };
+    format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )
+    .into_bytes()
+}
@@ -0,0 +1,211 @@
+use crate::config;
+use crate::protocol::types::enums::MessageType;
+use crate::protocol::types::message::Message;
+use crate::protocol::types::primitive::Enumeration4;
+
+use std::fmt;
+use std::time::Duration;
+
+pub mod metrics;
+
+#[cfg(test)]
+mod tests;
+
+/// Aggregate of the samples of one summary interval
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Summary {
+    pub count: u64,
+    pub mean: f64,
+    pub rms: f64,
+    /// Largest absolute value
+    pub max: f64,
+    pub stddev: f64,
+}
+
+/// Accumulates samples without storing them
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Stats {
+    count: u64,
+    sum: f64,
+    sum_squares: f64,
+    max: f64,
+}
+
+impl Stats {
+    pub fn add(&mut self, sample: f64) {
+        self.count += 1;
+        self.sum += sample;
+        self.sum_squares += sample * sample;
+        self.max = self.max.max(sample.abs());
+    }
+
+    pub fn summary(&self) -> Option<Summary> {
+        if self.count == 0 {
+            return None;
+        }
+        let count = self.count as f64;
+        let mean = self.sum / count;
+        let mean_square = self.sum_squares / count;
+        Some(Summary {
+            count: self.count,
+            mean,
+            rms: mean_square.sqrt(),
+            max: self.max,
+            stddev: (mean_square - mean * mean).max(0.0).sqrt(),
+        })
+    }
+
+    pub fn reset(&mut self) {
+        *self = Stats::default();
+    }
+}
+
+/// Servo statistics of one summary interval in nanoseconds and ppb
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ServoSummary {
+    pub offset: Summary,
+    pub freq: Option<Summary>,
+    pub delay: Option<Summary>,
+}
+
+/// Formatted like ptp4l's summary, e.g. `rms 12 max 30 freq -1200 +/- 5 delay 480 +/- 2`
+impl fmt::Display for ServoSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "rms {:4.0} max {:4.0}", self.offset.rms, self.offset.max)?;
+        if let Some(freq) = self.freq {
+            write!(f, " freq {:+6.0} +/- {:3.0}", freq.mean, freq.stddev)?;
+        }
+        if let Some(delay) = self.delay {
+            write!(f, " delay {:5.0} +/- {:3.0}", delay.mean, delay.stddev)?;
+        }
+        Ok(())
+    }
+}
+
+/// Offset, frequency adjustment and path delay samples summarized every `interval`
+#[derive(Debug, Clone)]
+pub struct ServoStats {
+    pub offset: Stats,
+    pub freq: Stats,
+    pub delay: Stats,
+    interval: Duration,
+    /// Time of the first sample of the current interval
+    start: Option<Duration>,
+}
+
+impl ServoStats {
+    /// `log_interval` is the log2 of the summary interval in seconds, as `summary_interval`
+    pub fn new(log_interval: i8) -> Self {
+        ServoStats {
+            offset: Stats::default(),
+            freq: Stats::default(),
+            delay: Stats::default(),
+            interval: config::log_interval(log_interval),
+            start: None,
+        }
+    }
+
+    /// Adds samples taken at `now` and returns the summary once the interval has elapsed
+    pub fn add(
+        &mut self,
+        now: Duration,
+        offset: f64,
+        freq: Option<f64>,
+        delay: Option<f64>,
+    ) -> Option<ServoSummary> {
+        let start = *self.start.get_or_insert(now);
+        self.offset.add(offset);
+        if let Some(freq) = freq {
+            self.freq.add(freq);
+        }
+        if let Some(delay) = delay {
+            self.delay.add(delay);
+        }
+        if now.saturating_sub(start) < self.interval {
+            return None;
+        }
+        let summary = ServoSummary {
+            offset: self.offset.summary()?,
+            freq: self.freq.summary(),
+            delay: self.delay.summary(),
+        };
+        self.offset.reset();
+        self.freq.reset();
+        self.delay.reset();
+        self.start = None;
+        Some(summary)
+    }
+}
+
+/// Number of message types, which are 4 bit values
+const MESSAGE_TYPES: usize = 16;
+
+/// Messages received, sent and dropped by message type, and messages that
+/// couldn't be parsed
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Counters {
+    pub received: [u64; MESSAGE_TYPES],
+    pub sent: [u64; MESSAGE_TYPES],
+    pub dropped: [u64; MESSAGE_TYPES],
+    pub unparseable: u64,
+}
+
+fn index(message: &Message) -> usize {
+    Enumeration4::from(message.header.messageType)
+        .into_inner()
+        .into()
+}
+
+impl Counters {
+    pub fn received(&mut self, message: &Message) {
+        self.received[index(message)] += 1;
+    }
+
+    pub fn sent(&mut self, message: &Message) {
+        self.sent[index(message)] += 1;
+    }
+
+    pub fn dropped(&mut self, message: &Message) {
+        self.dropped[index(message)] += 1;
+    }
+
+    pub fn unparseable(&mut self) {
+        self.unparseable += 1;
+    }
+
+    /// Counts since `earlier`, a previous copy of these counters
+    pub fn since(&self, earlier: &Counters) -> Counters {
+        let diff = |now: &[u64; MESSAGE_TYPES], then: &[u64; MESSAGE_TYPES]| {
+            let mut diff = [0; MESSAGE_TYPES];
+            for (idx, count) in diff.iter_mut().enumerate() {
+                *count = now[idx] - then[idx];
+            }
+            diff
+        };
+        Counters {
+            received: diff(&self.received, &earlier.received),
+            sent: diff(&self.sent, &earlier.sent),
+            dropped: diff(&self.dropped, &earlier.dropped),
+            unparseable: self.unparseable - earlier.unparseable,
+        }
+    }
+}
+
+fn write_counts(f: &mut fmt::Formatter, name: &str, counts: &[u64]) -> fmt::Result {
+    write!(f, "{}", name)?;
+    for (idx, &count) in counts.iter().enumerate().filter(|(_, &count)| count > 0) {
+        let message_type: MessageType = Enumeration4::new(idx as u8).into();
+        write!(f, " {} {}", message_type, count)?;
+    }
+    Ok(())
+}
+
+/// Non-zero counters, e.g. `rx Sync 16 Announce 2 tx Delay_Req 16 dropped unparseable 0`
+impl fmt::Display for Counters {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_counts(f, "rx", &self.received)?;
+        write_counts(f, " tx", &self.sent)?;
+        write_counts(f, " dropped", &self.dropped)?;
+        write!(f, " unparseable {}", self.unparseable)
+    }
+}
@@ -0,0 +1,128 @@
+use super::*;
+use crate::protocol::types::derived::PortIdentity;
+use crate::protocol::types::enums::values;
+use crate::protocol::types::message::{Body, Header};
+use crate::protocol::types::primitive::int::Int;
+
+#[test]
+pub fn test_stats() {
+    let mut stats = Stats::default();
+    assert_eq!(stats.summary(), None);
+    for sample in [2.0, -4.0, 4.0, -2.0] {
+        stats.add(sample);
+    }
+    let summary = stats.summary().unwrap();
+    assert_eq!(summary.count, 4);
+    assert_eq!(summary.mean, 0.0);
+    assert_eq!(summary.rms, 10f64.sqrt());
+    assert_eq!(summary.max, 4.0);
+    assert_eq!(summary.stddev, 10f64.sqrt());
+
+    let mut servo = ServoStats::new(0);
+    let secs = Duration::from_secs;
+    assert_eq!(servo.add(secs(10), 10.0, Some(-1000.0), Some(500.0)), None);
+    let summary = servo
+        .add(secs(11), -20.0, Some(-1010.0), Some(502.0))
+        .unwrap();
+    assert_eq!(summary.offset.max, 20.0);
+    assert_eq!(
+        summary.to_string(),
+        "rms   16 max   20 freq  -1005 +/-   5 delay   501 +/-   1"
+    );
+    // A new interval starts with the next sample
+    assert_eq!(servo.add(secs(12), 1.0, None, None), None);
+    assert_eq!(
+        servo.add(secs(13), 1.0, None, None).unwrap().to_string(),
+        "rms    1 max    1"
+    );
+}
+
+#[test]
+pub fn test_counters() {
+    let source = PortIdentity {
+        clockIdentity: [Int::new(1); 8],
+        portNumber: Int::new(1),
+    };
+    let message = |message_type| Message {
+        header: Header::new(message_type, 44, 0, source, 0, 0),
+        body: Body::Empty,
+    };
+    let mut counters = Counters::default();
+    counters.received(&message(values::MessageType::Sync));
+    counters.received(&message(values::MessageType::Sync));
+    counters.received(&message(values::MessageType::Announce));
+    counters.sent(&message(values::MessageType::Delay_Req));
+    counters.dropped(&message(values::MessageType::Sync));
+    counters.unparseable();
+    assert_eq!(counters.received[0], 2);
+    assert_eq!(
+        counters.to_string(),
+        "rx Sync 2 Announce 1 tx Delay_Req 1 dropped Sync 1 unparseable 1"
+    );
+}
+
+#[test]
+pub fn test_metrics() {
+    use crate::clock::Clock;
+    use crate::config::{ClockConfig, PortConfig};
+    use std::io::{Read, Write};
+    use std::time::Instant;
+
+    let clock = Clock::new(
+        [Int::new(1); 8],
+        ClockConfig::default(),
+        &[PortConfig::default()],
+        Instant::now(),
+    );
+    let mut counters = Counters::default();
+    counters.unparseable();
+    let text = metrics::render(&clock, &counters, None);
+    assert!(text.contains("ptp_port_state{port=\"1\",state=\"LISTENING\"} 4\n"));
+    assert!(text.contains("ptp_grandmaster_info{clock_identity=\"010101.0101.010101\"} 1\n"));
+    assert!(text.contains("ptp_messages_received_total{type=\"Sync\"} 0\n"));
+    assert!(text.contains("ptp_messages_unparseable_total 1\n"));
+    assert!(!text.contains("ptp_frequency_adjustment_ppb"));
+    let mut stats = ServoStats::new(0);
+    stats.add(Duration::from_secs(0), 10.0, Some(-1200.0), Some(480.0));
+    let summary = stats.add(Duration::from_secs(1), -10.0, Some(-1000.0), Some(520.0));
+    let with_summary = metrics::render(&clock, &counters, summary.as_ref());
+    assert!(with_summary.contains("ptp_offset_rms_nanoseconds 10\n"));
+    assert!(with_summary.contains("ptp_frequency_adjustment_ppb -1100\n"));
+
+    let mut server = metrics::MetricsServer::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+    let mut poll = mio::Poll::new().unwrap();
+    let mut events = mio::Events::with_capacity(8);
+    server
+        .register(poll.registry(), mio::Token(usize::MAX))
+        .unwrap();
+    let mut client = std::net::TcpStream::connect(server.local_addr().unwrap()).unwrap();
+    // A client that doesn't send its request doesn't hold up the others
+    let mut slow = std::net::TcpStream::connect(server.local_addr().unwrap()).unwrap();
+    client
+        .write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n")
+        .unwrap();
+    let now = Instant::now();
+    let mut serve = |server: &mut metrics::MetricsServer| {
+        poll.poll(&mut events, Some(Duration::from_millis(100)))
+            .unwrap();
+        for event in &events {
+            assert!(server.owns(event.token()));
+            server
+                .serve(poll.registry(), event.token(), now, || text.clone())
+                .unwrap();
+        }
+    };
+    serve(&mut server);
+    serve(&mut server);
+    client.write_all(b"\r\n").unwrap();
+    serve(&mut server);
+    let mut response = String::new();
+    client.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.ends_with(&text));
+
+    // Connections are closed once the client timeout expires
+    server.expire(poll.registry(), now + Duration::from_secs(1));
+    let mut rest = Vec::new();
+    assert_eq!(slow.read_to_end(&mut rest).unwrap(), 0);
+}
@@ -0,0 +1,275 @@
+use mio::net::UdpSocket;
+use mio::{Interest, Registry, Token};
+
+use ifaces::interface::{Interface, Kind};
+use nix::libc;
+use nix::sys::socket::sockopt::{BindToDevice, IpMulticastLoop, ReceiveTimestamp, ReuseAddr};
+use nix::sys::socket::{
+    bind, recvmsg, setsockopt, socket, AddressFamily, ControlMessageOwned, InetAddr, MsgFlags,
+    SockAddr, SockFlag, SockType,
+};
+use nix::sys::time::TimeVal;
+use nix::sys::uio::IoVec;
+
+use std::ffi::OsString;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+use std::time::Duration;
+
+pub const PTP_PRIMARY_MULTICAST: Ipv4Addr = Ipv4Addr::new(224, 0, 1, 129);
+pub const EVENT_PORT: u16 = 319;
+pub const GENERAL_PORT: u16 = 320;
+
+/// Reports timestamps without looping the datagram back, missing in libc
+const SOF_TIMESTAMPING_OPT_TSONLY: libc::c_uint = 1 << 11;
+/// Software transmit timestamps reported on the error queue of event sockets
+const TX_TIMESTAMPING: libc::c_uint = libc::SOF_TIMESTAMPING_TX_SOFTWARE
+    | libc::SOF_TIMESTAMPING_SOFTWARE
+    | SOF_TIMESTAMPING_OPT_TSONLY;
+/// Milliseconds the kernel gets to report a transmit timestamp, as linuxptp's
+/// default tx_timestamp_timeout
+const TX_TIMESTAMP_TIMEOUT: libc::c_int = 1;
+
+fn nix_error(err: nix::Error) -> io::Error {
+    match err.as_errno() {
+        Some(errno) => io::Error::from_raw_os_error(errno as i32),
+        None => io::Error::other(err),
+    }
+}
+
+/// IPv4 address of interface `iface`
+pub fn iface_addr(iface: &str) -> io::Result<Ipv4Addr> {
+    let not_found = |what| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("Couldn't find {} of iface: {}", what, iface),
+        )
+    };
+    match Interface::get_all()?
+        .iter()
+        .find(|interface| interface.name == iface && interface.kind == Kind::Ipv4)
+        .ok_or_else(|| not_found("IPv4 interface"))?
+        .addr
+        .ok_or_else(|| not_found("address"))?
+        .ip()
+    {
+        IpAddr::V4(addr) => Ok(addr),
+        IpAddr::V6(_) => Err(not_found("IPv4 address")),
+    }
+}
+
+/// Reads the MAC address of interface `iface` from sysfs
+pub fn mac_address(iface: &str) -> io::Result<[u8; 6]> {
+    let address = std::fs::read_to_string(format!("/sys/class/net/{}/address", iface))?;
+    let invalid = || {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("invalid MAC address of {}: {}", iface, address.trim()),
+        )
+    };
+    let mut mac = [0; 6];
+    let mut octets = address.trim().split(':');
+    for octet in mac.iter_mut() {
+        *octet = octets
+            .next()
+            .and_then(|octet| u8::from_str_radix(octet, 16).ok())
+            .ok_or_else(invalid)?;
+    }
+    match octets.next() {
+        Some(_) => Err(invalid()),
+        None => Ok(mac),
+    }
+}
+
+/// Takes the next entry off the error queue of `fd` and returns its software
+/// transmit timestamp, failing with WouldBlock when the queue is empty
+fn recv_tx_timestamp(fd: RawFd) -> io::Result<Option<Duration>> {
+    let mut data = [0u8; 64];
+    let mut control = [0u64; 64];
+    let mut iov = libc::iovec {
+        iov_base: data.as_mut_ptr() as *mut libc::c_void,
+        iov_len: data.len(),
+    };
+    let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+    msg.msg_controllen = std::mem::size_of_val(&control) as _;
+    if unsafe { libc::recvmsg(fd, &mut msg, libc::MSG_ERRQUEUE | libc::MSG_DONTWAIT) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let mut cmsg = unsafe { libc::CMSG_FIRSTHDR(&msg) };
+    while let Some(header) = unsafe { cmsg.as_ref() } {
+        if header.cmsg_level == libc::SOL_SOCKET && header.cmsg_type == libc::SCM_TIMESTAMPING {
+            // Software, legacy and hardware timestamps, the latter two unused
+            let timestamps = unsafe {
+                std::ptr::read_unaligned(libc::CMSG_DATA(cmsg) as *const [libc::timespec; 3])
+            };
+            let software = timestamps[0];
+            if software.tv_sec > 0 || software.tv_nsec > 0 {
+                return Ok(Some(Duration::new(
+                    software.tv_sec as u64,
+                    software.tv_nsec as u32,
+                )));
+            }
+        }
+        cmsg = unsafe { libc::CMSG_NXTHDR(&msg, cmsg) };
+    }
+    Ok(None)
+}
+
+/// Binds a socket to `port` that only receives traffic from interface `iface`,
+/// so several ports can share the PTP UDP ports
+fn bind_device(iface: &str, port: u16) -> io::Result<std::net::UdpSocket> {
+    let fd = socket(
+        AddressFamily::Inet,
+        SockType::Datagram,
+        SockFlag::SOCK_NONBLOCK | SockFlag::SOCK_CLOEXEC,
+        None,
+    )
+    .map_err(nix_error)?;
+    let socket = unsafe { std::net::UdpSocket::from_raw_fd(fd) };
+    setsockopt(fd, ReuseAddr, &true).map_err(nix_error)?;
+    setsockopt(fd, BindToDevice, &OsString::from(iface)).map_err(nix_error)?;
+    setsockopt(fd, IpMulticastLoop, &false).map_err(nix_error)?;
+    bind(
+        fd,
+        &SockAddr::new_inet(InetAddr::from_std(&SocketAddr::new(
+            IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            port,
+        ))),
+    )
+    .map_err(nix_error)?;
+    Ok(socket)
+}
+
+/// Blocking socket on the general port for exchanging management messages
+/// with PTP nodes reachable through interface `iface`, including the local one
+pub fn management_socket(iface: &str, iface_addr: Ipv4Addr) -> io::Result<std::net::UdpSocket> {
+    let socket = bind_device(iface, GENERAL_PORT)?;
+    setsockopt(socket.as_raw_fd(), IpMulticastLoop, &true).map_err(nix_error)?;
+    socket.set_nonblocking(false)?;
+    socket.join_multicast_v4(&PTP_PRIMARY_MULTICAST, &iface_addr)?;
+    Ok(socket)
+}
+
+/// Event and general sockets of a PTP port using UDP over IPv4
+#[derive(Debug)]
+pub struct UdpPort {
+    pub event: UdpSocket,
+    pub general: UdpSocket,
+}
+
+impl UdpPort {
+    pub fn open(iface: &str, iface_addr: Ipv4Addr) -> io::Result<Self> {
+        let event = UdpSocket::from_std(bind_device(iface, EVENT_PORT)?);
+        let general = UdpSocket::from_std(bind_device(iface, GENERAL_PORT)?);
+        setsockopt(event.as_raw_fd(), ReceiveTimestamp, &true).map_err(nix_error)?;
+        let flags = TX_TIMESTAMPING as libc::c_int;
+        if unsafe {
+            libc::setsockopt(
+                event.as_raw_fd(),
+                libc::SOL_SOCKET,
+                libc::SO_TIMESTAMPING,
+                &flags as *const libc::c_int as *const libc::c_void,
+                std::mem::size_of_val(&flags) as libc::socklen_t,
+            )
+        } < 0
+        {
+            return Err(io::Error::last_os_error());
+        }
+        event.join_multicast_v4(&PTP_PRIMARY_MULTICAST, &iface_addr)?;
+        general.join_multicast_v4(&PTP_PRIMARY_MULTICAST, &iface_addr)?;
+        Ok(UdpPort { event, general })
+    }
+
+    pub fn register(
+        &mut self,
+        registry: &Registry,
+        event: Token,
+        general: Token,
+    ) -> io::Result<()> {
+        registry.register(&mut self.event, event, Interest::READABLE)?;
+        registry.register(&mut self.general, general, Interest::READABLE)
+    }
+
+    /// Receives an event message along with its kernel receive timestamp
+    pub fn recv_event(
+        &self,
+        buf: &mut [u8],
+    ) -> io::Result<(usize, Option<SocketAddr>, Option<TimeVal>)> {
+        let mut cmsg_buf = cmsg_space!(TimeVal);
+        let iov_buf = IoVec::from_mut_slice(buf);
+        let msg = recvmsg(
+            self.event.as_raw_fd(),
+            &[iov_buf],
+            Some(&mut cmsg_buf),
+            MsgFlags::empty(),
+        )
+        .map_err(nix_error)?;
+        let timestamp = msg.cmsgs().find_map(|cmsg| match cmsg {
+            ControlMessageOwned::ScmTimestamp(timestamp) => Some(timestamp),
+            _ => None,
+        });
+        let addr = match msg.address {
+            Some(SockAddr::Inet(addr)) => Some(addr.to_std()),
+            _ => None,
+        };
+        Ok((msg.bytes, addr, timestamp))
+    }
+
+    pub fn recv_general(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        self.general.recv_from(buf)
+    }
+
+    pub fn send_event(&self, buf: &[u8]) -> io::Result<usize> {
+        self.send_event_to(buf, PTP_PRIMARY_MULTICAST)
+    }
+
+    pub fn send_general(&self, buf: &[u8]) -> io::Result<usize> {
+        self.send_general_to(buf, PTP_PRIMARY_MULTICAST)
+    }
+
+    pub fn send_event_to(&self, buf: &[u8], addr: Ipv4Addr) -> io::Result<usize> {
+        self.event
+            .send_to(buf, SocketAddr::new(addr.into(), EVENT_PORT))
+    }
+
+    /// Sends an event message and waits for its kernel transmit timestamp,
+    /// None when the kernel didn't report one in time
+    pub fn send_event_timestamped(
+        &self,
+        buf: &[u8],
+        addr: Ipv4Addr,
+    ) -> io::Result<Option<Duration>> {
+        let fd = self.event.as_raw_fd();
+        // Timestamps of earlier messages that came too late would be mistaken for this one's
+        loop {
+            match recv_tx_timestamp(fd) {
+                Ok(_) => continue,
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) => return Err(err),
+            }
+        }
+        self.send_event_to(buf, addr)?;
+        let mut pollfd = libc::pollfd {
+            fd,
+            events: libc::POLLPRI,
+            revents: 0,
+        };
+        match unsafe { libc::poll(&mut pollfd, 1, TX_TIMESTAMP_TIMEOUT) } {
+            -1 => Err(io::Error::last_os_error()),
+            0 => Ok(None),
+            _ => match recv_tx_timestamp(fd) {
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => Ok(None),
+                result => result,
+            },
+        }
+    }
+
+    pub fn send_general_to(&self, buf: &[u8], addr: Ipv4Addr) -> io::Result<usize> {
+        self.general
+            .send_to(buf, SocketAddr::new(addr.into(), GENERAL_PORT))
+    }
+}
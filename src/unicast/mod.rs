@@ -0,0 +1,492 @@
+use crate::config::{ClockConfig, PortConfig};
+use crate::management::all_ports;
+use crate::protocol::types::derived::PortIdentity;
+use crate::protocol::types::enums::{values, Enumeration, MessageType};
+use crate::protocol::types::message::{body, tlv, Body, Header, Message, Tlv};
+use crate::protocol::types::primitive::int::Int;
+use crate::protocol::types::primitive::UInteger16;
+
+use std::time::{Duration, Instant};
+
+#[cfg(test)]
+mod tests;
+
+/// Message types that can be negotiated with REQUEST_UNICAST_TRANSMISSION
+pub const NEGOTIATED: [values::MessageType; 3] = [
+    values::MessageType::Announce,
+    values::MessageType::Sync,
+    values::MessageType::Delay_Resp,
+];
+
+/// Message types a master grants; the clock only originates Announce messages
+pub const SERVED: [values::MessageType; 1] = [values::MessageType::Announce];
+
+pub const DEFAULT_DURATION: u32 = 300;
+pub const DEFAULT_RETRY: Duration = Duration::from_secs(1);
+
+const UNICAST_FLAG: u8 = 0x04;
+
+/// Wraps negotiation TLVs in a unicast Signaling message
+pub fn signaling(
+    source: PortIdentity,
+    target: PortIdentity,
+    domain: u8,
+    sequence_id: u16,
+    tlvs: Vec<Tlv>,
+) -> Message {
+    let mut header = Header::new(
+        values::MessageType::Signaling,
+        0,
+        domain,
+        source,
+        sequence_id,
+        0x7F,
+    );
+    header.flagField[0] = Int::new(UNICAST_FLAG);
+    Message {
+        header,
+        body: Body::Signaling(body::Signaling {
+            targetPortIdentity: target,
+            tlvs,
+        }),
+    }
+    .finalize()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ClientState {
+    /// Request has to be sent on the next poll
+    Idle,
+    Requested {
+        at: Instant,
+    },
+    Granted {
+        until: Instant,
+        renew_at: Instant,
+    },
+    Denied {
+        retry_at: Instant,
+    },
+}
+
+#[derive(Debug, Clone)]
+struct ClientEntry<A> {
+    peer: A,
+    message_type: values::MessageType,
+    log_period: i8,
+    state: ClientState,
+}
+
+/// Grantee side of the negotiation, keeps the configured grants alive
+#[derive(Debug)]
+pub struct UnicastClient<A> {
+    duration: u32,
+    retry: Duration,
+    entries: Vec<ClientEntry<A>>,
+}
+
+impl<A: Clone + PartialEq> UnicastClient<A> {
+    pub fn new(duration: u32, retry: Duration) -> Self {
+        UnicastClient {
+            duration,
+            retry,
+            entries: Vec::new(),
+        }
+    }
+
+    /// Starts negotiating transmission of `message_type` from `peer`
+    pub fn request(&mut self, peer: A, message_type: values::MessageType, log_period: i8) {
+        match self.entry_mut(&peer, message_type) {
+            Some(entry) => {
+                entry.log_period = log_period;
+                entry.state = ClientState::Idle;
+            }
+            None => self.entries.push(ClientEntry {
+                peer,
+                message_type,
+                log_period,
+                state: ClientState::Idle,
+            }),
+        }
+    }
+
+    /// Stops negotiating with `peer` and returns the CANCEL TLV to send
+    pub fn cancel(&mut self, peer: &A, message_type: values::MessageType) -> Option<Tlv> {
+        let len = self.entries.len();
+        self.entries
+            .retain(|entry| !(entry.peer == *peer && entry.message_type == message_type));
+        if self.entries.len() != len {
+            Some(Tlv::Cancel_Unicast_Transmission(
+                tlv::Cancel_Unicast_Transmission::new(Enumeration::Enum(message_type)),
+            ))
+        } else {
+            None
+        }
+    }
+
+    pub fn state(&self, peer: &A, message_type: values::MessageType) -> Option<ClientState> {
+        self.entries
+            .iter()
+            .find(|entry| entry.peer == *peer && entry.message_type == message_type)
+            .map(|entry| entry.state)
+    }
+
+    pub fn is_granted(&self, peer: &A, message_type: values::MessageType, now: Instant) -> bool {
+        match self.state(peer, message_type) {
+            Some(ClientState::Granted { until, .. }) => now < until,
+            _ => false,
+        }
+    }
+
+    /// Returns the requests that are due: new ones, retries after a denial or
+    /// a missing response and renewals of grants about to expire
+    pub fn poll(&mut self, now: Instant) -> Vec<(A, Tlv)> {
+        let (duration, retry) = (self.duration, self.retry);
+        let mut out = Vec::new();
+        for entry in self.entries.iter_mut() {
+            let due = match entry.state {
+                ClientState::Idle => true,
+                ClientState::Requested { at } => now >= at + retry,
+                ClientState::Granted { renew_at, .. } => now >= renew_at,
+                ClientState::Denied { retry_at } => now >= retry_at,
+            };
+            if due {
+                out.push((
+                    entry.peer.clone(),
+                    Tlv::Request_Unicast_Transmission(tlv::Request_Unicast_Transmission::new(
+                        Enumeration::Enum(entry.message_type),
+                        entry.log_period,
+                        duration,
+                    )),
+                ));
+                entry.state = match entry.state {
+                    // Keep the current grant while the renewal is in flight
+                    ClientState::Granted { until, .. } if now < until => ClientState::Granted {
+                        until,
+                        renew_at: now + retry,
+                    },
+                    _ => ClientState::Requested { at: now },
+                };
+            }
+        }
+        out
+    }
+
+    /// Processes a TLV received from `peer`, returning the response if one is needed
+    pub fn handle(&mut self, peer: &A, tlv: &Tlv, now: Instant) -> Option<Tlv> {
+        let retry = self.retry;
+        match tlv {
+            Tlv::Grant_Unicast_Transmission(grant) => {
+                if let Enumeration::Enum(message_type) = grant.messageType {
+                    if let Some(entry) = self.entry_mut(peer, message_type) {
+                        let duration = grant.durationField.into_inner();
+                        entry.state = if duration == 0 {
+                            ClientState::Denied {
+                                retry_at: now + retry,
+                            }
+                        } else {
+                            ClientState::Granted {
+                                until: now + Duration::from_secs(duration.into()),
+                                renew_at: now + Duration::from_secs(duration.into()) / 2,
+                            }
+                        };
+                    }
+                }
+                None
+            }
+            Tlv::Cancel_Unicast_Transmission(cancel) => {
+                if let Enumeration::Enum(message_type) = cancel.messageType {
+                    if let Some(entry) = self.entry_mut(peer, message_type) {
+                        entry.state = ClientState::Denied {
+                            retry_at: now + retry,
+                        };
+                    }
+                }
+                Some(Tlv::Acknowledge_Cancel_Unicast_Transmission(
+                    tlv::Acknowledge_Cancel_Unicast_Transmission::new(cancel.messageType),
+                ))
+            }
+            _ => None,
+        }
+    }
+
+    fn entry_mut(
+        &mut self,
+        peer: &A,
+        message_type: values::MessageType,
+    ) -> Option<&mut ClientEntry<A>> {
+        self.entries
+            .iter_mut()
+            .find(|entry| entry.peer == *peer && entry.message_type == message_type)
+    }
+}
+
+/// Limits applied by the grantor to incoming requests
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Limits {
+    /// Maximum number of concurrent grants per message type
+    pub max_grants: usize,
+    /// Fastest logInterMessagePeriod that will be granted
+    pub min_log_period: i8,
+    /// Longest duration in seconds, longer requests are shortened
+    pub max_duration: u32,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_grants: 32,
+            min_log_period: -7,
+            max_duration: 1000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Grant<A> {
+    pub peer: A,
+    pub message_type: values::MessageType,
+    pub log_period: i8,
+    pub until: Instant,
+}
+
+/// Grantor side of the negotiation
+#[derive(Debug)]
+pub struct UnicastServer<A> {
+    limits: Limits,
+    grants: Vec<Grant<A>>,
+}
+
+impl<A: Clone + PartialEq> UnicastServer<A> {
+    pub fn new(limits: Limits) -> Self {
+        UnicastServer {
+            limits,
+            grants: Vec::new(),
+        }
+    }
+
+    /// Active grants for `message_type`, i.e. the peers to transmit to
+    pub fn grants(&self, message_type: values::MessageType) -> impl Iterator<Item = &Grant<A>> {
+        self.grants
+            .iter()
+            .filter(move |grant| grant.message_type == message_type)
+    }
+
+    /// Drops grants whose duration has passed
+    pub fn expire(&mut self, now: Instant) {
+        self.grants.retain(|grant| now < grant.until);
+    }
+
+    /// Cancels every active grant, e.g. when the port leaves MASTER state
+    pub fn cancel_all(&mut self) -> Vec<(A, Tlv)> {
+        self.grants
+            .drain(..)
+            .map(|grant| {
+                (
+                    grant.peer,
+                    Tlv::Cancel_Unicast_Transmission(tlv::Cancel_Unicast_Transmission::new(
+                        Enumeration::Enum(grant.message_type),
+                    )),
+                )
+            })
+            .collect()
+    }
+
+    /// Processes a TLV received from `peer`, returning the response if one is needed
+    pub fn handle(&mut self, peer: &A, tlv: &Tlv, now: Instant) -> Option<Tlv> {
+        match tlv {
+            Tlv::Request_Unicast_Transmission(request) => {
+                let log_period = request.logInterMessagePeriod.into_inner();
+                let duration = self.grant(
+                    peer,
+                    request.messageType,
+                    log_period,
+                    request.durationField.into_inner(),
+                    now,
+                );
+                Some(Tlv::Grant_Unicast_Transmission(
+                    tlv::Grant_Unicast_Transmission::new(
+                        request.messageType,
+                        log_period,
+                        duration,
+                        duration != 0,
+                    ),
+                ))
+            }
+            Tlv::Cancel_Unicast_Transmission(cancel) => {
+                if let Enumeration::Enum(message_type) = cancel.messageType {
+                    self.grants.retain(|grant| {
+                        !(grant.peer == *peer && grant.message_type == message_type)
+                    });
+                }
+                Some(Tlv::Acknowledge_Cancel_Unicast_Transmission(
+                    tlv::Acknowledge_Cancel_Unicast_Transmission::new(cancel.messageType),
+                ))
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the granted duration, 0 meaning the request is denied
+    fn grant(
+        &mut self,
+        peer: &A,
+        message_type: MessageType,
+        log_period: i8,
+        duration: u32,
+        now: Instant,
+    ) -> u32 {
+        let message_type = match message_type {
+            Enumeration::Enum(message_type) if SERVED.contains(&message_type) => message_type,
+            _ => return 0,
+        };
+        if log_period < self.limits.min_log_period {
+            return 0;
+        }
+        let duration = duration.min(self.limits.max_duration);
+        let until = now + Duration::from_secs(duration.into());
+        if let Some(grant) = self
+            .grants
+            .iter_mut()
+            .find(|grant| grant.peer == *peer && grant.message_type == message_type)
+        {
+            grant.log_period = log_period;
+            grant.until = until;
+            return duration;
+        }
+        if duration == 0 || self.grants(message_type).count() >= self.limits.max_grants {
+            return 0;
+        }
+        self.grants.push(Grant {
+            peer: peer.clone(),
+            message_type,
+            log_period,
+            until,
+        });
+        duration
+    }
+}
+
+/// Whether a message targeted at `target` is meant for port `port`
+fn targets(target: &PortIdentity, port: &PortIdentity) -> bool {
+    let all = all_ports();
+    (target.clockIdentity == port.clockIdentity || target.clockIdentity == all.clockIdentity)
+        && (target.portNumber == port.portNumber || target.portNumber == all.portNumber)
+}
+
+/// Negotiation of one port: the client keeps the grants of the configured master
+/// alive, the server grants requests of other ports when enabled
+#[derive(Debug)]
+pub struct Negotiation<A> {
+    pub client: UnicastClient<A>,
+    pub server: Option<UnicastServer<A>>,
+    sequence_id: UInteger16,
+}
+
+impl<A: Clone + PartialEq> Negotiation<A> {
+    /// Requests Announce, Sync and Delay_Resp from `master` at the port's intervals,
+    /// grants requests if `unicast_listen` is set
+    pub fn new(master: Option<A>, clock: &ClockConfig, port: &PortConfig) -> Self {
+        let mut client = UnicastClient::new(clock.unicast_req_duration, DEFAULT_RETRY);
+        if let Some(master) = master {
+            client.request(
+                master.clone(),
+                values::MessageType::Announce,
+                port.log_announce_interval,
+            );
+            client.request(
+                master.clone(),
+                values::MessageType::Sync,
+                port.log_sync_interval,
+            );
+            client.request(
+                master,
+                values::MessageType::Delay_Resp,
+                port.log_min_delay_req_interval,
+            );
+        }
+        Negotiation {
+            client,
+            server: if clock.unicast_listen {
+                Some(UnicastServer::new(clock.unicast_limits))
+            } else {
+                None
+            },
+            sequence_id: UInteger16::default(),
+        }
+    }
+
+    fn signaling(
+        &mut self,
+        source: PortIdentity,
+        target: PortIdentity,
+        domain: u8,
+        tlvs: Vec<Tlv>,
+    ) -> Message {
+        let message = signaling(source, target, domain, self.sequence_id.into_inner(), tlvs);
+        self.sequence_id = self.sequence_id.wrapping_add(Int::new(1));
+        message
+    }
+
+    /// Signaling messages due at `now` from port `source`: requests and renewals,
+    /// and cancellations of the grants once the port is no longer master
+    pub fn poll(
+        &mut self,
+        source: PortIdentity,
+        domain: u8,
+        master: bool,
+        now: Instant,
+    ) -> Vec<(A, Message)> {
+        let mut tlvs = self.client.poll(now);
+        if let Some(server) = &mut self.server {
+            server.expire(now);
+            if !master {
+                tlvs.extend(server.cancel_all());
+            }
+        }
+        tlvs.into_iter()
+            .map(|(peer, tlv)| {
+                let message = self.signaling(source, all_ports(), domain, vec![tlv]);
+                (peer, message)
+            })
+            .collect()
+    }
+
+    /// Processes a message received from `peer` on port `source`, returning the
+    /// Signaling response if one is needed
+    pub fn handle(
+        &mut self,
+        source: PortIdentity,
+        peer: &A,
+        message: &Message,
+        now: Instant,
+    ) -> Option<Message> {
+        let tlvs = match &message.body {
+            Body::Signaling(signaling) if targets(&signaling.targetPortIdentity, &source) => {
+                &signaling.tlvs
+            }
+            _ => return None,
+        };
+        let (client, server) = (&mut self.client, &mut self.server);
+        let responses: Vec<_> = tlvs
+            .iter()
+            .filter_map(|tlv| {
+                let client = client.handle(peer, tlv, now);
+                let server = server
+                    .as_mut()
+                    .and_then(|server| server.handle(peer, tlv, now));
+                // Both sides acknowledge a cancellation, one acknowledgement is enough
+                server.or(client)
+            })
+            .collect();
+        if responses.is_empty() {
+            return None;
+        }
+        Some(self.signaling(
+            source,
+            message.header.sourcePortIdentity,
+            message.header.domainNumber.into_inner(),
+            responses,
+        ))
+    }
+}
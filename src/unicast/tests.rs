@@ -0,0 +1,161 @@
+use super::*;
+
+#[test]
+pub fn test_negotiation() {
+    let start = Instant::now();
+    let mut client = UnicastClient::new(60, DEFAULT_RETRY);
+    let mut server = UnicastServer::new(Limits {
+        max_grants: 1,
+        ..Limits::default()
+    });
+
+    client.request("master", values::MessageType::Announce, 0);
+    let requests = client.poll(start);
+    assert_eq!(requests.len(), 1);
+    assert!(client.poll(start).is_empty());
+
+    let grant = server.handle(&"slave1", &requests[0].1, start).unwrap();
+    assert_eq!(server.grants(values::MessageType::Announce).count(), 1);
+    assert_eq!(client.handle(&"master", &grant, start), None);
+    assert!(client.is_granted(&"master", values::MessageType::Announce, start));
+
+    // Renewed halfway through the grant
+    let later = start + Duration::from_secs(30);
+    let renewals = client.poll(later);
+    assert_eq!(renewals.len(), 1);
+    assert!(client.is_granted(&"master", values::MessageType::Announce, later));
+    server.handle(&"slave1", &renewals[0].1, later).unwrap();
+    assert_eq!(server.grants(values::MessageType::Announce).count(), 1);
+
+    // Second grantee exceeds the limit
+    match server.handle(&"slave2", &requests[0].1, start) {
+        Some(Tlv::Grant_Unicast_Transmission(grant)) => {
+            assert_eq!(grant.durationField.into_inner(), 0);
+            assert!(!grant.renewal_invited());
+        }
+        other => panic!("Unexpected response: {:?}", other),
+    }
+
+    server.expire(start + Duration::from_secs(60));
+    assert_eq!(server.grants(values::MessageType::Announce).count(), 1);
+    server.expire(later + Duration::from_secs(60));
+    assert_eq!(server.grants(values::MessageType::Announce).count(), 0);
+}
+
+#[test]
+pub fn test_denial_and_cancel() {
+    let start = Instant::now();
+    let mut client = UnicastClient::new(DEFAULT_DURATION, DEFAULT_RETRY);
+    let mut server = UnicastServer::new(Limits {
+        min_log_period: -4,
+        ..Limits::default()
+    });
+
+    client.request(1, values::MessageType::Announce, -5);
+    let request = client.poll(start).pop().unwrap();
+    let deny = server.handle(&2, &request.1, start).unwrap();
+    client.handle(&1, &deny, start);
+    assert_eq!(
+        client.state(&1, values::MessageType::Announce),
+        Some(ClientState::Denied {
+            retry_at: start + DEFAULT_RETRY
+        })
+    );
+    assert_eq!(client.poll(start + DEFAULT_RETRY).len(), 1);
+
+    // Sync and Delay_Resp are not served
+    for message_type in &[values::MessageType::Sync, values::MessageType::Delay_Resp] {
+        client.request(1, *message_type, 0);
+        let request = client.poll(start).pop().unwrap();
+        match server.handle(&2, &request.1, start) {
+            Some(Tlv::Grant_Unicast_Transmission(grant)) => {
+                assert_eq!(grant.durationField.into_inner(), 0)
+            }
+            other => panic!("Unexpected response: {:?}", other),
+        }
+        assert_eq!(server.grants(*message_type).count(), 0);
+    }
+
+    client.request(1, values::MessageType::Announce, 1);
+    let request = client.poll(start).pop().unwrap();
+    let grant = server.handle(&2, &request.1, start).unwrap();
+    client.handle(&1, &grant, start);
+    assert!(client.is_granted(&1, values::MessageType::Announce, start));
+
+    let cancel = client.cancel(&1, values::MessageType::Announce).unwrap();
+    assert_eq!(
+        server.handle(&2, &cancel, start),
+        Some(Tlv::Acknowledge_Cancel_Unicast_Transmission(
+            tlv::Acknowledge_Cancel_Unicast_Transmission::new(Enumeration::Enum(
+                values::MessageType::Announce
+            ))
+        ))
+    );
+    assert_eq!(server.grants(values::MessageType::Announce).count(), 0);
+    assert_eq!(client.state(&1, values::MessageType::Announce), None);
+}
+
+#[test]
+pub fn test_port_negotiation() {
+    use crate::config::{ClockConfig, PortConfig};
+    use crate::protocol::types::primitive::int::Int;
+
+    let start = Instant::now();
+    let port = |clock: u8| PortIdentity {
+        clockIdentity: [Int::new(clock); 8],
+        portNumber: Int::new(1),
+    };
+    let clock_config = ClockConfig {
+        unicast_listen: true,
+        ..ClockConfig::default()
+    };
+    let mut slave = Negotiation::new(
+        Some("master"),
+        &ClockConfig::default(),
+        &PortConfig::default(),
+    );
+    let mut master: Negotiation<&str> =
+        Negotiation::new(None, &clock_config, &PortConfig::default());
+
+    // Announce, Sync and Delay_Resp are requested in separate messages
+    let requests = slave.poll(port(1), 0, false, start);
+    assert_eq!(requests.len(), 3);
+    assert!(master.poll(port(2), 0, true, start).is_empty());
+    for (peer, request) in &requests {
+        assert_eq!(*peer, "master");
+        // Requests for another port are ignored
+        let mut misdirected = request.clone();
+        if let Body::Signaling(signaling) = &mut misdirected.body {
+            signaling.targetPortIdentity = port(3);
+        }
+        assert_eq!(master.handle(port(2), &"slave", &misdirected, start), None);
+        let grant = master.handle(port(2), &"slave", request, start).unwrap();
+        assert_eq!(grant.header.sourcePortIdentity, port(2));
+        match &grant.body {
+            Body::Signaling(signaling) => assert_eq!(signaling.targetPortIdentity, port(1)),
+            _ => panic!("Expected Signaling"),
+        }
+        assert_eq!(slave.handle(port(1), &"master", &grant, start), None);
+    }
+    for message_type in NEGOTIATED.iter() {
+        let served = SERVED.contains(message_type);
+        assert_eq!(
+            slave.client.is_granted(&"master", *message_type, start),
+            served
+        );
+        let grants = master.server.as_ref().unwrap().grants(*message_type);
+        let expected = if served { vec!["slave"] } else { vec![] };
+        assert_eq!(grants.map(|grant| grant.peer).collect::<Vec<_>>(), expected);
+    }
+
+    // Grants are cancelled once the master leaves MASTER state
+    let cancels = master.poll(port(2), 0, false, start);
+    assert_eq!(cancels.len(), 1);
+    let ack = slave
+        .handle(port(1), &"master", &cancels[0].1, start)
+        .unwrap();
+    assert_eq!(master.handle(port(2), &"slave", &ack, start), None);
+    assert!(!slave
+        .client
+        .is_granted(&"master", values::MessageType::Announce, start));
+}
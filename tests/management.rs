@@ -0,0 +1,227 @@
+use std::os::unix::net::UnixDatagram;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use ptp::clock::Clock;
+use ptp::config::{ClockConfig, DelayMechanism, PortConfig};
+use ptp::management::server::UdsServer;
+use ptp::management::{all_ports, management, Request};
+use ptp::protocol::parser::parse_ptp_message;
+use ptp::protocol::types::derived::PortIdentity;
+use ptp::protocol::types::enums::{values, Enumeration};
+use ptp::protocol::types::message::tlv::{ManagementData, Value};
+use ptp::protocol::types::message::{Body, Header, Message, Tlv};
+use ptp::protocol::types::primitive::int::Int;
+
+struct Client {
+    socket: UnixDatagram,
+    path: PathBuf,
+    source: PortIdentity,
+    sequence_id: u16,
+}
+
+impl Client {
+    fn new(dir: &Path, server: &Path) -> Self {
+        let path = dir.join("client");
+        let _ = std::fs::remove_file(&path);
+        let socket = UnixDatagram::bind(&path).unwrap();
+        socket.connect(server).unwrap();
+        socket
+            .set_read_timeout(Some(Duration::from_millis(100)))
+            .unwrap();
+        Client {
+            socket,
+            path,
+            source: PortIdentity {
+                clockIdentity: [Int::new(0); 8],
+                portNumber: Int::new(1),
+            },
+            sequence_id: 0,
+        }
+    }
+
+    fn send(&mut self, target: PortIdentity, command: &str) {
+        let request: Request = command.parse().unwrap();
+        let message = management(
+            self.source,
+            target,
+            0,
+            self.sequence_id,
+            0,
+            request.action,
+            vec![request.tlv()],
+        );
+        self.socket.send(&message.to_bytes()).unwrap();
+        self.sequence_id += 1;
+    }
+
+    fn recv(&self) -> Vec<Message> {
+        let mut responses = Vec::new();
+        let mut buf = [0; 1500];
+        while let Ok(len) = self.socket.recv(&mut buf) {
+            let (_, message) = parse_ptp_message(&buf[..len]).unwrap();
+            assert_eq!(
+                message.header.sourcePortIdentity.clockIdentity,
+                [Int::new(1); 8]
+            );
+            responses.push(message);
+        }
+        responses
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Action and TLV of a management response
+fn response(message: &Message) -> (values::ActionField, Tlv) {
+    match &message.body {
+        Body::Management(body) => match body.actionField {
+            Enumeration::Enum(action) => (action, body.tlvs[0].clone()),
+            Enumeration::Unknown(_) => panic!("Unknown action"),
+        },
+        _ => panic!("Expected Management message"),
+    }
+}
+
+fn data(message: &Message) -> ManagementData {
+    match response(message) {
+        (values::ActionField::RESPONSE, Tlv::Management(tlv)) => tlv.dataField,
+        response => panic!("Unexpected response {:?}", response),
+    }
+}
+
+#[test]
+fn test_uds_management() {
+    let dir = std::env::temp_dir().join(format!("ptp-management-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let server_path = dir.join("ptp");
+    let now = Instant::now();
+    let mut clock = Clock::new(
+        [Int::new(1); 8],
+        ClockConfig::default(),
+        &[PortConfig::default(); 2],
+        now,
+    );
+    // Only a stale socket is replaced
+    std::fs::write(&server_path, "not a socket").unwrap();
+    assert!(UdsServer::bind(&server_path).is_err());
+    std::fs::remove_file(&server_path).unwrap();
+    drop(UnixDatagram::bind(&server_path).unwrap());
+    let server = UdsServer::bind(&server_path).unwrap();
+    let mut client = Client::new(&dir, &server_path);
+    let port = |number| PortIdentity {
+        clockIdentity: [Int::new(1); 8],
+        portNumber: Int::new(number),
+    };
+
+    client.send(all_ports(), "GET DEFAULT_DATA_SET");
+    server.serve(&mut clock, now).unwrap();
+    let responses = client.recv();
+    assert_eq!(responses.len(), 1);
+    assert_eq!(
+        data(&responses[0]),
+        ManagementData::Default_Data_Set(clock.default_ds)
+    );
+
+    client.send(all_ports(), "GET PORT_DATA_SET");
+    client.send(all_ports(), "GET TIME_PROPERTIES_DATA_SET");
+    server.serve(&mut clock, now).unwrap();
+    let responses = client.recv();
+    assert_eq!(responses.len(), 3);
+    assert_eq!(responses[1].header.sourcePortIdentity, port(2));
+    assert_eq!(
+        data(&responses[1]),
+        ManagementData::Port_Data_Set(clock.ports[1].dataset())
+    );
+    assert_eq!(
+        data(&responses[2]),
+        ManagementData::Time_Properties_Data_Set(clock.time_properties_ds)
+    );
+
+    client.send(all_ports(), "SET PRIORITY1 100");
+    server.serve(&mut clock, now).unwrap();
+    assert_eq!(
+        data(&client.recv()[0]),
+        ManagementData::Priority1(Value::new(Int::new(100)))
+    );
+    assert_eq!(clock.default_ds.priority1, Int::new(100));
+    assert_eq!(clock.config.priority1, 100);
+
+    client.send(port(2), "SET LOG_SYNC_INTERVAL -3");
+    server.serve(&mut clock, now).unwrap();
+    assert_eq!(client.recv().len(), 1);
+    assert_eq!(clock.ports[0].config.log_sync_interval, 0);
+    assert_eq!(clock.ports[1].config.log_sync_interval, -3);
+
+    // Intervals the timers can't hold are refused, valid ones restart them
+    client.send(port(1), "SET LOG_ANNOUNCE_INTERVAL 100");
+    server.serve(&mut clock, now).unwrap();
+    match response(&client.recv()[0]) {
+        (values::ActionField::RESPONSE, Tlv::Management_Error_Status(tlv)) => assert_eq!(
+            tlv.managementErrorId,
+            Enumeration::Enum(values::ManagementErrorId::WRONG_VALUE)
+        ),
+        response => panic!("Unexpected response {:?}", response),
+    }
+    assert_eq!(clock.ports[0].config.log_announce_interval, 1);
+    client.send(port(1), "SET LOG_ANNOUNCE_INTERVAL 3");
+    server.serve(&mut clock, now).unwrap();
+    assert_eq!(client.recv().len(), 1);
+    assert_eq!(clock.ports[0].next_announce, now + Duration::from_secs(8));
+    assert_eq!(
+        clock.ports[0].announce_receipt_deadline,
+        now + Duration::from_secs(8 * 3)
+    );
+
+    client.send(all_ports(), "SET DOMAIN 4");
+    server.serve(&mut clock, now).unwrap();
+    assert_eq!(client.recv().len(), 1);
+    assert_eq!(clock.domain_number(), 4);
+    assert_eq!(clock.config.domain_number, 4);
+    // Requests for other domains are ignored
+    client.send(all_ports(), "GET DOMAIN");
+    server.serve(&mut clock, now).unwrap();
+    assert!(client.recv().is_empty());
+    clock.default_ds.domainNumber = Int::new(0);
+
+    // Auto reports E2E until a peer sends Pdelay_Req
+    clock.ports[0].config.delay_mechanism = DelayMechanism::Auto;
+    client.send(port(1), "GET DELAY_MECHANISM");
+    server.serve(&mut clock, now).unwrap();
+    assert_eq!(
+        data(&client.recv()[0]),
+        ManagementData::Delay_Mechanism(Value::new(values::DelayMechanism::E2E.into()))
+    );
+    let peer = PortIdentity {
+        clockIdentity: [Int::new(2); 8],
+        portNumber: Int::new(1),
+    };
+    let pdelay_req = Message {
+        header: Header::new(values::MessageType::Pdelay_Req, 54, 0, peer, 0, 0x7F),
+        body: Body::Empty,
+    };
+    clock.handle(0, &pdelay_req, now);
+    assert_eq!(
+        clock.ports[0].dataset().delayMechanism,
+        Enumeration::Enum(values::DelayMechanism::P2P)
+    );
+
+    client.send(all_ports(), "SET SLAVE_ONLY 1");
+    server.serve(&mut clock, now).unwrap();
+    match response(&client.recv()[0]) {
+        (values::ActionField::RESPONSE, Tlv::Management_Error_Status(tlv)) => assert_eq!(
+            tlv.managementErrorId,
+            Enumeration::Enum(values::ManagementErrorId::NOT_SETABLE)
+        ),
+        response => panic!("Unexpected response {:?}", response),
+    }
+
+    drop(server);
+    assert!(!server_path.exists());
+    drop(client);
+    std::fs::remove_dir(&dir).unwrap();
+}